@@ -0,0 +1,5 @@
+CREATE TABLE 'guild_settings' (
+	'guild_id' BIGINT NOT NULL,
+	'self_deafen' BOOLEAN NOT NULL DEFAULT 1,
+	PRIMARY KEY ('guild_id')
+) WITHOUT ROWID;
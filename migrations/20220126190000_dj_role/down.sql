@@ -0,0 +1,8 @@
+CREATE TABLE 'guild_settings_old' (
+	'guild_id' BIGINT NOT NULL,
+	'self_deafen' BOOLEAN NOT NULL DEFAULT 1,
+	PRIMARY KEY ('guild_id')
+) WITHOUT ROWID;
+INSERT INTO 'guild_settings_old' SELECT guild_id, self_deafen FROM 'guild_settings';
+DROP TABLE 'guild_settings';
+ALTER TABLE 'guild_settings_old' RENAME TO 'guild_settings';
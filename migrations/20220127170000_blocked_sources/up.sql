@@ -0,0 +1,5 @@
+CREATE TABLE 'blocked_sources' (
+	'guild_id' BIGINT NOT NULL,
+	'source' VARCHAR NOT NULL,
+	PRIMARY KEY ('guild_id', 'source')
+) WITHOUT ROWID;
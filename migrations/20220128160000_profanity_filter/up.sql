@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'profanity_filter' BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+CREATE TABLE 'guild_settings_old' (
+	'guild_id' BIGINT NOT NULL,
+	'self_deafen' BOOLEAN NOT NULL DEFAULT 1,
+	'dj_role_id' BIGINT,
+	'profanity_filter' BOOLEAN NOT NULL DEFAULT 0,
+	PRIMARY KEY ('guild_id')
+) WITHOUT ROWID;
+INSERT INTO 'guild_settings_old' SELECT guild_id, self_deafen, dj_role_id, profanity_filter FROM 'guild_settings';
+DROP TABLE 'guild_settings';
+ALTER TABLE 'guild_settings_old' RENAME TO 'guild_settings';
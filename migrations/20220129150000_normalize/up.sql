@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'normalize' BOOLEAN NOT NULL DEFAULT 0;
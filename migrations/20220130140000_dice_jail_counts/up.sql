@@ -0,0 +1,6 @@
+CREATE TABLE 'dice_jail_counts' (
+	'guild_id' BIGINT NOT NULL,
+	'user_id' BIGINT NOT NULL,
+	'count' INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY ('guild_id', 'user_id')
+) WITHOUT ROWID;
@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'mute_segments' BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'search_results' INTEGER NOT NULL DEFAULT 1;
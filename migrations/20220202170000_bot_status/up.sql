@@ -0,0 +1,6 @@
+CREATE TABLE 'bot_status' (
+	'id' INTEGER NOT NULL CHECK (id = 0),
+	'status_type' TEXT NOT NULL,
+	'status_text' TEXT NOT NULL,
+	PRIMARY KEY ('id')
+);
@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'crit_reactions' BOOLEAN NOT NULL DEFAULT 1;
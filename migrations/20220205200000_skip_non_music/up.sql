@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'skip_non_music' BOOLEAN NOT NULL DEFAULT 1;
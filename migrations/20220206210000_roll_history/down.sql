@@ -0,0 +1 @@
+DROP TABLE 'roll_history';
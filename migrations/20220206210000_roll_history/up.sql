@@ -0,0 +1,9 @@
+CREATE TABLE 'roll_history' (
+	'id' INTEGER NOT NULL,
+	'guild_id' BIGINT NOT NULL,
+	'user_id' BIGINT NOT NULL,
+	'die_size' INTEGER NOT NULL,
+	'die_value' INTEGER NOT NULL,
+	PRIMARY KEY ('id')
+);
+CREATE INDEX 'roll_history_guild_id' ON 'roll_history' ('guild_id');
@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'compact_now_playing' BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE 'guild_settings' ADD COLUMN 'vote_skip_threshold' INTEGER;
@@ -0,0 +1,5 @@
+DROP TABLE bot_status;
+DROP TABLE dice_jail_counts;
+DROP TABLE blocked_sources;
+DROP TABLE guild_settings;
+DROP TABLE saved_rolls;
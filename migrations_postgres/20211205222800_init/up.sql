@@ -0,0 +1,40 @@
+CREATE TABLE saved_rolls (
+	guild_id BIGINT NOT NULL,
+	user_id BIGINT NOT NULL,
+	name VARCHAR NOT NULL,
+	command VARCHAR NOT NULL,
+	PRIMARY KEY (guild_id, user_id, name)
+);
+
+CREATE TABLE guild_settings (
+	guild_id BIGINT NOT NULL,
+	self_deafen BOOLEAN NOT NULL DEFAULT TRUE,
+	dj_role_id BIGINT,
+	profanity_filter BOOLEAN NOT NULL DEFAULT FALSE,
+	normalize BOOLEAN NOT NULL DEFAULT FALSE,
+	mute_segments BOOLEAN NOT NULL DEFAULT FALSE,
+	search_results INTEGER NOT NULL DEFAULT 1,
+	volume INTEGER,
+	crit_reactions BOOLEAN NOT NULL DEFAULT TRUE,
+	PRIMARY KEY (guild_id)
+);
+
+CREATE TABLE blocked_sources (
+	guild_id BIGINT NOT NULL,
+	source VARCHAR NOT NULL,
+	PRIMARY KEY (guild_id, source)
+);
+
+CREATE TABLE dice_jail_counts (
+	guild_id BIGINT NOT NULL,
+	user_id BIGINT NOT NULL,
+	count INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE bot_status (
+	id INTEGER NOT NULL CHECK (id = 0),
+	status_type TEXT NOT NULL,
+	status_text TEXT NOT NULL,
+	PRIMARY KEY (id)
+);
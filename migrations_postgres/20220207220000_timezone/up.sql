@@ -0,0 +1 @@
+ALTER TABLE guild_settings ADD COLUMN timezone VARCHAR NOT NULL DEFAULT 'UTC';
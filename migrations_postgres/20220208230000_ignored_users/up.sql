@@ -0,0 +1,5 @@
+CREATE TABLE ignored_users (
+	guild_id BIGINT NOT NULL,
+	user_id BIGINT NOT NULL,
+	PRIMARY KEY (guild_id, user_id)
+);
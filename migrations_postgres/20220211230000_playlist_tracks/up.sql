@@ -0,0 +1,8 @@
+CREATE TABLE playlist_tracks (
+	guild_id BIGINT NOT NULL,
+	user_id BIGINT NOT NULL,
+	name VARCHAR NOT NULL,
+	position INTEGER NOT NULL,
+	uri VARCHAR NOT NULL,
+	PRIMARY KEY (guild_id, user_id, name, position)
+);
@@ -2,23 +2,60 @@
 mod roll;
 
 // Uses
-use std::borrow::Cow;
+use std::{
+	borrow::Cow,
+	cmp::Ordering,
+	sync::{Arc, PoisonError},
+	time::{Duration, Instant},
+};
 
 use anyhow::Context;
+#[cfg(feature = "postgres")]
+use diesel::pg::upsert::excluded;
 use diesel::{
 	delete,
-	replace_into,
+	dsl::sql,
+	insert_into,
+	sql_types::{BigInt, Double},
+	update,
+	Connection,
 	ExpressionMethods,
+	GroupByDsl,
+	OptionalExtension,
 	QueryDsl,
+	QueryResult,
 	RunQueryDsl,
 	TextExpressionMethods,
 };
-use poise::{command, serenity::model::misc::Mentionable};
-
-use self::roll::{evaluate_roll_rpn, parse_roll_command, Dice};
+#[cfg(feature = "sqlite")]
+use diesel::{insert_or_ignore_into, replace_into};
+use lru::LruCache;
+use poise::serenity::model::{channel::ReactionType, id::UserId, misc::Mentionable};
+
+use self::roll::{
+	check_roll_rpn,
+	evaluate_roll_rpn,
+	exceeds_precision,
+	parse_roll_command,
+	show_roll_structure,
+	show_roll_work,
+	Dice,
+	DiceRolls,
+	Evaluable,
+	EvaluateRollError,
+	ParseRollError,
+	MAX_DICE_PER_COMMAND,
+	OPERATOR_SYMBOLS,
+};
 use crate::{
-	db::{models::SavedRoll, schema::*},
-	util::{escape_str, is_application_context, reply, reply_embed, reply_plain},
+	db::{
+		get_guild_settings,
+		models::{RolledDie, SavedRoll},
+		schema::*,
+		DbConnection,
+	},
+	util::{confirm, escape_str, is_application_context, reply, reply_embed, reply_plain},
+	Data,
 	Error,
 	PoiseContext,
 };
@@ -26,21 +63,118 @@ use crate::{
 // Constants
 const ANNOTATION_CHAR: char = '!';
 const MAX_FIELD_VALUE: usize = 1024;
+const PRECISION_WARNING: &str = "*result exceeds precision*";
+const TOO_LARGE_MESSAGE: &str = "Your roll is too large to evaluate.";
+/// How long someone has to wait before they can put their dice in dice jail
+/// again.
+const DICE_JAIL_COOLDOWN: Duration = Duration::from_secs(30);
+/// Marks the dice in a dice pool that succeeded.
+const SUCCESS_MARK: char = '\u{2713}';
+/// Marks the dice in a dice pool that took away a success.
+const BOTCH_MARK: char = '\u{2717}';
+/// Marks the dice that were kept, for rolls that only keep the best or worst
+/// few.
+const KEPT_MARK: char = '\u{2605}';
+/// The option that saves a roll over an existing one without asking first.
+const FORCE_OPTION: &str = "--force";
+/// How long someone has to confirm saving over an existing roll.
+const OVERWRITE_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// The option that shows the roll with its dice values substituted in.
+const SHOW_WORK_OPTION: &str = "--show-work";
+/// The longest the worked-out roll can be before it's left out.
+const MAX_WORK_LENGTH: usize = 500;
+/// The size of the die that gets a reaction on a critical roll.
+const CRIT_DIE_SIZE: u32 = 20;
+const CRIT_SUCCESS_REACTION: &str = "\u{1f389}";
+const CRIT_FAILURE_REACTION: &str = "\u{1f480}";
+/// Separates the two rolls given to `rollvs`.
+const VERSUS_SEPARATOR: &str = "vs";
+/// How many parsed saved rolls are kept around.
+const PARSED_ROLL_CACHE_SIZE: usize = 1024;
+
+// Definitions
+/// Saved roll commands that have already been parsed, keyed by their guild ID,
+/// user ID, and name.
+///
+/// Entries have to be forgotten whenever the saved roll changes. Only the most
+/// recently run saved rolls are kept, so this doesn't grow forever.
+#[derive(Debug)]
+pub struct ParsedRollCache {
+	parsed_rolls: LruCache<(i64, i64, String), Arc<Vec<Evaluable>>>,
+}
+
+impl ParsedRollCache {
+	#[must_use]
+	pub fn new() -> Self {
+		Self {
+			parsed_rolls: LruCache::new(PARSED_ROLL_CACHE_SIZE),
+		}
+	}
+
+	pub fn get(&mut self, guild_id: i64, user_id: i64, name: &str) -> Option<Arc<Vec<Evaluable>>> {
+		self.parsed_rolls
+			.get(&(guild_id, user_id, name.to_owned()))
+			.cloned()
+	}
+
+	pub fn insert(&mut self, guild_id: i64, user_id: i64, name: &str, rpn: Arc<Vec<Evaluable>>) {
+		self.parsed_rolls
+			.put((guild_id, user_id, name.to_owned()), rpn);
+	}
+
+	/// Forgets the parsed form of a saved roll, for when it's changed or
+	/// deleted.
+	pub fn forget(&mut self, guild_id: i64, user_id: i64, name: &str) {
+		self.parsed_rolls.pop(&(guild_id, user_id, name.to_owned()));
+	}
+}
 
 // Commands
 
+panic_safe! {
 /// Roll as many dice as you want, and do whatever math you need to do with
 /// their roll results.
 ///
 /// Dice rolls are specified as `<count>d<size>`, eg. `2d8`. If the count is 1,
-/// you can leave it off. (eg. `d20`)
+/// you can leave it off. (eg. `d20`) Use `d%` for a `d100`.
 ///
 /// Dice rolls also support (dis)advantage. Simply put a `b` (for best) or `w`
 /// (for worst) on the end of the roll, eg. `3d10b2`. Again, if you only want
 /// the best 1, you can leave it off. (eg. `2d20w` for disadvantage)
 ///
+/// For Great Weapon Fighting, put `gwf` on the end of the roll, eg. `2d6gwf`.
+/// Any die that lands on a 1 or 2 is rerolled once, and the new value is kept.
+///
+/// To reroll low dice, put `r` and the highest value to reroll after the dice
+/// size, eg. `4d6r1`. Dice are rerolled until they land higher than that, or
+/// only once with `ro`, eg. `4d6ro1`. Rerolls happen before the best or worst
+/// dice are kept.
+///
+/// For exploding dice, put `!` after the dice size, eg. `3d6!`. Any die that
+/// lands on its maximum is rolled again and the new value is added to it, for
+/// as long as it keeps landing on its maximum. If the roll ends with exploding
+/// dice, leave a space before the `!` that starts the annotation.
+///
+/// For dice pools, put `>` and a target number on the end of the roll, eg.
+/// `6d10>7`. Instead of adding the dice up, this counts how many of them meet
+/// the target number. To have botches take away successes, put `f` and the
+/// highest value that botches after the target number, eg. `6d10>7f1`.
+///
+/// To add a bonus to each die instead of the total, put `+e` and the bonus on
+/// the very end of the roll, eg. `3d6+e1`. The bonus can take a die past its
+/// size, and it's applied before the best or worst dice are kept.
+///
 /// You can do whatever math you want with the dice values, or even do pure math
-/// with no dice involved. (eg. `/roll (2d20b + 1d8) ^ 2 / 3`)
+/// with no dice involved. (eg. `/roll (2d20b + 1d8) ^ 2 / 3`) Use `\` to
+/// divide and round down.
+///
+/// There are a couple of shorthands for common rules. `crit` doubles the dice
+/// right after it, so `crit 2d6 + 3` rolls `4d6 + 3`. `half` halves what's
+/// right after it and rounds down, so `half (8d6)` is `(8d6) \ 2`. Use
+/// parentheses to apply them to more than one thing.
+///
+/// Start the roll with `--show-work` to see the math with the dice values
+/// filled in, eg. `((4+5) + 3) * 2` for `(2d6 + 3) * 2`.
 #[command(
 	prefix_command,
 	slash_command,
@@ -51,23 +185,75 @@ pub async fn roll(
 	ctx: PoiseContext<'_>,
 	#[rest]
 	#[description = "The dice to roll. Follow the command with `!` to annotate what the roll is \
-	                 for."]
+					 for."]
 	command: String,
 ) -> Result<(), Error> {
 	// Parse the raw command string into clean, meaningful slices
-	let annotation_index = command.find(ANNOTATION_CHAR);
+	let annotation_index = find_annotation(command.as_str());
 	let command_slice = match annotation_index {
 		Some(index) => command[0..index].trim(),
 		None => command.trim(),
 	};
 	let annotation_slice = annotation_index.map(|index| command[(index + 1)..].trim());
+	let (command_slice, show_work) = if let Some(remaining) = command_slice
+		.strip_prefix(SHOW_WORK_OPTION)
+		.filter(|remaining| remaining.is_empty() || remaining.starts_with(char::is_whitespace))
+	{
+		(remaining.trim(), true)
+	} else {
+		(command_slice, false)
+	};
 
 	// Execute the command
-	execute_roll(ctx, command_slice, annotation_slice, false).await?;
+	execute_roll(ctx, command_slice, annotation_slice, false, show_work).await?;
 
 	Ok(())
 }
+}
 
+panic_safe! {
+/// Roll a d20 with advantage, taking the better of two rolls.
+///
+/// This is the same as `/roll 2d20b + <modifier>`.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	aliases("advantage")
+)]
+pub async fn adv(
+	ctx: PoiseContext<'_>,
+	#[description = "The modifier to add to the roll."] modifier: Option<i32>,
+	#[rest]
+	#[description = "What the roll is for."]
+	annotation: Option<String>,
+) -> Result<(), Error> {
+	execute_d20_pair_roll(ctx, 'b', modifier, annotation).await
+}
+}
+
+panic_safe! {
+/// Roll a d20 with disadvantage, taking the worse of two rolls.
+///
+/// This is the same as `/roll 2d20w + <modifier>`.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	aliases("disadvantage")
+)]
+pub async fn dis(
+	ctx: PoiseContext<'_>,
+	#[description = "The modifier to add to the roll."] modifier: Option<i32>,
+	#[rest]
+	#[description = "What the roll is for."]
+	annotation: Option<String>,
+) -> Result<(), Error> {
+	execute_d20_pair_roll(ctx, 'w', modifier, annotation).await
+}
+}
+
+panic_safe! {
 /// Batch roll the same command multiple times.
 #[command(
 	prefix_command,
@@ -81,7 +267,7 @@ pub async fn batch_roll(
 	#[description = "The number of times to execute the command."] count: u32,
 	#[rest]
 	#[description = "The dice to roll. Follow the command with `!` to annotate what the roll is \
-	                 for."]
+					 for."]
 	command: String,
 ) -> Result<(), Error> {
 	if count < 2 {
@@ -91,21 +277,28 @@ pub async fn batch_roll(
 
 	let slash_command = is_application_context(&ctx);
 
-	let annotation_index = command.find(ANNOTATION_CHAR);
+	let annotation_index = find_annotation(command.as_str());
 	let command_slice = match annotation_index {
 		Some(index) => command[0..index].trim(),
 		None => command.trim(),
 	};
 
-	if let Ok(rpn) = parse_roll_command(command_slice) {
-		// Execute the rolls
+	let parse_result = parse_roll_command(command_slice);
+	if let Ok(rpn) = parse_result {
+		// Execute the rolls, all sharing the same budget
 		let mut roll_results = Vec::new();
+		let mut dice_budget = MAX_DICE_PER_COMMAND;
 		for _ in 0..count {
-			if let Some((result, _)) = evaluate_roll_rpn(&rpn) {
-				roll_results.push(result);
-			} else {
-				reply(ctx, "Invalid command.").await?;
-				return Ok(());
+			match evaluate_roll_rpn(&rpn, &mut dice_budget) {
+				Ok((result, _)) => roll_results.push(result),
+				Err(EvaluateRollError::Invalid) => {
+					reply(ctx, "Invalid command.").await?;
+					return Ok(());
+				}
+				Err(EvaluateRollError::TooLarge) => {
+					reply(ctx, TOO_LARGE_MESSAGE).await?;
+					return Ok(());
+				}
 			}
 		}
 
@@ -134,6 +327,8 @@ pub async fn batch_roll(
 		// Escape the command string
 		let command_slice_escaped = escape_str(command_slice);
 
+		let precision_exceeded = roll_results.iter().any(|r| exceeds_precision(*r));
+
 		reply_embed(ctx, |e| {
 			if !slash_command {
 				e.field("For:", ctx.author().mention(), true);
@@ -142,18 +337,199 @@ pub async fn batch_roll(
 			if !annotation.is_empty() {
 				e.field("Reason:", format!("`{}`", annotation), true);
 			}
-			e.field("Command:", format!("`{}`", command_slice_escaped), false)
-				.field("Results:", format!("```{}```", result_display), false)
+			e.field("Command:", format!("`{}`", command_slice_escaped), false);
+			if precision_exceeded {
+				e.field(
+					"Results:",
+					format!("```{}```{}", result_display, PRECISION_WARNING),
+					false,
+				)
+			} else {
+				e.field("Results:", format!("```{}```", result_display), false)
+			}
 		})
 		.await?;
-	} else {
+	} else if let Err(e) = parse_result {
+		reply(ctx, describe_parse_error(&e)).await?;
+		return Ok(());
+	}
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Roll two commands against each other, and see which one comes out higher.
+///
+/// Separate the two commands with `vs`, eg. `/rollvs 1d20 + 5 vs 1d20 + 3`.
+/// This is handy for opposed checks, like grappling or stealth against
+/// perception.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	rename = "rollvs",
+	aliases("opposed")
+)]
+pub async fn roll_vs(
+	ctx: PoiseContext<'_>,
+	#[rest]
+	#[description = "The two rolls to compare, separated by `vs`. Follow the command with `!` to \
+					 annotate what the roll is for."]
+	command: String,
+) -> Result<(), Error> {
+	let slash_command = is_application_context(&ctx);
+
+	let annotation_index = find_annotation(command.as_str());
+	let command_slice = match annotation_index {
+		Some(index) => command[0..index].trim(),
+		None => command.trim(),
+	};
+	let annotation_escaped = annotation_index
+		.map(|index| escape_str(command[(index + 1)..].trim()))
+		.filter(|annotation| !annotation.is_empty());
+
+	let (first_command, second_command) =
+		if let Some((first, second)) = command_slice.split_once(VERSUS_SEPARATOR) {
+			(first.trim(), second.trim())
+		} else {
+			reply(
+				ctx,
+				"Separate the two rolls with `vs`, eg. `1d20 + 5 vs 1d20 + 3`.",
+			)
+			.await?;
+			return Ok(());
+		};
+
+	// Execute both rolls, sharing the same budget
+	let mut dice_budget = MAX_DICE_PER_COMMAND;
+	let mut sides = Vec::with_capacity(2);
+	for (side_name, side_command) in [("first", first_command), ("second", second_command)] {
+		let rpn = match parse_roll_command(side_command) {
+			Ok(rpn) => rpn,
+			Err(e) => {
+				reply(
+					ctx,
+					format!(
+						"The {} roll couldn't be read. {}",
+						side_name,
+						describe_parse_error(&e)
+					),
+				)
+				.await?;
+				return Ok(());
+			}
+		};
+		match evaluate_roll_rpn(&rpn, &mut dice_budget) {
+			Ok((result, dice_rolls)) => sides.push((side_command, result, dice_rolls)),
+			Err(EvaluateRollError::Invalid) => {
+				reply(
+					ctx,
+					format!("The {} roll is an invalid command.", side_name),
+				)
+				.await?;
+				return Ok(());
+			}
+			Err(EvaluateRollError::TooLarge) => {
+				reply(ctx, TOO_LARGE_MESSAGE).await?;
+				return Ok(());
+			}
+		}
+	}
+
+	let side_displays = sides
+		.iter()
+		.map(|(side_command, result, dice_rolls)| {
+			let mut rolls_string = display_rolls(dice_rolls);
+			if rolls_string.len() > MAX_FIELD_VALUE / 2 {
+				rolls_string = "*\u{2026}clipped because there were too many values*".to_owned();
+			}
+			let mut side_display = format!("`{}`\n", escape_str(side_command));
+			if !rolls_string.is_empty() {
+				side_display.push_str(rolls_string.as_str());
+				side_display.push('\n');
+			}
+			side_display.push_str(format!("Result: `{}`", display_result(*result)).as_str());
+			if exceeds_precision(*result) {
+				side_display.push(' ');
+				side_display.push_str(PRECISION_WARNING);
+			}
+			side_display
+		})
+		.collect::<Vec<_>>();
+	let outcome = match sides[0].1.partial_cmp(&sides[1].1) {
+		Some(Ordering::Greater) => "The first roll wins!",
+		Some(Ordering::Less) => "The second roll wins!",
+		Some(Ordering::Equal) | None => "It's a tie!",
+	};
+
+	reply_embed(ctx, |e| {
+		if !slash_command {
+			e.field("For:", ctx.author().mention(), true);
+		}
+		if let Some(annotation) = annotation_escaped {
+			e.field("Reason:", format!("`{}`", annotation), true);
+		}
+		e.field("First Roll:", &side_displays[0], false)
+			.field("Second Roll:", &side_displays[1], false)
+			.field("Outcome:", outcome, false)
+	})
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Check that a roll command is valid, without rolling it.
+///
+/// If it's valid, the command is shown the way it's read, with parentheses
+/// around each part that's worked out first. This is handy for making sure a
+/// complicated command does what you expect before saving it.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	rename = "validateroll",
+	aliases("checkroll")
+)]
+pub async fn validate_roll(
+	ctx: PoiseContext<'_>,
+	#[rest]
+	#[description = "The roll command to check. Type it out exactly how you would if you were \
+					 using the roll command."]
+	command: String,
+) -> Result<(), Error> {
+	let command = command.trim();
+	if command.is_empty() {
 		reply(ctx, "Invalid command.").await?;
 		return Ok(());
 	}
 
+	let rpn = match parse_roll_command(command) {
+		Ok(rpn) => rpn,
+		Err(e) => {
+			reply(ctx, describe_parse_error(&e)).await?;
+			return Ok(());
+		}
+	};
+	let response = match check_roll_rpn(&rpn, MAX_DICE_PER_COMMAND) {
+		Ok(()) => match show_roll_structure(&rpn, MAX_WORK_LENGTH) {
+			Some(structure) => format!("Valid \u{2713}\nReads as: `{}`", structure),
+			None => "Valid \u{2713}".to_owned(),
+		},
+		Err(EvaluateRollError::Invalid) => {
+			"Invalid command: the operators and values don't line up.".to_owned()
+		}
+		Err(EvaluateRollError::TooLarge) => TOO_LARGE_MESSAGE.to_owned(),
+	};
+	reply(ctx, response).await?;
+
 	Ok(())
 }
+}
 
+panic_safe! {
 /// Save a roll command for frequent use.
 ///
 /// The command should be typed out exactly as you would when using the roll
@@ -161,7 +537,9 @@ pub async fn batch_roll(
 ///
 /// You cannot save an annotation with the roll command.
 ///
-/// The command name is case-insensitive.
+/// The command name is case-insensitive. If you already have a roll saved with
+/// the same name, you'll be asked before it's overwritten - start the command
+/// with `--force` to skip that, eg. `/saveroll attack --force d20 + 6`.
 #[command(
 	prefix_command,
 	slash_command,
@@ -174,7 +552,7 @@ pub async fn save_roll(
 	#[description = "The name to save the command as."] mut identifier: String,
 	#[rest]
 	#[description = "The roll command to save. Type it out exactly how you would if you were \
-	                 using the roll command."]
+					 using the roll command."]
 	command: String,
 ) -> Result<(), Error> {
 	// Get the associated IDs or exit
@@ -187,16 +565,66 @@ pub async fn save_roll(
 
 	// Clean up the input
 	identifier = identifier.to_lowercase();
-	let command = command.trim();
+	let (command, force) = if let Some(remaining) = command
+		.trim()
+		.strip_prefix(FORCE_OPTION)
+		.filter(|remaining| remaining.is_empty() || remaining.starts_with(char::is_whitespace))
+	{
+		(remaining.trim(), true)
+	} else {
+		(command.trim(), false)
+	};
 
 	// Verify that the command is valid
-	if command.contains(ANNOTATION_CHAR) {
-		reply(ctx, "You cannot include annotations on saved commands.").await?;
+	if let Err(problem) = validate_saveable_command(command) {
+		reply(ctx, problem).await?;
 		return Ok(());
 	}
-	if command.is_empty() || parse_roll_command(command).is_err() {
-		reply(ctx, "Invalid command.").await?;
-		return Ok(());
+
+	// Make sure an existing roll isn't overwritten by accident
+	if !force {
+		let existing_command = {
+			let conn = ctx.data().db_pool.get().unwrap();
+
+			saved_rolls::table
+				.find((ctx_guild_id, ctx_user_id, identifier.as_str()))
+				.select(saved_rolls::command)
+				.first::<String>(&conn)
+				.optional()
+				.with_context(|| "failed to check for an existing saved roll command")?
+		};
+		if let Some(existing_command) = existing_command {
+			if existing_command == command {
+				reply(
+					ctx,
+					format!(
+						"The roll command `{}` is already saved as that.",
+						identifier
+					),
+				)
+				.await?;
+				return Ok(());
+			}
+			let confirmed = confirm(
+				ctx,
+				format!(
+					"A roll named `{}` already exists, as `{}`. Overwrite it?",
+					identifier,
+					escape_str(existing_command.as_str())
+				),
+				"Overwrite",
+				OVERWRITE_CONFIRMATION_TIMEOUT,
+			)
+			.await?;
+			if !confirmed {
+				reply(
+					ctx,
+					format!("Kept the existing roll command `{}`.", identifier),
+				)
+				.await?;
+				return Ok(());
+			}
+		}
 	}
 
 	// Create the new records and insert
@@ -210,18 +638,203 @@ pub async fn save_roll(
 			name: Cow::from(identifier.as_str()),
 			command: Cow::from(command),
 		};
-		replace_into(saved_rolls::table)
-			.values(&saved_roll)
-			.execute(&conn)
+		insert_saved_rolls(&conn, &[saved_roll])
 			.with_context(|| "failed to save the roll command to the database")?;
 	}
+	forget_parsed_roll(ctx.data(), ctx_guild_id, ctx_user_id, identifier.as_str());
 
 	// Finish up
 	reply(ctx, format!("Saved the roll command `{}`.", identifier)).await?;
 
 	Ok(())
 }
+}
+
+panic_safe! {
+/// Save many roll commands at once.
+///
+/// Put each command on its own line as `name: command`, eg. `attack: d20 + 5`.
+/// Commands can also be separated with `;` instead, which is handy for the
+/// slash version of the command. Any invalid lines are skipped, and the rest
+/// are saved.
+///
+/// If any of the names already have a different roll saved under them, you'll
+/// be asked before they're overwritten - start the command with `--force` to
+/// skip that.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	rename = "savemany"
+)]
+pub async fn save_many(
+	ctx: PoiseContext<'_>,
+	#[rest]
+	#[description = "The roll commands to save, as `name: command`, separated by new lines or `;`."]
+	commands: String,
+) -> Result<(), Error> {
+	const ENTRY_SEPARATORS: [char; 2] = ['\n', ';'];
+	const NAME_SEPARATOR: char = ':';
+
+	// Get the associated IDs or exit
+	let (ctx_guild_id, ctx_user_id) = if let Some(ids) = get_ctx_ids(ctx) {
+		ids
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let (commands, force) = if let Some(remaining) = commands
+		.trim()
+		.strip_prefix(FORCE_OPTION)
+		.filter(|remaining| remaining.is_empty() || remaining.starts_with(char::is_whitespace))
+	{
+		(remaining, true)
+	} else {
+		(commands.as_str(), false)
+	};
+
+	// Parse and validate each entry, keeping track of the ones that can't be saved
+	let mut rolls_to_save = Vec::new();
+	let mut failures = Vec::new();
+	for (i, entry) in commands
+		.split(&ENTRY_SEPARATORS[..])
+		.map(str::trim)
+		.enumerate()
+		.filter(|(_, entry)| !entry.is_empty())
+	{
+		let (name, command) = if let Some((name, command)) = entry.split_once(NAME_SEPARATOR) {
+			(name.trim().to_lowercase(), command.trim())
+		} else {
+			failures.push((i + 1, "It isn't in the form `name: command`.".into()));
+			continue;
+		};
+		if name.is_empty() || name.contains(char::is_whitespace) {
+			failures.push((i + 1, "The name must be a single word.".into()));
+			continue;
+		}
+		if let Err(problem) = validate_saveable_command(command) {
+			failures.push((i + 1, problem));
+			continue;
+		}
+
+		rolls_to_save.push(SavedRoll {
+			guild_id: ctx_guild_id,
+			user_id: ctx_user_id,
+			name: Cow::from(name),
+			command: Cow::from(command),
+		});
+	}
+
+	// Make sure existing rolls aren't overwritten by accident
+	let mut kept_names = Vec::new();
+	if !force && !rolls_to_save.is_empty() {
+		let existing_rolls = {
+			let conn = ctx.data().db_pool.get().unwrap();
+
+			saved_rolls::table
+				.filter(saved_rolls::guild_id.eq(ctx_guild_id))
+				.filter(saved_rolls::user_id.eq(ctx_user_id))
+				.filter(
+					saved_rolls::name.eq_any(
+						rolls_to_save
+							.iter()
+							.map(|saved_roll| saved_roll.name.as_ref()),
+					),
+				)
+				.select((saved_rolls::name, saved_rolls::command))
+				.load::<(String, String)>(&conn)
+				.with_context(|| "failed to check for existing saved roll commands")?
+		};
+		// Saving a roll as exactly what it already is doesn't overwrite anything
+		let overwritten_names = existing_rolls
+			.into_iter()
+			.filter(|(name, command)| {
+				rolls_to_save.iter().any(|saved_roll| {
+					saved_roll.name == name.as_str() && saved_roll.command != command.as_str()
+				})
+			})
+			.map(|(name, _)| name)
+			.collect::<Vec<_>>();
+
+		if !overwritten_names.is_empty() {
+			let names = overwritten_names
+				.iter()
+				.map(|name| format!("`{}`", escape_str(name)))
+				.collect::<Vec<_>>()
+				.join(", ");
+			let confirmed = confirm(
+				ctx,
+				if overwritten_names.len() == 1 {
+					format!("A roll named {} already exists. Overwrite it?", names)
+				} else {
+					format!("Rolls named {} already exist. Overwrite them?", names)
+				},
+				"Overwrite",
+				OVERWRITE_CONFIRMATION_TIMEOUT,
+			)
+			.await?;
+			if !confirmed {
+				rolls_to_save.retain(|saved_roll| {
+					!overwritten_names
+						.iter()
+						.any(|name| saved_roll.name == name.as_str())
+				});
+				kept_names = overwritten_names;
+			}
+		}
+	}
+
+	// Save all of the valid entries together
+	if !rolls_to_save.is_empty() {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		insert_saved_rolls(&conn, &rolls_to_save)
+			.with_context(|| "failed to save the roll commands to the database")?;
+	}
+	for saved_roll in &rolls_to_save {
+		forget_parsed_roll(ctx.data(), ctx_guild_id, ctx_user_id, &saved_roll.name);
+	}
+
+	// Report back
+	let mut response = if rolls_to_save.is_empty() {
+		"Nothing was saved.".to_owned()
+	} else {
+		format!(
+			"Saved {} roll command{}: {}",
+			rolls_to_save.len(),
+			if rolls_to_save.len() == 1 { "" } else { "s" },
+			rolls_to_save
+				.iter()
+				.map(|saved_roll| format!("`{}`", escape_str(saved_roll.name.as_ref())))
+				.collect::<Vec<_>>()
+				.join(", ")
+		)
+	};
+	if !kept_names.is_empty() {
+		response.push_str(
+			format!(
+				"\nKept the existing roll command{}: {}",
+				if kept_names.len() == 1 { "" } else { "s" },
+				kept_names
+					.iter()
+					.map(|name| format!("`{}`", escape_str(name)))
+					.collect::<Vec<_>>()
+					.join(", ")
+			)
+			.as_str(),
+		);
+	}
+	for (entry_number, problem) in &failures {
+		response.push_str(format!("\nEntry {} wasn't saved: {}", entry_number, problem).as_str());
+	}
+	reply(ctx, response).await?;
+
+	Ok(())
+}
+}
 
+panic_safe! {
 /// Delete a saved roll command.
 #[command(
 	prefix_command,
@@ -256,6 +869,7 @@ pub async fn delete_roll(
 			.filter(name.eq(&identifier))
 			.execute(&conn)
 	};
+	forget_parsed_roll(ctx.data(), ctx_guild_id, ctx_user_id, identifier.as_str());
 
 	// Respond with the result
 	if let Ok(count) = deleted_rows {
@@ -280,8 +894,13 @@ pub async fn delete_roll(
 	}
 	Ok(())
 }
+}
 
+panic_safe! {
 /// Run a saved roll command.
+///
+/// You only have to type the start of the name, as long as it's enough to tell
+/// it apart from your other saved rolls.
 #[command(
 	prefix_command,
 	slash_command,
@@ -305,23 +924,31 @@ pub async fn run_roll(
 	};
 
 	// Clean and prepare the identifier
-	let identifier_query = format!("{}%", identifier.trim().to_lowercase());
+	let identifier_clean = identifier.trim().to_lowercase();
+	let identifier_query = format!("{}%", identifier_clean);
 
-	// Fetch the command to execute from the database
-	let (mut roll_reason, mut roll_command) = {
+	// Fetch the candidate commands from the database
+	let mut candidates = {
 		use self::saved_rolls::dsl::*;
 
 		let conn = ctx.data().db_pool.get().unwrap();
 
-		let search_result = saved_rolls
+		saved_rolls
 			.filter(guild_id.eq(ctx_guild_id))
 			.filter(user_id.eq(ctx_user_id))
 			.filter(name.like(&identifier_query))
 			.select((name, command))
-			.limit(1)
-			.get_result::<(String, String)>(&conn);
+			.load::<(String, String)>(&conn)
+			.with_context(|| "failed to retrieve the matching saved roll commands")?
+	};
 
-		if search_result.is_err() {
+	// Rank the candidates so the choice is deterministic - an exact match wins
+	// outright, then shorter names come first, then alphabetical order
+	candidates.sort_unstable_by(|(a, _), (b, _)| {
+		(a != &identifier_clean, a.len(), a).cmp(&(b != &identifier_clean, b.len(), b))
+	});
+	let (roll_name, mut roll_command) = match candidates.len() {
+		0 => {
 			reply(
 				ctx,
 				format!(
@@ -332,34 +959,97 @@ pub async fn run_roll(
 			.await?;
 			return Ok(());
 		}
-
-		search_result.unwrap()
+		1 => candidates.remove(0),
+		_ if candidates[0].0 == identifier_clean => candidates.remove(0),
+		_ => {
+			let mut output = format!(
+				"Multiple saved rolls match the query `{}`. Please be more specific:",
+				identifier
+			);
+			for (name, _) in &candidates {
+				output.push_str(format!("\n- `{}`", name).as_str());
+			}
+			reply(ctx, output).await?;
+			return Ok(());
+		}
 	};
 
+	let mut roll_reason = roll_name.clone();
+
 	// Parse the raw command string into clean, meaningful slices
-	let annotation_index = additional.find(ANNOTATION_CHAR);
+	let annotation_index = find_annotation(additional.as_str());
 	let additional_command_slice =
 		annotation_index.map_or_else(|| additional.trim(), |index| additional[0..index].trim());
 	let additional_annotation_slice =
 		annotation_index.map_or("", |index| additional[(index + 1)..].trim());
 
-	// Combine the saved roll with the additional information provided, if any
-	if !additional_command_slice.is_empty() {
-		roll_command.insert(0, '(');
-		roll_command.push_str(") ");
-		roll_command.push_str(additional_command_slice);
-	}
 	if !additional_annotation_slice.is_empty() {
 		roll_reason.push_str("; ");
 		roll_reason.push_str(additional_annotation_slice);
 	}
 
-	// Execute the command
-	execute_roll(ctx, roll_command.as_str(), Some(roll_reason.as_str()), true).await?;
+	// Combine the saved roll with the additional information provided, if any -
+	// otherwise, the saved roll can be run as-is, without parsing it again
+	if additional_command_slice.is_empty() {
+		let cached_rpn = ctx
+			.data()
+			.parsed_roll_cache
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(ctx_guild_id, ctx_user_id, roll_name.as_str());
+		let rpn = if let Some(rpn) = cached_rpn {
+			rpn
+		} else {
+			match parse_roll_command(roll_command.as_str()) {
+				Ok(rpn) => {
+					let rpn = Arc::new(rpn);
+					ctx.data()
+						.parsed_roll_cache
+						.lock()
+						.unwrap_or_else(PoisonError::into_inner)
+						.insert(
+							ctx_guild_id,
+							ctx_user_id,
+							roll_name.as_str(),
+							Arc::clone(&rpn),
+						);
+					rpn
+				}
+				Err(e) => {
+					reply(ctx, describe_parse_error(&e)).await?;
+					return Ok(());
+				}
+			}
+		};
+		execute_roll_rpn(
+			ctx,
+			roll_command.as_str(),
+			&rpn,
+			Some(roll_reason.as_str()),
+			true,
+			false,
+		)
+		.await?;
+	} else {
+		roll_command.insert(0, '(');
+		roll_command.push_str(") ");
+		roll_command.push_str(additional_command_slice);
+
+		execute_roll(
+			ctx,
+			roll_command.as_str(),
+			Some(roll_reason.as_str()),
+			true,
+			false,
+		)
+		.await?;
+	}
 
 	Ok(())
 }
+}
 
+panic_safe! {
 /// Show a list of all your saved rolls.
 #[command(
 	prefix_command,
@@ -414,8 +1104,14 @@ pub async fn saved_rolls(ctx: PoiseContext<'_>) -> Result<(), Error> {
 
 	Ok(())
 }
+}
 
+panic_safe! {
 /// Put bad dice in dice jail and get new dice.
+///
+/// Radium keeps count of how many times everyone has jailed their dice - see
+/// `dicejailboard`. Dice can only be jailed once every 30 seconds, so give the
+/// new ones a chance.
 #[command(
 	prefix_command,
 	slash_command,
@@ -427,12 +1123,75 @@ pub async fn dice_jail(ctx: PoiseContext<'_>) -> Result<(), Error> {
 	const DICE_SIZE: u32 = 20;
 	const DICE_COUNT: u32 = 5;
 
-	let (rolls, _) = Dice {
+	// Keep the counts from being inflated by spam
+	let on_cooldown = {
+		let mut last_dice_jails = ctx
+			.data()
+			.last_dice_jails
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		// Forget the cooldowns that have run out, so that only the people on cooldown
+		// are kept track of
+		last_dice_jails.retain(|_, last_dice_jail| last_dice_jail.elapsed() < DICE_JAIL_COOLDOWN);
+		let author_id = ctx.author().id;
+		if last_dice_jails.contains_key(&author_id) {
+			true
+		} else {
+			last_dice_jails.insert(author_id, Instant::now());
+			false
+		}
+	};
+	if on_cooldown {
+		reply(
+			ctx,
+			"Your dice were only just jailed. Give the new ones a chance.",
+		)
+		.await?;
+		return Ok(());
+	}
+
+	// Count it, if it's in a server
+	if let Some((ctx_guild_id, ctx_user_id)) = get_ctx_ids(ctx) {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		let new_count = (
+			dice_jail_counts::guild_id.eq(ctx_guild_id),
+			dice_jail_counts::user_id.eq(ctx_user_id),
+		);
+		#[cfg(feature = "sqlite")]
+		let inserted = insert_or_ignore_into(dice_jail_counts::table)
+			.values(new_count)
+			.execute(&conn);
+		#[cfg(feature = "postgres")]
+		let inserted = insert_into(dice_jail_counts::table)
+			.values(new_count)
+			.on_conflict_do_nothing()
+			.execute(&conn);
+
+		inserted
+			.and_then(|_| {
+				update(dice_jail_counts::table.find((ctx_guild_id, ctx_user_id)))
+					.set(dice_jail_counts::count.eq(dice_jail_counts::count + 1))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to update the dice jail count in the database")?;
+	}
+
+	let dice = Dice {
 		size: DICE_SIZE,
 		count: DICE_COUNT,
 		modifier: None,
-	}
-	.eval();
+		reroll: None,
+		great_weapon_fighting: false,
+		exploding: false,
+		success_threshold: None,
+		botch_threshold: None,
+		each_bonus: 0,
+	};
+	let mut dice_budget = MAX_DICE_PER_COMMAND;
+	let (rolls, total) = dice
+		.eval(&mut dice_budget)
+		.expect("plain dice never roll any extra dice");
 
 	reply_embed(ctx, |e| {
 		if !is_application_context(&ctx) {
@@ -444,7 +1203,11 @@ pub async fn dice_jail(ctx: PoiseContext<'_>) -> Result<(), Error> {
 			)
 			.field(
 				format!("Sample Rolls ({}d{}):", DICE_COUNT, DICE_SIZE),
-				display_rolls(&[rolls]),
+				display_rolls(&[DiceRolls {
+					dice: &dice,
+					rolls,
+					total,
+				}]),
 				false,
 			)
 	})
@@ -452,95 +1215,363 @@ pub async fn dice_jail(ctx: PoiseContext<'_>) -> Result<(), Error> {
 
 	Ok(())
 }
+}
+
+panic_safe! {
+/// See who has put their dice in dice jail the most in this server.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	rename = "dicejailboard",
+	aliases("jailboard")
+)]
+pub async fn dice_jail_board(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	const LEADERBOARD_SIZE: i64 = 10;
+
+	let ctx_guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id.0 as i64
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let leaders = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		dice_jail_counts::table
+			.filter(dice_jail_counts::guild_id.eq(ctx_guild_id))
+			.filter(dice_jail_counts::count.gt(0))
+			.select((dice_jail_counts::user_id, dice_jail_counts::count))
+			.order(dice_jail_counts::count.desc())
+			.limit(LEADERBOARD_SIZE)
+			.load::<(i64, i32)>(&conn)
+			.with_context(|| "failed to load the dice jail counts from the database")?
+	};
+
+	if leaders.is_empty() {
+		reply(ctx, "Nobody has put their dice in dice jail here yet.").await?;
+		return Ok(());
+	}
+
+	let mut output = String::new();
+	for (i, (user_id, count)) in leaders.iter().enumerate() {
+		if i > 0 {
+			output.push('\n');
+		}
+		output.push_str(
+			format!(
+				"`{}.` {} - {} time{}",
+				i + 1,
+				UserId(*user_id as u64).mention(),
+				count,
+				if *count == 1 { "" } else { "s" }
+			)
+			.as_str(),
+		);
+	}
+
+	reply_embed(ctx, |e| {
+		e.title("Dice Jail Leaderboard").description(output)
+	})
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// See who rolls the best in this server, on average.
+///
+/// Each roll counts the first die rolled in it, scaled so that rolling a 1 is
+/// 0% and rolling the highest number on the die is 100%. Someone has to have
+/// rolled at least 10 times to be ranked.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Chance",
+	aliases("luckboard")
+)]
+pub async fn luck(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	const LEADERBOARD_SIZE: usize = 10;
+	const MIN_ROLLS: i64 = 10;
+
+	let ctx_guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id.0 as i64
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	// Work out each user's average in the database, as a fraction of the way from
+	// the lowest roll to the highest
+	let averages = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		roll_history::table
+			.filter(roll_history::guild_id.eq(ctx_guild_id))
+			.group_by(roll_history::user_id)
+			.select((
+				roll_history::user_id,
+				sql::<Double>("AVG(CAST(die_value - 1 AS DOUBLE PRECISION) / (die_size - 1))"),
+				sql::<BigInt>("COUNT(*)"),
+			))
+			.load::<(i64, f64, i64)>(&conn)
+			.with_context(|| "failed to load the roll history from the database")?
+	};
+
+	let mut averages = averages
+		.into_iter()
+		.filter(|(_, _, count)| *count >= MIN_ROLLS)
+		.collect::<Vec<_>>();
+
+	if averages.is_empty() {
+		reply(
+			ctx,
+			format!(
+				"Nobody here has rolled enough to tell yet. It takes {} rolls to be ranked.",
+				MIN_ROLLS
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	averages.sort_unstable_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+
+	let averages_len = averages.len();
+	let mut output = String::new();
+	for (i, (user_id, average, count)) in averages.iter().enumerate() {
+		// Always show the unluckiest, even if they're too far down to fit
+		if i >= LEADERBOARD_SIZE {
+			if i < averages_len - 1 {
+				continue;
+			}
+			if i > LEADERBOARD_SIZE {
+				output.push_str("\n\u{2026}");
+			}
+		}
+		if i > 0 {
+			output.push('\n');
+		}
+		output.push_str(
+			format!(
+				"`{}.` {} - {:.0}% over {} rolls",
+				i + 1,
+				UserId(*user_id as u64).mention(),
+				average * 100.0,
+				count
+			)
+			.as_str(),
+		);
+	}
+
+	reply_embed(ctx, |e| e.title("Luck Leaderboard").description(output)).await?;
+
+	Ok(())
+}
+}
 
 // Utility Functions
 
 /// Executes a roll command and replies to the requester with the results,
 /// formatted.
+///
+/// If `show_work` is set, the roll is also shown with its dice values
+/// substituted in.
 async fn execute_roll(
 	ctx: PoiseContext<'_>,
 	command: &str,
 	annotation: Option<&str>,
 	always_show_command_in_output: bool,
+	show_work: bool,
+) -> Result<(), Error> {
+	match parse_roll_command(command) {
+		Ok(rpn) => {
+			execute_roll_rpn(
+				ctx,
+				command,
+				&rpn,
+				annotation,
+				always_show_command_in_output,
+				show_work,
+			)
+			.await
+		}
+		Err(e) => {
+			reply(ctx, describe_parse_error(&e)).await?;
+			Ok(())
+		}
+	}
+}
+
+/// Executes an already-parsed roll command and replies to the requester with
+/// the results, formatted.
+///
+/// `command` is the original command, which is only used for display.
+async fn execute_roll_rpn(
+	ctx: PoiseContext<'_>,
+	command: &str,
+	rpn: &[Evaluable],
+	annotation: Option<&str>,
+	always_show_command_in_output: bool,
+	show_work: bool,
 ) -> Result<(), Error> {
 	let slash_command = is_application_context(&ctx);
 
-	if let Ok(rpn) = parse_roll_command(command) {
-		if let Some((result, dice_rolls)) = evaluate_roll_rpn(&rpn) {
-			// Display preparation
-			let mut rolls_string = display_rolls(&dice_rolls);
-
-			// Annotation parsing
-			let annotation_escaped = annotation.map(escape_str);
-
-			// Display
-			let dice_rolls_len = dice_rolls.len();
-			let display_big_result =
-				dice_rolls_len > 1 || (dice_rolls_len == 1 && dice_rolls[0].len() >= 5);
-
-			// Display the result with maximum 2 decimal places of precision, but strip
-			// off trailing '0's and '.'s so that normal rolls don't have decimals
-			// We don't use the &[char] pattern:
-			// If we did, numbers like `600.0` would become `6`
-			let result_display = format!("{:.2}", result)
-				.trim_end_matches('0')
-				.trim_end_matches('.')
-				.to_owned();
-
-			let command_slice_escaped = escape_str(command);
-
-			if display_big_result {
-				if rolls_string.len() > MAX_FIELD_VALUE {
-					rolls_string =
-						"*\u{2026}clipped because there were too many values*".to_owned();
+	let mut dice_budget = MAX_DICE_PER_COMMAND;
+	let evaluation = evaluate_roll_rpn(rpn, &mut dice_budget);
+	if let Ok((result, dice_rolls)) = evaluation {
+		// Remember the first die for `luck`, without any bonus added to it
+		if let (Some((ctx_guild_id, ctx_user_id)), Some(dice_roll)) =
+			(get_ctx_ids(ctx), dice_rolls.first())
+		{
+			// Dice too large for the table's columns are left out of the history. A
+			// die's first value never goes past its size, so checking the size covers
+			// both
+			if let (Some(roll), Ok(die_size)) =
+				(dice_roll.rolls.first(), i32::try_from(dice_roll.dice.size))
+			{
+				let conn = ctx.data().db_pool.get()?;
+				// Missing one roll from the history isn't worth failing the roll over
+				insert_into(roll_history::table)
+					.values(RolledDie {
+						guild_id: ctx_guild_id,
+						user_id: ctx_user_id,
+						die_size,
+						die_value: dice_roll.dice.first_value(roll) as i32,
+					})
+					.execute(&conn)
+					.ok();
+			}
+		}
+
+		// Display preparation
+		let mut rolls_string = display_rolls(&dice_rolls);
+
+		// Annotation parsing
+		let annotation_escaped = annotation.map(escape_str);
+
+		// Work out the math with the dice values in, if requested
+		let work_display = show_work.then(|| {
+			show_roll_work(rpn, &dice_rolls, MAX_WORK_LENGTH).map_or_else(
+				|| "*too long to show*".to_owned(),
+				|work| format!("`{}`", work),
+			)
+		});
+
+		// Display
+		let dice_rolls_len = dice_rolls.len();
+		let display_big_result = work_display.is_some()
+			|| dice_rolls_len > 1
+			|| (dice_rolls_len == 1 && dice_rolls[0].rolls.len() >= 5);
+
+		let result_display = display_result(result);
+
+		let command_slice_escaped = escape_str(command);
+
+		// Large results can't be trusted to be exact, so let the user know
+		let precision_exceeded = exceeds_precision(result);
+
+		// A lone d20 gets a reaction on a natural 20 or 1, for flavour
+		let crit_reaction = match dice_rolls.as_slice() {
+			[dice_roll] if dice_roll.dice.size == CRIT_DIE_SIZE && dice_roll.rolls.len() == 1 => {
+				match dice_roll.dice.first_value(&dice_roll.rolls[0]) {
+					CRIT_DIE_SIZE => Some(CRIT_SUCCESS_REACTION),
+					1 => Some(CRIT_FAILURE_REACTION),
+					_ => None,
 				}
-				reply_embed(ctx, |e| {
-					if !slash_command {
-						e.field("For:", ctx.author().mention(), true);
-					}
-					if let Some(annotation) = annotation_escaped {
-						e.field("Reason:", format!("`{}`", annotation), true);
-					}
-					e.field("Command:", format!("`{}`", command_slice_escaped), false)
-						.field("Rolls:", rolls_string, false)
-						.field("Result:", format!("`{}`", result_display), false)
-				})
-				.await?;
-			} else {
-				let mut display = String::new();
+			}
+			_ => None,
+		};
+
+		let reply_handle = if display_big_result {
+			if rolls_string.len() > MAX_FIELD_VALUE {
+				rolls_string = "*\u{2026}clipped because there were too many values*".to_owned();
+			}
+			reply_embed(ctx, |e| {
 				if !slash_command {
-					display.push_str(ctx.author().mention().to_string().as_str());
+					e.field("For:", ctx.author().mention(), true);
 				}
 				if let Some(annotation) = annotation_escaped {
-					display.push_str(" `");
-					display.push_str(annotation.as_str());
-					display.push('`');
+					e.field("Reason:", format!("`{}`", annotation), true);
+				}
+				e.field("Command:", format!("`{}`", command_slice_escaped), false)
+					.field("Rolls:", rolls_string, false);
+				if let Some(work_display) = work_display {
+					e.field("Work:", work_display, false);
 				}
-				if always_show_command_in_output || slash_command {
-					display.push_str(" - `");
-					display.push_str(command_slice_escaped.as_str());
-					display.push('`');
+				if precision_exceeded {
+					e.field(
+						"Result:",
+						format!("`{}` {}", result_display, PRECISION_WARNING),
+						false,
+					)
+				} else {
+					e.field("Result:", format!("`{}`", result_display), false)
+				}
+			})
+			.await?
+		} else {
+			let mut display = String::new();
+			if !slash_command {
+				display.push_str(ctx.author().mention().to_string().as_str());
+			}
+			if let Some(annotation) = annotation_escaped {
+				display.push_str(" `");
+				display.push_str(annotation.as_str());
+				display.push('`');
+			}
+			if always_show_command_in_output || slash_command {
+				display.push_str(" - `");
+				display.push_str(command_slice_escaped.as_str());
+				display.push('`');
+			}
+			display.push_str(": ");
+			display.push_str(rolls_string.as_str());
+			if !(dice_rolls_len == 1
+				&& dice_rolls[0].rolls.len() == 1
+				&& f64::from(dice_rolls[0].rolls[0].value).eq(&result))
+			{
+				if !rolls_string.is_empty() {
+					display.push(' ');
 				}
-				display.push_str(": ");
-				display.push_str(rolls_string.as_str());
-				if !(dice_rolls_len == 1
-					&& dice_rolls[0].len() == 1
-					&& f64::from(dice_rolls[0][0]).eq(&result))
-				{
-					if !rolls_string.is_empty() {
-						display.push(' ');
-					}
-					display.push_str("Result: `");
-					display.push_str(result_display.as_str());
-					display.push('`');
+				display.push_str("Result: `");
+				display.push_str(result_display.as_str());
+				display.push('`');
+				if precision_exceeded {
+					display.push(' ');
+					display.push_str(PRECISION_WARNING);
 				}
+			}
+
+			reply_plain(ctx, display.trim()).await?
+		};
 
-				reply_plain(ctx, display.trim()).await?;
+		if let (Some(crit_reaction), Some(reply_handle)) = (crit_reaction, reply_handle) {
+			let crit_reactions = if let Some(guild_id) = ctx.guild_id() {
+				let conn = ctx.data().db_pool.get()?;
+				get_guild_settings(&conn, guild_id.0 as i64)?.crit_reactions
+			} else {
+				true
+			};
+			if crit_reactions {
+				// Radium might not be allowed to add reactions, which isn't worth bothering
+				// anyone about
+				if let Ok(message) = reply_handle.message().await {
+					message
+						.react(
+							ctx.discord(),
+							ReactionType::Unicode(crit_reaction.to_owned()),
+						)
+						.await
+						.ok();
+				}
 			}
-		} else {
-			reply(ctx, "Invalid command.").await?;
-			return Ok(());
 		}
+	} else if let Err(EvaluateRollError::TooLarge) = evaluation {
+		reply(ctx, TOO_LARGE_MESSAGE).await?;
+		return Ok(());
 	} else {
 		reply(ctx, "Invalid command.").await?;
 		return Ok(());
@@ -549,8 +1580,109 @@ async fn execute_roll(
 	Ok(())
 }
 
+/// Checks whether a roll command can be saved, returning the reason if it
+/// can't.
+fn validate_saveable_command(command: &str) -> Result<(), Cow<'static, str>> {
+	if find_annotation(command).is_some() {
+		return Err("You cannot include annotations on saved commands.".into());
+	}
+	if command.is_empty() {
+		return Err("Invalid command.".into());
+	}
+	if let Err(e) = parse_roll_command(command) {
+		return Err(describe_parse_error(&e).into());
+	}
+	Ok(())
+}
+
+/// Describes what's wrong with a roll command that couldn't be parsed.
+fn describe_parse_error(error: &ParseRollError) -> String {
+	match error {
+		ParseRollError::UnknownToken(token) => format!(
+			"Invalid command: `{}` isn't a number, dice roll, or operator.",
+			escape_str(token)
+		),
+		ParseRollError::Dice(e) => e.to_string(),
+		ParseRollError::MismatchedParentheses => {
+			"Invalid command: the parentheses don't match up.".to_owned()
+		}
+	}
+}
+
+/// Forgets the parsed form of a saved roll, for when it's changed or deleted.
+fn forget_parsed_roll(data: &Data, guild_id: i64, user_id: i64, name: &str) {
+	data.parsed_roll_cache
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.forget(guild_id, user_id, name);
+}
+
+/// Saves roll commands, overwriting any existing ones with the same names.
+///
+/// The commands are saved in a single transaction, so either all of them are
+/// saved or none are.
+fn insert_saved_rolls(conn: &DbConnection, rolls: &[SavedRoll]) -> QueryResult<()> {
+	conn.transaction(|| {
+		for saved_roll in rolls {
+			#[cfg(feature = "sqlite")]
+			replace_into(saved_rolls::table)
+				.values(saved_roll)
+				.execute(conn)?;
+			#[cfg(feature = "postgres")]
+			insert_into(saved_rolls::table)
+				.values(saved_roll)
+				.on_conflict((
+					saved_rolls::guild_id,
+					saved_rolls::user_id,
+					saved_rolls::name,
+				))
+				.do_update()
+				.set(saved_rolls::command.eq(excluded(saved_rolls::command)))
+				.execute(conn)?;
+		}
+		Ok(())
+	})
+}
+
+/// Rolls two d20s, keeping one of them according to `keep_modifier` (`b` or
+/// `w`), and adds the modifier.
+async fn execute_d20_pair_roll(
+	ctx: PoiseContext<'_>,
+	keep_modifier: char,
+	modifier: Option<i32>,
+	annotation: Option<String>,
+) -> Result<(), Error> {
+	// Build the equivalent roll command so it can be shown to the user
+	let command = match modifier.unwrap_or(0) {
+		0 => format!("2d20{}", keep_modifier),
+		m if m < 0 => format!("2d20{} - {}", keep_modifier, m.unsigned_abs()),
+		m => format!("2d20{} + {}", keep_modifier, m),
+	};
+
+	// The annotation character is optional here, since there's no command for it to
+	// separate the annotation from
+	let annotation = annotation
+		.as_deref()
+		.map(|a| a.trim().trim_start_matches(ANNOTATION_CHAR).trim())
+		.filter(|a| !a.is_empty());
+
+	execute_roll(ctx, command.as_str(), annotation, true, false).await
+}
+
 /// Displays a set of rolls.
-fn display_rolls(dice_rolls: &[Vec<u32>]) -> String {
+///
+/// If there's more than one set of dice, each set is labelled with its dice
+/// notation, eg. `2d6:[4 5] 1d20:[13]`.
+///
+/// Rerolled dice are shown with their original value, followed by an arrow and
+/// the value they were rerolled to.
+///
+/// If only the best or worst few dice are kept, the kept dice are marked with a
+/// star.
+///
+/// In dice pools (with a target number), the kept dice that succeeded are
+/// marked with a check mark, and the ones that botched with a cross.
+fn display_rolls(dice_rolls: &[DiceRolls]) -> String {
 	let mut rolls_string = String::new();
 
 	let rolls_count = dice_rolls.len();
@@ -558,35 +1690,102 @@ fn display_rolls(dice_rolls: &[Vec<u32>]) -> String {
 		return rolls_string;
 	}
 	rolls_string.push('`');
-	if rolls_count > 1 {
-		rolls_string.push('[');
-	}
 	for (i, dice_roll) in dice_rolls.iter().enumerate() {
 		if i > 0 {
 			rolls_string.push(' ');
 		}
-		let roll_dice_count = dice_roll.len();
-		if roll_dice_count > 1 {
+		// Only label the sets if there's more than one, since otherwise it's obvious
+		// which dice they are
+		let bracketed = rolls_count > 1 || dice_roll.rolls.len() > 1;
+		if rolls_count > 1 {
+			rolls_string.push_str(dice_roll.dice.to_string().as_str());
+			rolls_string.push(':');
+		}
+		if bracketed {
 			rolls_string.push('[');
 		}
-		for (j, value) in dice_roll.iter().enumerate() {
+		for (j, roll) in dice_roll.rolls.iter().enumerate() {
 			if j > 0 {
 				rolls_string.push(' ');
 			}
-			rolls_string.push_str(value.to_string().as_str());
+			for original_value in &roll.rerolled_from {
+				rolls_string.push_str(original_value.to_string().as_str());
+				rolls_string.push('\u{2192}');
+			}
+			// Exploded dice show each value in the chain
+			if roll.explosions > 0 {
+				rolls_string.push('(');
+				for _ in 0..roll.explosions {
+					rolls_string.push_str(dice_roll.dice.size.to_string().as_str());
+					rolls_string.push('+');
+				}
+				rolls_string.push_str(roll.last_explosion.to_string().as_str());
+				rolls_string.push(')');
+			} else {
+				rolls_string.push_str(roll.value.to_string().as_str());
+			}
+			if dice_roll.dice.modifier.is_some() && roll.kept {
+				rolls_string.push(KEPT_MARK);
+			}
+			if roll.kept
+				&& dice_roll
+					.dice
+					.success_threshold
+					.map_or(false, |success_threshold| roll.value >= success_threshold)
+			{
+				rolls_string.push(SUCCESS_MARK);
+			}
+			if roll.kept
+				&& dice_roll
+					.dice
+					.botch_threshold
+					.map_or(false, |botch_threshold| {
+						dice_roll.dice.is_botch(roll, botch_threshold)
+					}) {
+				rolls_string.push(BOTCH_MARK);
+			}
 		}
-		if roll_dice_count > 1 {
+		if bracketed {
 			rolls_string.push(']');
 		}
 	}
-	if rolls_count > 1 {
-		rolls_string.push(']');
-	}
 	rolls_string.push('`');
 
 	rolls_string
 }
 
+/// Finds where the annotation starts in a roll command, if it has one.
+///
+/// The annotation character also makes dice explode, so it's only taken as
+/// the start of an annotation if it isn't part of a dice roll. (eg. `3d6!`)
+fn find_annotation(command: &str) -> Option<usize> {
+	let is_word_boundary = |c: char| c.is_whitespace() || OPERATOR_SYMBOLS.contains(&c);
+	command
+		.match_indices(ANNOTATION_CHAR)
+		.map(|(index, _)| index)
+		.find(|index| {
+			let word_start = command[..*index]
+				.rfind(is_word_boundary)
+				.map_or(0, |boundary| boundary + 1);
+			let word_end = command[(index + 1)..]
+				.find(is_word_boundary)
+				.map_or(command.len(), |boundary| index + 1 + boundary);
+			command[word_start..word_end].parse::<Dice>().is_err()
+		})
+}
+
+/// Displays a roll result with at most 2 decimal places of precision.
+///
+/// Trailing '0's and '.'s are stripped off so that normal rolls don't have
+/// decimals. We don't use the &[char] pattern: if we did, numbers like `600.0`
+/// would become `6`.
+fn display_result(result: f64) -> String {
+	format!("{:.2}", result)
+		.trim_end_matches('0')
+		.trim_end_matches('.')
+		.to_owned()
+}
+
 /// Retrieves the guild ID and user ID from the message context.
 fn get_ctx_ids(ctx: PoiseContext) -> Option<(i64, i64)> {
 	Some((
@@ -598,3 +1797,41 @@ fn get_ctx_ids(ctx: PoiseContext) -> Option<(i64, i64)> {
 		ctx.author().id.0 as i64,
 	))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(command: &str) -> Arc<Vec<Evaluable>> {
+		Arc::new(parse_roll_command(command).unwrap())
+	}
+
+	#[test]
+	fn parsed_roll_cache_forgets_edited_rolls() {
+		let mut cache = ParsedRollCache::new();
+		cache.insert(1, 2, "fireball", parse("8d6"));
+		cache.insert(1, 2, "heal", parse("2d4 + 2"));
+		assert!(cache.get(1, 2, "fireball").is_some());
+
+		// Editing a saved roll forgets its parsed form, the same as `saveroll` does
+		cache.forget(1, 2, "fireball");
+		assert!(cache.get(1, 2, "fireball").is_none());
+		assert!(cache.get(1, 2, "heal").is_some());
+
+		// The edited roll is parsed again the next time it's run
+		cache.insert(1, 2, "fireball", parse("10d6"));
+		let rpn = cache.get(1, 2, "fireball").unwrap();
+		assert!(matches!(rpn.as_slice(), [Evaluable::Dice(dice)] if dice.count == 10));
+	}
+
+	#[test]
+	fn parsed_roll_cache_is_per_user() {
+		let mut cache = ParsedRollCache::new();
+		cache.insert(1, 2, "fireball", parse("8d6"));
+		cache.insert(1, 3, "fireball", parse("8d6"));
+
+		cache.forget(1, 2, "fireball");
+		assert!(cache.get(1, 2, "fireball").is_none());
+		assert!(cache.get(1, 3, "fireball").is_some());
+	}
+}
@@ -1,10 +1,58 @@
 // Uses
-use std::{cmp::Reverse, collections::VecDeque, num::ParseIntError, str::FromStr};
+use std::{
+	borrow::Cow,
+	cmp::Reverse,
+	collections::VecDeque,
+	fmt::{Display, Formatter, Result as FmtResult},
+	num::ParseIntError,
+	str::FromStr,
+};
 
 use rand::{distributions::Uniform, thread_rng, Rng};
 
 // Constants
-const OPERATOR_SYMBOLS: [char; 10] = ['^', '*', '×', 'x', '/', '÷', '+', '-', '(', ')'];
+pub const OPERATOR_SYMBOLS: [char; 11] = ['^', '*', '×', 'x', '/', '÷', '\\', '+', '-', '(', ')'];
+/// The largest integer that an [`f64`] can represent exactly, along with every
+/// integer below it. (`2^53 - 1`)
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+/// The most dice that a single command can roll in total, to keep huge rolls
+/// from tying up the bot.
+pub const MAX_DICE_PER_COMMAND: u64 = 10_000;
+/// The suffix that enables Great Weapon Fighting for a dice roll, eg. `2d6gwf`.
+const GREAT_WEAPON_FIGHTING_SUFFIX: &str = "gwf";
+/// Great Weapon Fighting rerolls any die showing this value or lower.
+const GREAT_WEAPON_FIGHTING_MAX_REROLL: u32 = 2;
+/// The character that rerolls low dice, eg. `4d6r1`.
+const REROLL_CHAR: char = 'r';
+/// The character that follows [`REROLL_CHAR`] to only reroll dice once, eg.
+/// `4d6ro1`.
+const REROLL_ONCE_CHAR: char = 'o';
+/// The most times that dice can be rerolled at once, so that rerolling almost
+/// every value can't go on for too long. Rolls that need more rerolls than this
+/// are too large to evaluate.
+const MAX_REROLLS: u32 = 100;
+/// The character that makes dice explode, eg. `3d6!`.
+pub const EXPLODE_CHAR: char = '!';
+/// The most extra dice that exploding dice can roll at once, so that dice with
+/// few sides can't keep exploding for too long. Rolls that explode more than
+/// this are too large to evaluate.
+const MAX_EXPLOSIONS: u32 = 100;
+/// The shorthand for a percentile die's size, eg. `d%` for `d100`.
+const PERCENTILE_SIZE: &str = "%";
+const PERCENTILE_DIE_SIZE: u32 = 100;
+/// The character that sets a target number for a dice pool, eg. `6d10>7`.
+const SUCCESS_THRESHOLD_CHAR: char = '>';
+/// The character that follows a target number to have low dice take away
+/// successes, eg. `6d10>7f1`.
+const BOTCH_CHAR: char = 'f';
+/// The prefix for a bonus that's added to each die in a roll, eg. `3d6+e1`.
+const EACH_BONUS_PREFIX: &str = "+e";
+/// The shorthand that doubles the dice that follow it, for critical hits, eg.
+/// `crit 2d6` for `4d6`.
+const CRIT_SHORTHAND: &str = "crit";
+/// The shorthands that halve what follows them, rounding down, eg. `half 7`
+/// for `3`.
+const HALF_SHORTHANDS: [&str; 2] = ["half", "halve"];
 
 // Types
 #[derive(Debug)]
@@ -19,6 +67,30 @@ pub struct Dice {
 	pub size: u32,
 	pub count: u32,
 	pub modifier: Option<DiceModifier>,
+	pub reroll: Option<RerollModifier>,
+	pub great_weapon_fighting: bool, // Reroll low values once, keeping the new value
+	pub exploding: bool,             // Roll again and add it on whenever a die rolls its maximum
+	pub success_threshold: Option<u32>, // Count the dice that meet this instead of summing them
+	pub botch_threshold: Option<u32>, // Dice at or under this take away a success
+	pub each_bonus: u32,             // Added to every die, and not capped at the dice size
+}
+
+#[derive(Debug, Clone)]
+pub struct DieRoll {
+	pub value: u32,              // Includes the dice's bonus to each die, if any
+	pub rerolled_from: Vec<u32>, // The discarded values, oldest first, if the die was rerolled
+	pub explosions: u32,         // How many times the die rolled its maximum again
+	pub last_explosion: u32,     // The value the die's last explosion landed on, if it exploded
+	pub kept: bool,              // Whether the die counts towards the total
+}
+
+/// The rolls for one set of dice in an expression, along with the dice they
+/// were rolled for.
+#[derive(Debug)]
+pub struct DiceRolls<'a> {
+	pub dice: &'a Dice,
+	pub rolls: Vec<DieRoll>,
+	pub total: i64,
 }
 
 #[derive(Debug)]
@@ -27,37 +99,198 @@ pub enum DiceModifier {
 	Worst(u32), // Keep the worst n values
 }
 
+#[derive(Debug)]
+pub enum RerollModifier {
+	Always(u32), // Reroll values of n or lower until they're higher
+	Once(u32),   // Reroll values of n or lower once, keeping the new value
+}
+
 impl Dice {
-	pub fn eval(&self) -> (Vec<u32>, u32) {
+	/// Rolls the dice, returning each die's roll and the result.
+	///
+	/// The extra dice rolled for rerolls and explosions are taken out of
+	/// `dice_budget`, and the roll fails if it runs out.
+	///
+	/// The result is only negative for dice pools with more botches than
+	/// successes.
+	pub fn eval(&self, dice_budget: &mut u64) -> Result<(Vec<DieRoll>, i64), EvaluateRollError> {
 		let mut rolls = Vec::new();
 		let mut rng = thread_rng();
 		let range = Uniform::new_inclusive(1, self.size);
+		let mut rerolls_left = MAX_REROLLS;
+		let mut explosions_left = MAX_EXPLOSIONS;
 		for _ in 0..self.count {
-			rolls.push(rng.sample(range));
-		}
-
-		let result = match self.modifier {
-			Some(DiceModifier::Best(n)) => {
-				let mut temp_rolls = rolls.clone();
-				temp_rolls.sort_unstable_by_key(|r| Reverse(*r));
-				temp_rolls.iter().take(n as usize).sum::<u32>()
+			let mut roll = DieRoll {
+				value: rng.sample(range),
+				rerolled_from: Vec::new(),
+				explosions: 0,
+				last_explosion: 0,
+				kept: true,
+			};
+			match self.reroll {
+				Some(RerollModifier::Always(n)) => {
+					while roll.value <= n {
+						charge_extra_die(&mut rerolls_left, dice_budget)?;
+						roll.rerolled_from.push(roll.value);
+						roll.value = rng.sample(range);
+					}
+				}
+				Some(RerollModifier::Once(n)) if roll.value <= n => {
+					charge_extra_die(&mut rerolls_left, dice_budget)?;
+					roll.rerolled_from.push(roll.value);
+					roll.value = rng.sample(range);
+				}
+				Some(RerollModifier::Once(_)) | None => {}
+			}
+			// Great Weapon Fighting only ever rerolls a die once, and the new value is kept
+			// even if it's worse. Since it can't go on, it only needs to come out of the
+			// budget
+			if self.great_weapon_fighting && roll.value <= GREAT_WEAPON_FIGHTING_MAX_REROLL {
+				charge_budget(dice_budget)?;
+				roll.rerolled_from.push(roll.value);
+				roll.value = rng.sample(range);
 			}
-			Some(DiceModifier::Worst(n)) => {
-				let mut temp_rolls = rolls.clone();
-				temp_rolls.sort_unstable();
-				temp_rolls.iter().take(n as usize).sum::<u32>()
+			// Each explosion is added onto the die that exploded, so the chain counts as
+			// one die when dice are kept or counted
+			if self.exploding {
+				let mut last_value = roll.value;
+				while last_value == self.size {
+					charge_extra_die(&mut explosions_left, dice_budget)?;
+					last_value = rng.sample(range);
+					roll.value = roll.value.saturating_add(last_value);
+					roll.explosions += 1;
+					roll.last_explosion = last_value;
+				}
 			}
-			None => rolls.iter().sum::<u32>(),
+			// The bonus is applied after any rerolls, since those look at the die itself,
+			// and before the dice are kept or counted, since those look at the final values
+			roll.value = roll.value.saturating_add(self.each_bonus);
+			for rerolled_from in &mut roll.rerolled_from {
+				*rerolled_from = rerolled_from.saturating_add(self.each_bonus);
+			}
+			if roll.explosions > 0 {
+				roll.last_explosion = roll.last_explosion.saturating_add(self.each_bonus);
+			}
+			rolls.push(roll);
+		}
+
+		mark_kept_rolls(&mut rolls, self.modifier.as_ref());
+
+		let kept_rolls = rolls.iter().filter(|r| r.kept).collect::<Vec<_>>();
+		// Dice pools count how many dice succeeded instead of adding them up
+		let result = if let Some(success_threshold) = self.success_threshold {
+			let successes = kept_rolls
+				.iter()
+				.filter(|roll| roll.value >= success_threshold)
+				.count() as i64;
+			let botches = self.botch_threshold.map_or(0, |botch_threshold| {
+				kept_rolls
+					.iter()
+					.filter(|roll| self.is_botch(roll, botch_threshold))
+					.count() as i64
+			});
+			successes - botches
+		} else {
+			kept_rolls
+				.iter()
+				.map(|roll| i64::from(roll.value))
+				.sum::<i64>()
 		};
 
-		(rolls, result)
+		Ok((rolls, result))
+	}
+
+	/// Gets the value that a die first landed on, after any reroll but without
+	/// any explosions or the bonus to each die.
+	pub fn first_value(&self, roll: &DieRoll) -> u32 {
+		if roll.explosions > 0 {
+			self.size
+		} else {
+			roll.value - self.each_bonus
+		}
+	}
+
+	/// Whether a die in a dice pool takes away a success. This looks at the
+	/// value the die landed on, without the bonus to each die.
+	pub fn is_botch(&self, roll: &DieRoll, botch_threshold: u32) -> bool {
+		self.first_value(roll) <= botch_threshold
 	}
 }
 
+impl Display for Dice {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "{}d{}", self.count, self.size)?;
+		match self.reroll {
+			Some(RerollModifier::Always(n)) => write!(f, "{}{}", REROLL_CHAR, n)?,
+			Some(RerollModifier::Once(n)) => write!(f, "{}{}{}", REROLL_CHAR, REROLL_ONCE_CHAR, n)?,
+			None => {}
+		}
+		match self.modifier {
+			Some(DiceModifier::Best(n)) => write!(f, "b{}", n)?,
+			Some(DiceModifier::Worst(n)) => write!(f, "w{}", n)?,
+			None => {}
+		}
+		if self.exploding {
+			write!(f, "{}", EXPLODE_CHAR)?;
+		}
+		if self.great_weapon_fighting {
+			f.write_str(GREAT_WEAPON_FIGHTING_SUFFIX)?;
+		}
+		if let Some(success_threshold) = self.success_threshold {
+			write!(f, "{}{}", SUCCESS_THRESHOLD_CHAR, success_threshold)?;
+		}
+		if let Some(botch_threshold) = self.botch_threshold {
+			write!(f, "{}{}", BOTCH_CHAR, botch_threshold)?;
+		}
+		if self.each_bonus > 0 {
+			write!(f, "{}{}", EACH_BONUS_PREFIX, self.each_bonus)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
 pub enum ParseDiceError {
 	Int(ParseIntError),
 	Format,
-	Value,
+	FractionalCount,
+	NegativeCount,
+	ZeroCount,
+	FractionalSize,
+	TooFewSides,
+	TooManyKept,
+	ImpossibleThreshold,
+	ImpossibleBotch,
+	ImpossibleReroll,
+	TooLargeBonus,
+}
+
+impl Display for ParseDiceError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::Int(_) | Self::Format => "Invalid dice roll.",
+			Self::FractionalCount => "Dice count must be a whole number.",
+			Self::NegativeCount => "Dice count can't be negative.",
+			Self::ZeroCount => "You can't roll zero dice.",
+			Self::FractionalSize => "Dice size must be a whole number.",
+			Self::TooFewSides => "Dice must have at least 2 sides.",
+			Self::TooManyKept => "You can't keep more dice than you roll.",
+			Self::ImpossibleThreshold => {
+				"The target number can't be higher than the dice can roll."
+			}
+			Self::ImpossibleBotch => "Botches have to be lower than the target number.",
+			Self::ImpossibleReroll => "You can't reroll every value the dice can roll.",
+			Self::TooLargeBonus => "The bonus to each die is too large.",
+		})
+	}
+}
+
+impl ParseDiceError {
+	/// Whether the error means the value just isn't a dice roll, as opposed to
+	/// being a dice roll with something wrong with it.
+	pub fn is_not_dice(&self) -> bool {
+		matches!(self, Self::Int(_) | Self::Format)
+	}
 }
 
 impl FromStr for Dice {
@@ -73,10 +306,90 @@ impl FromStr for Dice {
 		let dice_count = if d_index == 0 {
 			1
 		} else {
-			s[0..d_index].parse::<u32>().map_err(ParseDiceError::Int)?
+			let count_str = &s[0..d_index];
+			count_str.parse::<u32>().map_err(|e| {
+				// Anything that isn't a number at all is just an invalid count
+				let count = count_str.parse::<f64>().unwrap_or_default();
+				if count < 0.0 {
+					ParseDiceError::NegativeCount
+				} else if count.fract() != 0.0 {
+					ParseDiceError::FractionalCount
+				} else {
+					ParseDiceError::Int(e)
+				}
+			})?
 		};
+		if dice_count < 1 {
+			return Err(ParseDiceError::ZeroCount);
+		}
 
-		let remaining = &s[(d_index + 1)..];
+		let mut remaining = &s[(d_index + 1)..];
+		let each_bonus =
+			if let Some(bonus_index) = remaining.to_ascii_lowercase().find(EACH_BONUS_PREFIX) {
+				let bonus = remaining[(bonus_index + EACH_BONUS_PREFIX.len())..]
+					.parse::<u32>()
+					.map_err(ParseDiceError::Int)?;
+				remaining = &remaining[..bonus_index];
+				bonus
+			} else {
+				0
+			};
+		let (success_threshold, botch_threshold) = if let Some((rest, threshold_str)) =
+			remaining.split_once(SUCCESS_THRESHOLD_CHAR)
+		{
+			remaining = rest;
+			// Botches can only be counted in a dice pool, so they come after the target
+			// number
+			let (threshold_str, botch_threshold) =
+				if let Some((threshold_str, botch_str)) = threshold_str.split_once(BOTCH_CHAR) {
+					(
+						threshold_str,
+						Some(botch_str.parse::<u32>().map_err(ParseDiceError::Int)?),
+					)
+				} else {
+					(threshold_str, None)
+				};
+			(
+				Some(threshold_str.parse::<u32>().map_err(ParseDiceError::Int)?),
+				botch_threshold,
+			)
+		} else {
+			(None, None)
+		};
+		let great_weapon_fighting =
+			if let Some(stripped) = remaining.strip_suffix(GREAT_WEAPON_FIGHTING_SUFFIX) {
+				remaining = stripped;
+				true
+			} else {
+				false
+			};
+		// The explode marker can go on either side of the best or worst modifier
+		let exploding = remaining.contains(EXPLODE_CHAR);
+		let mut remaining = remaining.replacen(EXPLODE_CHAR, "", 1);
+		// The reroll modifier can also go on either side, so it's taken out the same
+		// way
+		let reroll = if let Some(r_index) = remaining.find(REROLL_CHAR) {
+			let (once, after) =
+				if let Some(after) = remaining[(r_index + 1)..].strip_prefix(REROLL_ONCE_CHAR) {
+					(true, after)
+				} else {
+					(false, &remaining[(r_index + 1)..])
+				};
+			let threshold_len = after
+				.find(|c: char| !c.is_ascii_digit())
+				.unwrap_or(after.len());
+			let threshold = after[..threshold_len]
+				.parse::<u32>()
+				.map_err(ParseDiceError::Int)?;
+			remaining = format!("{}{}", &remaining[..r_index], &after[threshold_len..]);
+			Some(if once {
+				RerollModifier::Once(threshold)
+			} else {
+				RerollModifier::Always(threshold)
+			})
+		} else {
+			None
+		};
 		let b_index = remaining.find('b');
 		let w_index = remaining.find('w');
 
@@ -88,12 +401,49 @@ impl FromStr for Dice {
 		} else {
 			w_index
 		};
-		let die_size = match mod_index {
-			Some(i) => remaining[0..i]
-				.parse::<u32>()
-				.map_err(ParseDiceError::Int)?,
-			None => remaining.parse::<u32>().map_err(ParseDiceError::Int)?,
+		let size_str = match mod_index {
+			Some(i) => &remaining[0..i],
+			None => remaining.as_str(),
 		};
+		let die_size = if size_str == PERCENTILE_SIZE {
+			Ok(PERCENTILE_DIE_SIZE)
+		} else {
+			size_str.parse::<u32>()
+		}
+		.map_err(|e| {
+			if size_str
+				.parse::<f64>()
+				.map_or(false, |size| size.fract() != 0.0)
+			{
+				ParseDiceError::FractionalSize
+			} else {
+				ParseDiceError::Int(e)
+			}
+		})?;
+		if die_size < 2 {
+			return Err(ParseDiceError::TooFewSides);
+		}
+		// Every value the dice can roll has to fit once the bonus is added on
+		let max_value = die_size
+			.checked_add(each_bonus)
+			.ok_or(ParseDiceError::TooLargeBonus)?;
+		// Exploding dice can go past their maximum, so any target can be met
+		if !exploding && success_threshold.map_or(false, |threshold| threshold > max_value) {
+			return Err(ParseDiceError::ImpossibleThreshold);
+		}
+		if let (Some(success_threshold), Some(botch_threshold)) =
+			(success_threshold, botch_threshold)
+		{
+			if botch_threshold >= success_threshold {
+				return Err(ParseDiceError::ImpossibleBotch);
+			}
+		}
+		// Rerolling every value would never end
+		if let Some(RerollModifier::Always(n) | RerollModifier::Once(n)) = reroll {
+			if n >= die_size {
+				return Err(ParseDiceError::ImpossibleReroll);
+			}
+		}
 		let modifier = match mod_index {
 			Some(i) => {
 				let n = if i + 1 < remaining.len() {
@@ -104,7 +454,7 @@ impl FromStr for Dice {
 					1
 				};
 				if n > dice_count {
-					return Err(ParseDiceError::Value);
+					return Err(ParseDiceError::TooManyKept);
 				}
 				if b_index.is_some() {
 					Some(DiceModifier::Best(n))
@@ -115,21 +465,33 @@ impl FromStr for Dice {
 			None => None,
 		};
 
-		if dice_count < 1 {
-			return Err(ParseDiceError::Value);
-		}
-		if die_size < 2 {
-			return Err(ParseDiceError::Value);
-		}
-
 		Ok(Dice {
 			size: die_size,
 			count: dice_count,
 			modifier,
+			reroll,
+			great_weapon_fighting,
+			exploding,
+			success_threshold,
+			botch_threshold,
+			each_bonus,
 		})
 	}
 }
 
+#[derive(Debug)]
+pub enum ParseRollError {
+	UnknownToken(String),  // Something that isn't a number, dice roll, or operator
+	Dice(ParseDiceError),  // A dice roll with something wrong with it
+	MismatchedParentheses, // A parenthesis without a partner
+}
+
+#[derive(Debug)]
+pub enum EvaluateRollError {
+	Invalid,  // The expression is malformed
+	TooLarge, // The expression rolls more dice than the budget allows
+}
+
 #[derive(Debug)]
 pub struct Operator {
 	pub op: OperatorType,
@@ -143,6 +505,7 @@ pub enum OperatorType {
 	Exponent,
 	Multiply,
 	Divide,
+	FloorDivide,
 	Add,
 	Subtract,
 	ParenthesisLeft,
@@ -154,7 +517,7 @@ pub enum OperatorType {
 /// Parse the roll command into a [Reverse Polish Notation](https://en.wikipedia.org/wiki/Reverse_Polish_notation) expression.
 ///
 /// This is an implementation of the [Shunting-Yard Algorithm](https://en.wikipedia.org/wiki/Shunting-yard_algorithm).
-pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
+pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ParseRollError> {
 	/// Sub-function for converting token chars into their proper operators.
 	fn token_to_operator(token: char) -> Option<Operator> {
 		match token {
@@ -176,6 +539,12 @@ pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
 				precedence: 3,
 				associates_left: true,
 			}),
+			'\\' => Some(Operator {
+				op: OperatorType::FloorDivide,
+				functional: true,
+				precedence: 3,
+				associates_left: true,
+			}),
 			'+' => Some(Operator {
 				op: OperatorType::Add,
 				functional: true,
@@ -212,7 +581,13 @@ pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
 			let mut tokens = Vec::new();
 			let mut start_index = 0;
 			for (i, c) in s.char_indices() {
-				if OPERATOR_SYMBOLS.contains(&c) {
+				// A bonus to each die is part of the dice roll it follows, not an addition
+				let is_each_bonus = s
+					.get(i..(i + EACH_BONUS_PREFIX.len()))
+					.map_or(false, |prefix| {
+						prefix.eq_ignore_ascii_case(EACH_BONUS_PREFIX)
+					}) && s[start_index..i].to_ascii_lowercase().contains('d');
+				if OPERATOR_SYMBOLS.contains(&c) && !is_each_bonus {
 					if start_index != i {
 						tokens.push(&s[start_index..i]);
 					}
@@ -226,14 +601,28 @@ pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
 			tokens
 		})
 		.collect::<Vec<_>>();
+	let tokens = expand_shorthands(&tokens);
 
 	// Parse the tokens into RPN.
 	let mut output = Vec::new();
 	let mut operator_stack: VecDeque<Operator> = VecDeque::new();
-	for token in tokens {
+	let mut follows_operand = false;
+	for (i, token) in tokens.iter().enumerate() {
 		// Operators, including parentheses
 		if token.chars().count() == 1 {
 			if let Some(op) = token_to_operator(token.chars().next().unwrap()) {
+				// Negative numbers aren't supported, but a minus sign in front of a dice roll
+				// deserves a better explanation than the expression just being invalid
+				if op.op == OperatorType::Subtract
+					&& !follows_operand
+					&& tokens
+						.get(i + 1)
+						.map_or(false, |next| next.parse::<Dice>().is_ok())
+				{
+					return Err(ParseRollError::Dice(ParseDiceError::NegativeCount));
+				}
+				follows_operand = op.op == OperatorType::ParenthesisRight;
+
 				// True operators
 				if op.functional {
 					while let Some(other_op) = operator_stack.front() {
@@ -260,7 +649,7 @@ pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
 								output
 									.push(Evaluable::Operator(operator_stack.pop_front().unwrap()));
 							} else {
-								return Err(());
+								return Err(ParseRollError::MismatchedParentheses);
 							}
 						}
 						operator_stack.pop_front(); // Discard the left parenthesis
@@ -270,19 +659,27 @@ pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
 			}
 		}
 		// Otherwise, it's a standard token
-		if let Ok(dice) = token.parse::<Dice>() {
-			output.push(Evaluable::Dice(dice));
-			continue;
-		}
+		follows_operand = true;
+		let dice_error = match token.parse::<Dice>() {
+			Ok(dice) => {
+				output.push(Evaluable::Dice(dice));
+				continue;
+			}
+			Err(e) => e,
+		};
 		if let Ok(value) = token.parse::<f64>() {
 			output.push(Evaluable::Num(value));
 			continue;
 		}
-		return Err(());
+		return Err(if dice_error.is_not_dice() {
+			ParseRollError::UnknownToken(token.to_string())
+		} else {
+			ParseRollError::Dice(dice_error)
+		});
 	}
 	while let Some(op) = operator_stack.pop_front() {
 		if op.op == OperatorType::ParenthesisLeft || op.op == OperatorType::ParenthesisRight {
-			return Err(());
+			return Err(ParseRollError::MismatchedParentheses);
 		}
 		output.push(Evaluable::Operator(op));
 	}
@@ -290,24 +687,131 @@ pub fn parse_roll_command(command: &str) -> Result<Vec<Evaluable>, ()> {
 	Ok(output)
 }
 
+/// Expands the shorthands in a tokenized roll command into the standard
+/// expressions they stand for.
+///
+/// Each shorthand applies to the operand right after it, which is either a
+/// single value, a group in parentheses, or another shorthand and its operand:
+/// - `crit` doubles the number of dice, eg. `crit 2d6 + 3` is `4d6 + 3`.
+/// - `half` (or `halve`) halves the operand and rounds down, eg. `half (2d6 +
+///   3)` is `((2d6 + 3) \\ 2)`.
+///
+/// A shorthand with nothing after it is left alone, and fails to parse as an
+/// unknown token.
+fn expand_shorthands<'a>(tokens: &[&'a str]) -> Vec<Cow<'a, str>> {
+	/// Finds where the operand starting at `start` ends. (exclusive)
+	fn find_operand_end(tokens: &[&str], start: usize) -> usize {
+		match tokens.get(start) {
+			None => start,
+			Some(token) if is_shorthand(token) => find_operand_end(tokens, start + 1),
+			Some(&"(") => {
+				let mut depth = 0_usize;
+				for (i, token) in tokens.iter().enumerate().skip(start) {
+					match *token {
+						"(" => depth += 1,
+						")" => {
+							depth -= 1;
+							if depth == 0 {
+								return i + 1;
+							}
+						}
+						_ => {}
+					}
+				}
+				tokens.len()
+			}
+			Some(_) => start + 1,
+		}
+	}
+	/// Checks whether a token is one of the shorthands, ignoring case.
+	fn is_shorthand(token: &str) -> bool {
+		token.eq_ignore_ascii_case(CRIT_SHORTHAND)
+			|| HALF_SHORTHANDS
+				.iter()
+				.any(|shorthand| token.eq_ignore_ascii_case(shorthand))
+	}
+	/// Doubles the number of dice in a token, if it's a dice roll.
+	fn double_dice_count(token: Cow<str>) -> Cow<str> {
+		if token.parse::<Dice>().is_err() {
+			return token;
+		}
+		// The count can be left off for a single die
+		let count_length = token
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(token.len());
+		let count = token[..count_length].parse::<u32>().unwrap_or(1);
+		Cow::Owned(format!(
+			"{}{}",
+			count.saturating_mul(2),
+			&token[count_length..]
+		))
+	}
+
+	let mut expanded = Vec::with_capacity(tokens.len());
+	let mut i = 0;
+	while i < tokens.len() {
+		let token = tokens[i];
+		let operand_end = find_operand_end(tokens, i + 1);
+		if !is_shorthand(token) || operand_end == i + 1 {
+			expanded.push(Cow::Borrowed(token));
+			i += 1;
+			continue;
+		}
+
+		let operand = expand_shorthands(&tokens[(i + 1)..operand_end]);
+		if token.eq_ignore_ascii_case(CRIT_SHORTHAND) {
+			expanded.extend(operand.into_iter().map(double_dice_count));
+		} else {
+			expanded.push(Cow::Borrowed("("));
+			expanded.push(Cow::Borrowed("("));
+			expanded.extend(operand);
+			expanded.push(Cow::Borrowed(")"));
+			expanded.push(Cow::Borrowed("\\"));
+			expanded.push(Cow::Borrowed("2"));
+			expanded.push(Cow::Borrowed(")"));
+		}
+		i = operand_end;
+	}
+
+	expanded
+}
+
 /// Evaluate the Reverse Polish Notation expression into final results.
-pub fn evaluate_roll_rpn(rpn: &[Evaluable]) -> Option<(f64, Vec<Vec<u32>>)> {
+///
+/// Every die rolled is taken out of `dice_budget`, and evaluation stops if it
+/// runs out. The same budget can be shared between several evaluations to
+/// limit them as a whole.
+///
+/// The rolls for each set of dice are returned in the order the dice appear in
+/// the expression.
+pub fn evaluate_roll_rpn<'a>(
+	rpn: &'a [Evaluable],
+	dice_budget: &mut u64,
+) -> Result<(f64, Vec<DiceRolls<'a>>), EvaluateRollError> {
 	let mut dice_rolls = Vec::new();
 	let mut stack = VecDeque::new();
 
 	for operand in rpn {
 		match operand {
 			Evaluable::Dice(dice) => {
-				let (rolls, value) = dice.eval();
-				dice_rolls.push(rolls);
-				stack.push_front(f64::from(value));
+				// Check the budget before rolling anything, so huge rolls are never attempted
+				*dice_budget = dice_budget
+					.checked_sub(u64::from(dice.count))
+					.ok_or(EvaluateRollError::TooLarge)?;
+				let (rolls, value) = dice.eval(dice_budget)?;
+				dice_rolls.push(DiceRolls {
+					dice,
+					rolls,
+					total: value,
+				});
+				stack.push_front(value as f64);
 			}
 			Evaluable::Num(value) => {
 				stack.push_front(*value);
 			}
 			Evaluable::Operator(op) => {
 				if stack.len() < 2 {
-					return None;
+					return Err(EvaluateRollError::Invalid);
 				}
 				let right = stack.pop_front().unwrap();
 				let left = stack.pop_front().unwrap();
@@ -315,19 +819,442 @@ pub fn evaluate_roll_rpn(rpn: &[Evaluable]) -> Option<(f64, Vec<Vec<u32>>)> {
 					OperatorType::Exponent => left.powf(right),
 					OperatorType::Multiply => left * right,
 					OperatorType::Divide => left / right,
+					OperatorType::FloorDivide => (left / right).floor(),
 					OperatorType::Add => left + right,
 					OperatorType::Subtract => left - right,
 					OperatorType::ParenthesisLeft | OperatorType::ParenthesisRight => {
-						return None;
+						return Err(EvaluateRollError::Invalid);
 					}
 				};
 				stack.push_front(value);
 			}
 		}
 	}
+	if stack.len() != 1 {
+		return Err(EvaluateRollError::Invalid);
+	}
+
+	Ok((stack.pop_front().unwrap(), dice_rolls))
+}
+
+/// Checks that a Reverse Polish Notation expression could be evaluated,
+/// without rolling anything.
+///
+/// This catches the same problems that [`evaluate_roll_rpn`] would, given the
+/// same `dice_budget`, except for running out of it on the extra dice rolled
+/// for rerolls and explosions, since those depend on what's rolled.
+pub fn check_roll_rpn(rpn: &[Evaluable], dice_budget: u64) -> Result<(), EvaluateRollError> {
+	let mut dice_count = 0_u64;
+	let mut stack_len = 0_usize;
+
+	for operand in rpn {
+		match operand {
+			Evaluable::Dice(dice) => {
+				dice_count = dice_count.saturating_add(u64::from(dice.count));
+				stack_len += 1;
+			}
+			Evaluable::Num(_) => {
+				stack_len += 1;
+			}
+			Evaluable::Operator(op) => {
+				if stack_len < 2
+					|| matches!(
+						op.op,
+						OperatorType::ParenthesisLeft | OperatorType::ParenthesisRight
+					) {
+					return Err(EvaluateRollError::Invalid);
+				}
+				stack_len -= 1;
+			}
+		}
+	}
+	if stack_len != 1 {
+		return Err(EvaluateRollError::Invalid);
+	}
+	if dice_count > dice_budget {
+		return Err(EvaluateRollError::TooLarge);
+	}
+
+	Ok(())
+}
+
+/// Writes out a Reverse Polish Notation expression with each set of dice
+/// replaced by what it rolled, eg. `((4+5) + 3) * 2` for `(2d6 + 3) * 2`.
+///
+/// `dice_rolls` must be the rolls from evaluating `rpn`.
+///
+/// Returns [`None`] if the result would be longer than `max_length`
+/// characters.
+pub fn show_roll_work(
+	rpn: &[Evaluable],
+	dice_rolls: &[DiceRolls],
+	max_length: usize,
+) -> Option<String> {
+	let mut dice_rolls = dice_rolls.iter();
+	write_out_rpn(rpn, max_length, |_| {
+		let dice_roll = dice_rolls.next()?;
+		// Show the individual dice if they're all added up, otherwise it's not clear
+		// how the total was reached
+		Some(
+			if dice_roll.dice.modifier.is_none()
+				&& dice_roll.dice.success_threshold.is_none()
+				&& dice_roll.rolls.len() > 1
+			{
+				format!(
+					"({})",
+					dice_roll
+						.rolls
+						.iter()
+						.map(|roll| roll.value.to_string())
+						.collect::<Vec<_>>()
+						.join("+")
+				)
+			} else {
+				dice_roll.total.to_string()
+			},
+		)
+	})
+}
+
+/// Writes out a Reverse Polish Notation expression the way it's read, with
+/// only the parentheses that matter, eg. `(2d6 + 3) * 2`.
+///
+/// Returns [`None`] if the result would be longer than `max_length`
+/// characters.
+pub fn show_roll_structure(rpn: &[Evaluable], max_length: usize) -> Option<String> {
+	write_out_rpn(rpn, max_length, |dice| Some(dice.to_string()))
+}
+
+/// Writes out a Reverse Polish Notation expression in infix notation, using
+/// `write_dice` to write out each set of dice in the order they appear.
+///
+/// Returns [`None`] if the result would be longer than `max_length`
+/// characters, or if `write_dice` does.
+fn write_out_rpn<F>(rpn: &[Evaluable], max_length: usize, mut write_dice: F) -> Option<String>
+where
+	F: FnMut(&Dice) -> Option<String>,
+{
+	/// Values on their own never need to be put in parentheses.
+	const VALUE_PRECEDENCE: u8 = u8::MAX;
+
+	let mut stack = Vec::new();
+	for operand in rpn {
+		let (work, precedence) = match operand {
+			Evaluable::Dice(dice) => (write_dice(dice)?, VALUE_PRECEDENCE),
+			Evaluable::Num(value) => (value.to_string(), VALUE_PRECEDENCE),
+			Evaluable::Operator(op) => {
+				let (right, right_precedence) = stack.pop()?;
+				let (left, left_precedence) = stack.pop()?;
+				let symbol = match op.op {
+					OperatorType::Exponent => '^',
+					OperatorType::Multiply => '*',
+					OperatorType::Divide => '/',
+					OperatorType::FloorDivide => '\\',
+					OperatorType::Add => '+',
+					OperatorType::Subtract => '-',
+					OperatorType::ParenthesisLeft | OperatorType::ParenthesisRight => return None,
+				};
+				let wrap_left = left_precedence < op.precedence
+					|| (left_precedence == op.precedence && !op.associates_left);
+				let wrap_right = right_precedence < op.precedence
+					|| (right_precedence == op.precedence && op.associates_left);
+				let work = format!(
+					"{} {} {}",
+					if wrap_left {
+						format!("({})", left)
+					} else {
+						left
+					},
+					symbol,
+					if wrap_right {
+						format!("({})", right)
+					} else {
+						right
+					}
+				);
+				(work, op.precedence)
+			}
+		};
+		if work.len() > max_length {
+			return None;
+		}
+		stack.push((work, precedence));
+	}
 	if stack.len() != 1 {
 		return None;
 	}
 
-	Some((stack.pop_front().unwrap(), dice_rolls))
+	stack.pop().map(|(work, _)| work)
+}
+
+/// Checks whether a result is too large to be represented exactly, meaning it
+/// may not be accurate.
+///
+/// This is the case if it's infinite (or not a number), or if it's beyond the
+/// range where every integer can be represented.
+pub fn exceeds_precision(value: f64) -> bool {
+	!value.is_finite() || value.abs() > MAX_SAFE_INTEGER
+}
+
+/// Marks which rolls are kept, for dice that only keep the best or worst few.
+///
+/// The sort is stable, so ties at the edge of the kept dice go to the earlier
+/// ones, which keeps the display predictable.
+fn mark_kept_rolls(rolls: &mut [DieRoll], modifier: Option<&DiceModifier>) {
+	let mut ranked_indices = (0..rolls.len()).collect::<Vec<_>>();
+	let keep_count = match modifier {
+		Some(DiceModifier::Best(n)) => {
+			ranked_indices.sort_by_key(|i| Reverse(rolls[*i].value));
+			*n as usize
+		}
+		Some(DiceModifier::Worst(n)) => {
+			ranked_indices.sort_by_key(|i| rolls[*i].value);
+			*n as usize
+		}
+		None => rolls.len(),
+	};
+	for i in ranked_indices.into_iter().skip(keep_count) {
+		rolls[i].kept = false;
+	}
+}
+
+/// Takes one extra die out of both the dice's own limit and the budget for the
+/// whole expression, failing if either has run out.
+fn charge_extra_die(
+	extra_dice_left: &mut u32,
+	dice_budget: &mut u64,
+) -> Result<(), EvaluateRollError> {
+	*extra_dice_left = extra_dice_left
+		.checked_sub(1)
+		.ok_or(EvaluateRollError::TooLarge)?;
+	charge_budget(dice_budget)
+}
+
+/// Takes one extra die out of the budget for the whole expression, failing if
+/// it has run out.
+fn charge_budget(dice_budget: &mut u64) -> Result<(), EvaluateRollError> {
+	*dice_budget = dice_budget
+		.checked_sub(1)
+		.ok_or(EvaluateRollError::TooLarge)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Parses and evaluates an expression, returning only the result.
+	fn evaluate(expression: &str) -> f64 {
+		let rpn = parse_roll_command(expression).expect("the expression should parse");
+		let mut dice_budget = MAX_DICE_PER_COMMAND;
+		let (value, _) =
+			evaluate_roll_rpn(&rpn, &mut dice_budget).expect("the expression should evaluate");
+		value
+	}
+
+	/// Parses an expression and writes it back out, to see how it was read.
+	fn structure(expression: &str) -> String {
+		let rpn = parse_roll_command(expression).expect("the expression should parse");
+		show_roll_structure(&rpn, usize::MAX).expect("the expression should be shown")
+	}
+
+	#[test]
+	fn exceeds_precision_when_infinite() {
+		let value = evaluate("10^309");
+		assert!(value.is_infinite());
+		assert!(exceeds_precision(value));
+	}
+
+	#[test]
+	fn exceeds_precision_past_safe_integers() {
+		assert!(exceeds_precision(evaluate("2^53 + 1")));
+		assert!(!exceeds_precision(evaluate("2^53 - 1")));
+	}
+
+	#[test]
+	fn great_weapon_fighting_rerolls_once() {
+		// Every face of a d2 is low enough to reroll, including the rerolled values
+		let dice = "1000d2gwf".parse::<Dice>().unwrap();
+		let mut dice_budget = MAX_DICE_PER_COMMAND;
+		let (rolls, _) = dice.eval(&mut dice_budget).unwrap();
+		assert_eq!(rolls.len(), 1000);
+		for roll in rolls {
+			assert_eq!(roll.rerolled_from.len(), 1);
+		}
+	}
+
+	#[test]
+	fn great_weapon_fighting_comes_out_of_the_budget() {
+		// The dice themselves use up the whole budget, and every d2 gets rerolled
+		let rpn = parse_roll_command("50d2gwf").unwrap();
+		let mut dice_budget = 50;
+		assert!(matches!(
+			evaluate_roll_rpn(&rpn, &mut dice_budget),
+			Err(EvaluateRollError::TooLarge)
+		));
+	}
+
+	#[test]
+	fn each_bonus_is_added_before_keeping_the_best() {
+		let dice = "4d6b3+e2".parse::<Dice>().unwrap();
+		assert_eq!(dice.each_bonus, 2);
+		assert!(matches!(dice.modifier, Some(DiceModifier::Best(3))));
+		for _ in 0..100 {
+			let mut dice_budget = MAX_DICE_PER_COMMAND;
+			let (rolls, total) = dice.eval(&mut dice_budget).unwrap();
+			assert!(rolls.iter().all(|roll| (3..=8).contains(&roll.value)));
+
+			// The best values are kept, with the bonus counted once for each kept die
+			let mut values = rolls.iter().map(|roll| roll.value).collect::<Vec<_>>();
+			values.sort_unstable_by(|a, b| b.cmp(a));
+			assert_eq!(rolls.iter().filter(|roll| roll.kept).count(), 3);
+			assert_eq!(
+				total,
+				values[..3].iter().map(|v| i64::from(*v)).sum::<i64>()
+			);
+		}
+	}
+
+	#[test]
+	fn each_bonus_in_an_expression() {
+		let rpn = parse_roll_command("2d6b1+e3 + 1").unwrap();
+		assert!(matches!(
+			rpn.as_slice(),
+			[Evaluable::Dice(dice), Evaluable::Num(_), Evaluable::Operator(_)] if dice.each_bonus == 3
+		));
+	}
+
+	#[test]
+	fn each_bonus_cannot_overflow() {
+		assert!(matches!(
+			"3d6+e4294967295".parse::<Dice>(),
+			Err(ParseDiceError::TooLargeBonus)
+		));
+		assert!(matches!(
+			"3d4294967295+e1".parse::<Dice>(),
+			Err(ParseDiceError::TooLargeBonus)
+		));
+	}
+
+	/// Makes a plain roll with the given value, as if it had just been rolled.
+	fn roll(value: u32) -> DieRoll {
+		DieRoll {
+			value,
+			rerolled_from: Vec::new(),
+			explosions: 0,
+			last_explosion: 0,
+			kept: true,
+		}
+	}
+
+	#[test]
+	fn ties_keep_only_the_best_few() {
+		let mut rolls = [6, 4, 6, 6, 1].map(roll);
+		mark_kept_rolls(&mut rolls, Some(&DiceModifier::Best(2)));
+		let kept = rolls.iter().map(|r| r.kept).collect::<Vec<_>>();
+		assert_eq!(kept, [true, false, true, false, false]);
+	}
+
+	#[test]
+	fn ties_keep_only_the_worst_few() {
+		let mut rolls = [2, 2, 5, 2].map(roll);
+		mark_kept_rolls(&mut rolls, Some(&DiceModifier::Worst(1)));
+		let kept = rolls.iter().map(|r| r.kept).collect::<Vec<_>>();
+		assert_eq!(kept, [true, false, false, false]);
+	}
+
+	#[test]
+	fn all_rolls_kept_without_a_modifier() {
+		let mut rolls = [3, 3, 3].map(roll);
+		mark_kept_rolls(&mut rolls, None);
+		assert!(rolls.iter().all(|r| r.kept));
+	}
+
+	#[test]
+	fn crit_doubles_the_dice() {
+		assert_eq!(structure("crit 2d6 + 3"), "4d6 + 3");
+	}
+
+	#[test]
+	fn crit_doubles_a_single_die() {
+		assert_eq!(structure("crit d6"), "2d6");
+	}
+
+	#[test]
+	fn half_covers_the_parentheses() {
+		assert_eq!(structure("half (2d6 + 3)"), "(2d6 + 3) \\ 2");
+	}
+
+	#[test]
+	fn trailing_crit_is_rejected() {
+		assert!(parse_roll_command("2d6 crit").is_err());
+	}
+
+	#[test]
+	fn exploding_dice_can_target_past_their_maximum() {
+		let dice = "3d6!>7".parse::<Dice>().unwrap();
+		assert_eq!(dice.success_threshold, Some(7));
+		assert!(matches!(
+			"3d6>7".parse::<Dice>(),
+			Err(ParseDiceError::ImpossibleThreshold)
+		));
+	}
+
+	#[test]
+	fn explosion_chains_end_on_their_last_value() {
+		// A d2 only stops exploding once it lands on a 1, which the bonus is added to
+		let dice = "10d2!+e3".parse::<Dice>().unwrap();
+		for _ in 0..100 {
+			let mut dice_budget = MAX_DICE_PER_COMMAND;
+			let (rolls, _) = dice.eval(&mut dice_budget).unwrap();
+			for roll in rolls.iter().filter(|roll| roll.explosions > 0) {
+				assert_eq!(roll.last_explosion, 4);
+				assert_eq!(roll.value, roll.explosions * 2 + 4);
+			}
+		}
+	}
+
+	#[test]
+	fn too_many_explosions_are_too_large() {
+		// Half of the dice explode each time, so they go well past the limit
+		let rpn = parse_roll_command("1000d2!").unwrap();
+		let mut dice_budget = MAX_DICE_PER_COMMAND;
+		assert!(matches!(
+			evaluate_roll_rpn(&rpn, &mut dice_budget),
+			Err(EvaluateRollError::TooLarge)
+		));
+	}
+
+	#[test]
+	fn explosions_come_out_of_the_budget() {
+		// The dice themselves use up the whole budget, so any explosion runs it out
+		let rpn = parse_roll_command("50d2!").unwrap();
+		let mut dice_budget = 50;
+		assert!(matches!(
+			evaluate_roll_rpn(&rpn, &mut dice_budget),
+			Err(EvaluateRollError::TooLarge)
+		));
+	}
+
+	#[test]
+	fn too_many_rerolls_are_too_large() {
+		// Half of the dice need rerolling each time, so they go well past the limit
+		let rpn = parse_roll_command("1000d2r1").unwrap();
+		let mut dice_budget = MAX_DICE_PER_COMMAND;
+		assert!(matches!(
+			evaluate_roll_rpn(&rpn, &mut dice_budget),
+			Err(EvaluateRollError::TooLarge)
+		));
+	}
+
+	#[test]
+	fn rerolls_come_out_of_the_budget() {
+		// The dice themselves use up the whole budget, so any reroll runs it out
+		let rpn = parse_roll_command("50d2ro1").unwrap();
+		let mut dice_budget = 50;
+		assert!(matches!(
+			evaluate_roll_rpn(&rpn, &mut dice_budget),
+			Err(EvaluateRollError::TooLarge)
+		));
+	}
 }
@@ -1,7 +1,42 @@
+// Macros
+/// Defines a command, running its body through
+/// [`catch_panic`](crate::util::catch_panic) so that the user still gets a
+/// reply if it panics.
+///
+/// `command` isn't imported anywhere else, so a command can't be defined
+/// without this.
+macro_rules! panic_safe {
+	(
+		$(#[doc = $doc:tt])*
+		#[command($($args:tt)*)]
+		pub async fn $name:ident($ctx:ident: $ctx_type:ident<'_> $($params:tt)*) -> $return_type:ty {
+			$($body:tt)*
+		}
+	) => {
+		$(#[doc = $doc])*
+		#[::poise::command($($args)*)]
+		pub async fn $name($ctx: $ctx_type<'_> $($params)*) -> $return_type {
+			$crate::util::catch_panic(panic_safe!(@context $ctx_type, $ctx), async move {
+				$($body)*
+			})
+			.await
+		}
+	};
+	(@context PoisePrefixContext, $ctx:ident) => {
+		$crate::PoiseContext::Prefix($ctx)
+	};
+	(@context PoiseContext, $ctx:ident) => {
+		$ctx
+	};
+}
+
 // Modules
 mod chance;
 mod playback;
+mod playlists;
+mod settings;
+mod tts;
 mod util;
 
 // Public Exports
-pub use self::{chance::*, playback::*, util::*};
+pub use self::{chance::*, playback::*, playlists::*, settings::*, tts::*, util::*};
@@ -1,35 +1,61 @@
 // Uses
-use std::time::Duration;
+use std::{
+	collections::VecDeque,
+	fmt::Display,
+	iter::once,
+	sync::{Arc, PoisonError},
+	time::{Duration, Instant},
+};
 
 use anyhow::Context;
-use lavalink_rs::LavalinkClient;
-use parse_duration::parse as parse_duration;
-use poise::{
-	command,
-	serenity::model::{
-		guild::Guild,
-		id::{ChannelId, UserId},
-		misc::Mentionable,
-	},
+use chrono::{DateTime, Utc};
+use lavalink_rs::{
+	model::{GuildId as LavalinkGuildId, Info, Track, TrackQueue},
+	LavalinkClient,
 };
-use songbird::{
-	id::{ChannelId as SongbirdChannelId, GuildId},
-	Songbird,
+use parse_duration::parse as parse_duration;
+use poise::serenity::model::{
+	channel::{ChannelType, GuildChannel},
+	guild::{Guild, Member},
+	id::{ChannelId, GuildId as SerenityGuildId, RoleId, UserId},
+	misc::Mentionable,
+	user::User,
 };
-use sponsor_block::ActionableSegment;
+use rand::{seq::SliceRandom, thread_rng};
+use songbird::id::{ChannelId as SongbirdChannelId, GuildId};
+use tokio::{spawn, task::JoinHandle, time::sleep};
 use url::Url;
 
 use crate::{
-	constants::{ACCEPTED_CATEGORIES, MILLIS_PER_SECOND, MILLIS_PER_SECOND_F32},
-	segments::SkipSegment,
+	constants::{MAIN_COLOUR, MILLIS_PER_SECOND, MILLIS_PER_SECOND_F32},
+	db::{get_blocked_sources, get_guild_settings, save_guild_volume},
+	filters::{apply_filters, FilterKind},
+	lyrics::{fetch_lyrics, guess_song, strip_section_labels},
+	segments::{
+		fetch_skip_segments,
+		get_youtube_video_id,
+		segments_for_guild,
+		GuildSegments,
+		SkipSegment,
+	},
 	util::{
 		chop_str,
+		confirm,
+		contains_filtered_word,
+		display_time,
 		display_timecode,
 		display_timecode_f32,
+		escape_str,
 		push_chopped_str,
 		reply,
 		reply_embed,
+		reply_file,
+		reply_paginated,
+		reply_plain,
+		split_into_pages,
+		PageJump,
 	},
+	Data,
 	Error,
 	PoiseContext,
 };
@@ -38,30 +64,460 @@ use crate::{
 const MAX_DESCRIPTION_LENGTH: usize = 2048;
 const DESCRIPTION_LENGTH_CUTOFF: usize = MAX_DESCRIPTION_LENGTH - 512;
 const MAX_LIST_ENTRY_LENGTH: usize = 60;
-const MAX_SINGLE_ENTRY_LENGTH: usize = 40;
+pub(super) const MAX_SINGLE_ENTRY_LENGTH: usize = 40;
 const UNKNOWN_TITLE: &str = "Unknown title";
+const UNKNOWN_AUTHOR: &str = "Unknown artist";
 const LIVE_INDICATOR: &str = "\u{1f534} **LIVE**";
+const NOT_A_DJ_MESSAGE: &str = "Only members with the DJ role can do that.";
+const LAVALINK_UNAVAILABLE_MESSAGE: &str =
+	"The audio backend is currently unavailable, please try again shortly.";
+pub(super) const FILTERED_MESSAGE: &str = "That contains words that aren't allowed in this server.";
+const QUEUE_EXPORT_FILENAME: &str = "queue.txt";
+/// The content type that says nothing about what an attachment is.
+const GENERIC_CONTENT_TYPE: &str = "application/octet-stream";
+/// The file extensions of attachments that are playable, for when their content
+/// type doesn't say.
+const PLAYABLE_EXTENSIONS: [&str; 13] = [
+	"aac", "flac", "m4a", "mka", "mkv", "mov", "mp3", "mp4", "oga", "ogg", "opus", "wav", "webm",
+];
+/// The option used to search SoundCloud instead of YouTube, eg. `--sc <query>`.
+const SOUNDCLOUD_OPTION: &str = "--sc";
+/// The options that override a guild's `nowplaying` style.
+const COMPACT_OPTION: &str = "--compact";
+const FULL_OPTION: &str = "--full";
+/// The option that leaves section labels like `[Chorus]` out of lyrics.
+const CLEAN_OPTION: &str = "--clean";
+/// The name that turns off the filter from `filter`.
+const FILTER_OFF_OPTION: &str = "off";
+/// Display names for well-known track sources, matched against the end of a
+/// track URI's host.
+const SOURCE_NAMES: [(&str, &str); 8] = [
+	("youtube.com", "YouTube"),
+	("youtu.be", "YouTube"),
+	("soundcloud.com", "SoundCloud"),
+	("bandcamp.com", "Bandcamp"),
+	("twitch.tv", "Twitch"),
+	("vimeo.com", "Vimeo"),
+	("discordapp.com", "Discord"),
+	("discordapp.net", "Discord"),
+];
+/// The most search results that a guild can have queued at once.
+pub(super) const MAX_SEARCH_RESULTS: usize = 5;
+
+/// Repeat skips from the same user within this period are ignored.
+const SKIP_DEBOUNCE_PERIOD: Duration = Duration::from_secs(2);
+/// Queues longer than this have to be confirmed before they're cleared.
+const CLEAR_CONFIRMATION_THRESHOLD: usize = 20;
+const CLEAR_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a shuffle preview can be applied for.
+const SHUFFLE_PREVIEW_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many entries are shown on each page of the queue.
+const QUEUE_PAGE_SIZE: usize = 10;
+/// How long the queue's page buttons stay usable after the last time they were
+/// pressed.
+const QUEUE_PAGINATION_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long the lyrics can be paged through, which is long enough to read
+/// along with most songs.
+const LYRICS_PAGINATION_TIMEOUT: Duration = Duration::from_secs(300);
+/// Lavalink's default volume, as a percentage.
+const DEFAULT_VOLUME: u16 = 100;
+/// The loudest volume a guild can use, as a percentage. Anything louder
+/// distorts badly.
+pub(super) const MAX_VOLUME: u16 = 150;
+/// Each guild can only refresh the segments for a track once in this period, to
+/// go easy on the SponsorBlock API.
+const REFRESH_SEGMENTS_COOLDOWN: Duration = Duration::from_secs(30);
+/// The most tracks that `warmsegments` fetches the segments for at once.
+const MAX_WARM_TRACKS: usize = 100;
+/// The longest a track can be scheduled ahead of time with `playin`.
+const MAX_SCHEDULE_DELAY: Duration = Duration::from_secs(60 * 60);
+/// How many of the other results for a search are kept to fall back on.
+const MAX_SEARCH_FALLBACKS: usize = 2;
+/// How many searches each guild keeps alternatives around for at once.
+const MAX_PENDING_SEARCH_FALLBACKS: usize = 20;
+/// Adding at least this many tracks at once shows a progress message while
+/// they're queued.
+const PLAY_PROGRESS_THRESHOLD: usize = 20;
+/// How often the progress message is updated, to stay well clear of Discord's
+/// rate limits on editing messages.
+const PLAY_PROGRESS_INTERVAL: Duration = Duration::from_secs(3);
+/// How many tracks are kept in each guild's play history.
+const MAX_HISTORY_LENGTH: usize = 50;
+/// How many tracks `previous` can go back through in each guild.
+const MAX_PREVIOUS_TRACKS: usize = 50;
+/// How many tracks `history` shows if it isn't asked for a specific number.
+const DEFAULT_HISTORY_COUNT: usize = 10;
+
+// Definitions
+/// The per-guild settings that change how tracks are played back.
+///
+/// Any guild without an entry is playing back with the defaults.
+#[derive(Debug, Clone)]
+pub struct PlaybackModifiers {
+	/// The volume to play at, which is kept while muted so it can be restored.
+	pub volume: u16,
+	pub muted: bool,
+	pub loop_mode: LoopMode,
+}
+
+impl Default for PlaybackModifiers {
+	fn default() -> Self {
+		Self {
+			volume: DEFAULT_VOLUME,
+			muted: false,
+			loop_mode: LoopMode::Off,
+		}
+	}
+}
+
+impl PlaybackModifiers {
+	/// Summarizes the modifiers in a single line, leaving out any that are at
+	/// their defaults.
+	///
+	/// Returns [`None`] if every modifier is at its default.
+	fn display(&self) -> Option<String> {
+		let mut parts = Vec::new();
+		if let Some(loop_mode_display) = self.loop_mode.display() {
+			parts.push(loop_mode_display.to_owned());
+		}
+		if self.muted {
+			parts.push("\u{1f507} Muted".to_owned());
+		} else if self.volume != DEFAULT_VOLUME {
+			parts.push(format!("\u{1f50a} {}%", self.volume));
+		}
+
+		if parts.is_empty() {
+			None
+		} else {
+			Some(parts.join(" \u{b7} "))
+		}
+	}
+}
+
+/// What gets played again once a track finishes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LoopMode {
+	Off,
+	Track,
+	Queue,
+}
+
+impl LoopMode {
+	fn display(self) -> Option<&'static str> {
+		match self {
+			Self::Off => None,
+			Self::Track => Some("\u{1f502} Track"),
+			Self::Queue => Some("\u{1f501} Queue"),
+		}
+	}
+}
+
+/// A track that's been scheduled to be queued later with `playin`.
+#[derive(Debug)]
+pub struct ScheduledPlay {
+	pub play_at: Instant,
+	pub task: JoinHandle<()>,
+}
+
+/// Other results for a search, to fall back on if the track that was queued for
+/// it can't be played.
+#[derive(Debug)]
+pub struct SearchFallback {
+	/// The encoded track string of the track that was queued.
+	pub track: String,
+	/// The next results for the search, best first.
+	pub alternatives: VecDeque<Track>,
+	pub requester: UserId,
+	/// When the track started playing, if it has.
+	pub started_at: Option<Instant>,
+}
+
+/// A track that started playing in a guild, as remembered by its play history.
+#[derive(Debug, Clone)]
+pub struct PlayedTrack {
+	/// The encoded track string, so it can be played again as-is.
+	pub track: String,
+	pub title: String,
+	pub uri: String,
+	pub requester: Option<UserId>,
+	pub played_at: DateTime<Utc>,
+}
+
+/// A piece of a `play` query.
+#[derive(Debug)]
+enum QueryPart {
+	Url(String),
+	Search(String),
+}
+
+/// Where plain-text (non-URL) queries are searched.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SearchSource {
+	YouTube,
+	SoundCloud,
+}
 
 // Functions
-async fn join_internal<G, C>(
-	songbird: &Songbird,
-	lavalink: &LavalinkClient,
-	guild_id: G,
-	channel_id: C,
-) -> Result<(), Error>
+async fn join_internal<G, C>(data: &Data, guild_id: G, channel_id: C) -> Result<(), Error>
 where
 	G: Into<GuildId>,
 	C: Into<SongbirdChannelId>,
 {
-	let (_, handler) = songbird.join_gateway(guild_id, channel_id).await;
+	let guild_id = guild_id.into();
+	let (call, handler) = data.songbird.join_gateway(guild_id, channel_id).await;
 
 	match handler {
-		Ok(connection_info) => Ok(lavalink
+		Ok(connection_info) => data
+			.lavalink
 			.create_session_with_songbird(&connection_info)
 			.await
-			.map_err(Box::new)?),
-		Err(e) => Err(Box::new(e)),
+			.map_err(Box::new)?,
+		Err(e) => return Err(Box::new(e)),
+	}
+
+	let guild_settings = {
+		let conn = data.db_pool.get()?;
+		get_guild_settings(&conn, guild_id.0 as i64)?
+	};
+
+	// Radium never needs to hear anything, so it deafens itself unless the guild
+	// has turned that off
+	if guild_settings.self_deafen {
+		call.lock().await.deafen(true).await?;
+	}
+
+	// Start at the guild's volume right away, so the first track isn't at the
+	// default
+	if let Some(volume) = guild_settings.volume {
+		let volume = (volume.max(0) as u16).min(MAX_VOLUME);
+		data.lavalink
+			.volume(guild_id.0, volume)
+			.await
+			.map_err(Box::new)?;
+		data.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.entry(SerenityGuildId(guild_id.0))
+			.or_default()
+			.volume = volume;
+	}
+
+	Ok(())
+}
+
+/// Builds the Lavalink search query for a plain-text query, using the search
+/// prefix for the chosen source.
+fn build_search_query(query: &str, source: SearchSource) -> String {
+	let prefix = match source {
+		SearchSource::YouTube => "ytsearch:",
+		SearchSource::SoundCloud => "scsearch:",
+	};
+	format!("{}{}", prefix, query)
+}
+
+/// Records that a track started playing in the guild, forgetting the oldest
+/// track in its history if it's full.
+pub fn record_played_track(data: &Data, guild_id: SerenityGuildId, played_track: PlayedTrack) {
+	let mut hash_map = data
+		.play_history
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner);
+	let history = hash_map.entry(guild_id).or_default();
+	history.push_front(played_track);
+	history.truncate(MAX_HISTORY_LENGTH);
+}
+
+/// Records that a track started playing in the guild, so `previous` can go
+/// back to it, forgetting the oldest track if there are too many.
+pub fn record_previous_track(data: &Data, guild_id: SerenityGuildId, track: Track) {
+	let mut previous_tracks = data
+		.previous_tracks
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner);
+	let guild_tracks = previous_tracks.entry(guild_id).or_default();
+	guild_tracks.push_front(track);
+	guild_tracks.truncate(MAX_PREVIOUS_TRACKS);
+}
+
+/// Keeps the alternatives for a search around until its track is played.
+///
+/// Only the most recent searches are kept for each guild.
+pub fn record_search_fallback(data: &Data, guild_id: SerenityGuildId, fallback: SearchFallback) {
+	let mut search_fallbacks = data
+		.search_fallbacks
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner);
+	let guild_fallbacks = search_fallbacks.entry(guild_id).or_default();
+	if guild_fallbacks.len() >= MAX_PENDING_SEARCH_FALLBACKS {
+		guild_fallbacks.pop_front();
+	}
+	guild_fallbacks.push_back(fallback);
+}
+
+/// Checks whether some text is caught by the profanity filter, if the guild has
+/// it turned on.
+pub(super) fn violates_profanity_filter(
+	data: &Data,
+	guild_id: SerenityGuildId,
+	text: &str,
+) -> Result<bool, Error> {
+	let profanity_filter = {
+		let conn = data.db_pool.get()?;
+		get_guild_settings(&conn, guild_id.0 as i64)?.profanity_filter
+	};
+
+	Ok(profanity_filter && contains_filtered_word(text, &data.filtered_words))
+}
+
+/// Checks whether an attachment looks like something that can be played.
+///
+/// Some clients upload audio with a generic content type (or none at all), so
+/// the filename's extension is checked when the content type doesn't say.
+fn is_playable_attachment(content_type: Option<&str>, filename: &str) -> bool {
+	match content_type {
+		Some(t) if t.starts_with("audio") || t.starts_with("video") => true,
+		Some(t) if !t.starts_with(GENERIC_CONTENT_TYPE) => false,
+		_ => filename.rsplit_once('.').map_or(false, |(_, extension)| {
+			PLAYABLE_EXTENSIONS
+				.iter()
+				.any(|playable| playable.eq_ignore_ascii_case(extension))
+		}),
+	}
+}
+
+/// Checks whether a track URI is from one of the blocked sources, which are
+/// matched against the URI's host.
+///
+/// A source blocks its own host and any of its subdomains, so blocking
+/// `youtube.com` blocks `www.youtube.com` but not `notyoutube.com`.
+pub(super) fn is_blocked_source(uri: &str, blocked_sources: &[String]) -> bool {
+	Url::parse(uri)
+		.ok()
+		.and_then(|url| url.host_str().map(str::to_lowercase))
+		.map_or(false, |host| {
+			blocked_sources
+				.iter()
+				.any(|source| host == *source || host.ends_with(&format!(".{}", source)))
+		})
+}
+
+/// Adds up how long a set of tracks will take to play, in milliseconds.
+///
+/// Streams don't have a meaningful length, so they're left out.
+fn sum_track_durations(tracks: &[Track]) -> u64 {
+	tracks
+		.iter()
+		.filter_map(|track| track.info.as_ref())
+		.filter(|info| !info.is_stream)
+		.map(|info| info.length)
+		.sum()
+}
+
+/// Gets a display name for the source of a track URI, eg. `YouTube`.
+///
+/// Sources that aren't well-known are named by their host.
+fn get_source_name(uri: &str) -> Option<String> {
+	let url = Url::parse(uri).ok()?;
+	let host = url.host_str()?.to_lowercase();
+	Some(
+		SOURCE_NAMES
+			.iter()
+			.find(|(source_host, _)| host.ends_with(source_host))
+			.map_or_else(
+				|| host.trim_start_matches("www.").to_owned(),
+				|(_, source_name)| (*source_name).to_owned(),
+			),
+	)
+}
+
+/// Describes where a set of tracks came from, eg. `YouTube playlist`.
+fn describe_track_source(tracks: &[Track], from_playlist: bool) -> String {
+	let mut source_names = tracks
+		.iter()
+		.filter_map(|track| track.info.as_ref())
+		.filter_map(|info| get_source_name(info.uri.as_str()));
+	let first_source_name = if let Some(first_source_name) = source_names.next() {
+		first_source_name
+	} else {
+		return "unknown sources".to_owned();
+	};
+	if source_names.any(|source_name| source_name != first_source_name) {
+		return "multiple sources".to_owned();
+	}
+
+	if from_playlist {
+		format!("{} playlist", first_source_name)
+	} else {
+		first_source_name
+	}
+}
+
+/// Lets the user know that a request to the audio server (Lavalink) failed,
+/// and logs the error.
+///
+/// A failure here almost always means the audio server is down, which there's
+/// nothing the user can do about other than try again later.
+async fn report_lavalink_failure<E>(
+	ctx: PoiseContext<'_>,
+	action: &str,
+	error: E,
+) -> Result<(), Error>
+where
+	E: Display,
+{
+	reply(ctx, LAVALINK_UNAVAILABLE_MESSAGE).await?;
+	eprintln!("Failed to {}: {}", action, error);
+	Ok(())
+}
+
+/// Checks whether the user is allowed to use the playback control commands,
+/// like `skip` and `clear`.
+///
+/// If the guild has a DJ role set, only members with that role and those who
+/// can manage the server can. Otherwise, everyone can.
+pub(super) async fn can_control_playback(
+	ctx: PoiseContext<'_>,
+	guild: &Guild,
+) -> Result<bool, Error> {
+	let dj_role_id = {
+		let conn = ctx.data().db_pool.get()?;
+		get_guild_settings(&conn, guild.id.0 as i64)?.dj_role_id
+	};
+	if dj_role_id.is_none() {
+		return Ok(true);
+	}
+
+	is_dj(ctx, guild, dj_role_id).await
+}
+
+/// Checks whether the authour has the guild's DJ role or manages the guild.
+///
+/// Unlike [`can_control_playback`], this is only true for managers when the
+/// guild doesn't have a DJ role set.
+async fn is_dj(
+	ctx: PoiseContext<'_>,
+	guild: &Guild,
+	dj_role_id: Option<i64>,
+) -> Result<bool, Error> {
+	let member = guild.id.member(ctx.discord(), ctx.author().id).await?;
+	Ok(dj_role_id.map_or(false, |dj_role_id| {
+		member.roles.contains(&RoleId(dj_role_id as u64))
+	}) || is_server_manager(guild, &member))
+}
+
+/// Checks whether a member owns the guild or is allowed to manage it.
+pub(super) fn is_server_manager(guild: &Guild, member: &Member) -> bool {
+	if guild.owner_id == member.user.id {
+		return true;
 	}
+
+	// The @everyone role shares its ID with the guild
+	member
+		.roles
+		.iter()
+		.chain(once(&RoleId(guild.id.0)))
+		.filter_map(|role_id| guild.roles.get(role_id))
+		.any(|role| role.permissions.administrator() || role.permissions.manage_guild())
 }
 
 fn authour_channel_id(guild: &Guild, authour_id: UserId) -> Option<ChannelId> {
@@ -71,9 +527,68 @@ fn authour_channel_id(guild: &Guild, authour_id: UserId) -> Option<ChannelId> {
 		.and_then(|voice_state| voice_state.channel_id)
 }
 
-/// Have Radium join the voice channel you're in.
+/// Counts the members in a voice channel that aren't bots.
+fn count_listeners(guild: &Guild, channel_id: ChannelId) -> usize {
+	guild
+		.voice_states
+		.values()
+		.filter(|voice_state| voice_state.channel_id == Some(channel_id))
+		.filter(|voice_state| {
+			!voice_state
+				.member
+				.as_ref()
+				.or_else(|| guild.members.get(&voice_state.user_id))
+				.map_or(false, |member| member.user.bot)
+		})
+		.count()
+}
+
+/// Makes sure Radium is in a voice channel in the guild, joining the authour's
+/// channel if it isn't.
+///
+/// Returns whether Radium is connected. If it isn't, the user has already been
+/// told why.
+pub(super) async fn ensure_voice_connection(
+	ctx: PoiseContext<'_>,
+	guild: &Guild,
+) -> Result<bool, Error> {
+	if ctx.data().songbird.get(guild.id).is_some() {
+		return Ok(true);
+	}
+
+	let channel_id = if let Some(channel) = authour_channel_id(guild, ctx.author().id) {
+		channel
+	} else {
+		reply(
+			ctx,
+			"You must use this command while either you or Radium is in a voice channel.",
+		)
+		.await?;
+		return Ok(false);
+	};
+
+	if let Err(e) = join_internal(ctx.data(), guild.id, channel_id).await {
+		reply(
+			ctx,
+			format!("Error joining {}: {}", channel_id.mention(), e),
+		)
+		.await?;
+		return Ok(false);
+	}
+
+	Ok(true)
+}
+
+panic_safe! {
+/// Have Radium join the voice channel you're in, or a specific one.
+///
+/// Choosing a specific channel lets you summon Radium without being in it. If
+/// the server has a DJ role set, only members with it can do that.
 #[command(prefix_command, slash_command, category = "Playback", aliases("j"))]
-pub async fn join(ctx: PoiseContext<'_>) -> Result<(), Error> {
+pub async fn join(
+	ctx: PoiseContext<'_>,
+	#[description = "The voice channel to join."] channel: Option<GuildChannel>,
+) -> Result<(), Error> {
 	let guild = if let Some(guild) = ctx.guild() {
 		guild
 	} else {
@@ -81,21 +596,30 @@ pub async fn join(ctx: PoiseContext<'_>) -> Result<(), Error> {
 		return Ok(());
 	};
 
-	let channel_id = if let Some(channel) = authour_channel_id(&guild, ctx.author().id) {
+	let channel_id = if let Some(channel) = channel {
+		if channel.guild_id != guild.id
+			|| !matches!(channel.kind, ChannelType::Voice | ChannelType::Stage)
+		{
+			reply(ctx, "That isn't a voice channel in this server.").await?;
+			return Ok(());
+		}
+		if !can_control_playback(ctx, &guild).await? {
+			reply(ctx, NOT_A_DJ_MESSAGE).await?;
+			return Ok(());
+		}
+		channel.id
+	} else if let Some(channel) = authour_channel_id(&guild, ctx.author().id) {
 		channel
 	} else {
-		reply(ctx, "You must use this command while in a voice channel.").await?;
+		reply(
+			ctx,
+			"You must use this command while in a voice channel, or choose one to join.",
+		)
+		.await?;
 		return Ok(());
 	};
 
-	match join_internal(
-		&ctx.data().songbird,
-		&ctx.data().lavalink,
-		guild.id,
-		channel_id,
-	)
-	.await
-	{
+	match join_internal(ctx.data(), guild.id, channel_id).await {
 		Ok(_) => reply(ctx, format!("Joined: {}", channel_id.mention())).await?,
 		Err(e) => {
 			reply(
@@ -108,7 +632,9 @@ pub async fn join(ctx: PoiseContext<'_>) -> Result<(), Error> {
 
 	Ok(())
 }
+}
 
+panic_safe! {
 /// Have Radium leave the voice channel it's in, if any.
 #[command(prefix_command, slash_command, category = "Playback", aliases("l"))]
 pub async fn leave(ctx: PoiseContext<'_>) -> Result<(), Error> {
@@ -126,8 +652,15 @@ pub async fn leave(ctx: PoiseContext<'_>) -> Result<(), Error> {
 			reply(ctx, format!("Error leaving voice channel: {}", e)).await?;
 		}
 
+		// The playback state is cleared either way, since Radium has left the voice
+		// channel regardless
 		let lavalink = &ctx.data().lavalink;
-		lavalink.destroy(guild_id.0).await?;
+		let destroy_result = lavalink.destroy(guild_id.0).await;
+		ctx.data().clear_guild_playback_state(guild_id);
+		if let Err(e) = destroy_result {
+			report_lavalink_failure(ctx, "destroy the player", e).await?;
+			return Ok(());
+		}
 
 		reply(ctx, "Left the voice channel.").await?;
 	} else {
@@ -136,22 +669,32 @@ pub async fn leave(ctx: PoiseContext<'_>) -> Result<(), Error> {
 
 	Ok(())
 }
+}
 
+panic_safe! {
 /// Queue up a song or playlist from YouTube, Twitch, Vimeo, SoundCloud, etc.
 ///
 /// Spotify is sadly not supported.
 ///
 /// If Radium is provided with a URL, it will queue up all tracks it finds.
 /// Otherwise it will search the query on YouTube and queue up the first result.
+/// If that result won't play, the next one is played in its place.
+/// You can give several URLs at once, separated by spaces or new lines, and
+/// they'll all be queued in order.
+/// Start the query with `--sc` to search SoundCloud instead.
 /// Age-restricted videos likely won't work.
 ///
 /// You may also use this command with attachments (audio or video files),
 /// though in that case you have to use the non-slash version of the command.
+/// Replying to a message with attachments will queue those up too.
+///
+/// Large playlists take a little while to queue, so their progress is shown as
+/// they're added.
 #[command(prefix_command, slash_command, category = "Playback", aliases("p"))]
 pub async fn play(
 	ctx: PoiseContext<'_>,
 	#[rest]
-	#[description = "What to play."]
+	#[description = "What to play. Start with `--sc` to search SoundCloud."]
 	query: String,
 ) -> Result<(), Error> {
 	let guild = if let Some(guild) = ctx.guild() {
@@ -161,47 +704,41 @@ pub async fn play(
 		return Ok(());
 	};
 
-	let songbird = &ctx.data().songbird;
-	let lavalink = &ctx.data().lavalink;
-
-	if songbird.get(guild.id).is_none() {
-		let channel_id = if let Some(channel) = authour_channel_id(&guild, ctx.author().id) {
-			channel
-		} else {
-			reply(
-				ctx,
-				"You must use this command while either you or Radium is in a voice channel.",
-			)
-			.await?;
-			return Ok(());
-		};
-
-		if let Err(e) = join_internal(songbird, lavalink, guild.id, channel_id).await {
-			reply(
-				ctx,
-				format!("Error joining {}: {}", channel_id.mention(), e),
-			)
-			.await?;
-			return Ok(());
-		}
+	if !ensure_voice_connection(ctx, &guild).await? {
+		return Ok(());
 	}
 
+	let lavalink = &ctx.data().lavalink;
+
 	let mut queueable_tracks = Vec::new();
 
-	// Queue up any attachments
+	// Queue up any attachments, including those on the message being replied to
 	if let PoiseContext::Prefix(prefix_ctx) = ctx {
-		for attachment in &prefix_ctx.msg.attachments {
+		let referenced_attachments = prefix_ctx
+			.msg
+			.referenced_message
+			.as_ref()
+			.map_or(&[][..], |m| m.attachments.as_slice());
+		for attachment in referenced_attachments
+			.iter()
+			.chain(&prefix_ctx.msg.attachments)
+		{
 			// Verify the attachment is playable
-			let playable_content = match &attachment.content_type {
-				Some(t) => t.starts_with("audio") || t.starts_with("video"),
-				None => false,
-			};
-			if !playable_content {
+			if !is_playable_attachment(
+				attachment.content_type.as_deref(),
+				attachment.filename.as_str(),
+			) {
 				continue;
 			}
 
 			// Queue it up
-			let mut query_result = lavalink.auto_search_tracks(&attachment.url).await?;
+			let mut query_result = match lavalink.auto_search_tracks(&attachment.url).await {
+				Ok(query_result) => query_result,
+				Err(e) => {
+					report_lavalink_failure(ctx, "load an attachment", e).await?;
+					return Ok(());
+				}
+			};
 			for track in &mut query_result.tracks {
 				track.info = match &track.info {
 					Some(old_info) => {
@@ -218,233 +755,230 @@ pub async fn play(
 		}
 	}
 
-	// Load the command query - if playable attachments were also with the message,
-	// the attachments are queued first
-	let query_information = lavalink.auto_search_tracks(&query).await?;
-
-	let is_url = Url::parse(query.trim()).is_ok();
-
-	// If the query was a URL, then it's likely a playlist where all retrieved
-	// tracks are desired - otherwise, only queue the top result
-	let query_tracks = if is_url {
-		query_information.tracks.len()
+	// Parse the search source option, if any
+	let (query, search_source) = if let Some(remaining) = query
+		.trim()
+		.strip_prefix(SOUNDCLOUD_OPTION)
+		.filter(|remaining| remaining.is_empty() || remaining.starts_with(char::is_whitespace))
+	{
+		(remaining.trim(), SearchSource::SoundCloud)
 	} else {
-		1
+		(query.trim(), SearchSource::YouTube)
 	};
 
-	queueable_tracks.extend_from_slice(
-		&query_information
-			.tracks
-			.iter()
-			.take(query_tracks)
-			.cloned()
-			.collect::<Vec<_>>(),
-	);
+	// Split the query up into URLs, which are each loaded in full, and the words
+	// around them, which are searched together as one phrase
+	let mut query_parts = Vec::new();
+	let mut search_words = Vec::new();
+	let mut search_index = None;
+	for token in query.split_whitespace() {
+		if Url::parse(token).map_or(false, |url| url.has_host()) {
+			query_parts.push(QueryPart::Url(token.to_owned()));
+		} else {
+			search_index.get_or_insert(query_parts.len());
+			search_words.push(token);
+		}
+	}
+	if let Some(search_index) = search_index {
+		let search_phrase = search_words.join(" ");
 
-	let queueable_tracks_len = queueable_tracks.len();
-	if queueable_tracks_len == 0 {
+		// Searches are checked against the profanity filter, but URLs aren't
+		if violates_profanity_filter(ctx.data(), guild.id, search_phrase.as_str())? {
+			reply(ctx, FILTERED_MESSAGE).await?;
+			return Ok(());
+		}
+
+		query_parts.insert(search_index, QueryPart::Search(search_phrase));
+	}
+
+	// Load the command query - if playable attachments were also with the message,
+	// the attachments are queued first
+	// A lone search can fall back on the next few results if the top one won't play
+	let single_search = matches!(query_parts.as_slice(), [QueryPart::Search(_)]);
+	let mut search_alternatives = Vec::new();
+	let mut from_playlist = false;
+	for query_part in query_parts {
+		match query_part {
+			// URLs are likely playlists where all retrieved tracks are desired
+			QueryPart::Url(url) => {
+				let mut query_information = match lavalink.get_tracks(url).await {
+					Ok(query_information) => query_information,
+					Err(e) => {
+						report_lavalink_failure(ctx, "load a URL", e).await?;
+						return Ok(());
+					}
+				};
+				if query_information.tracks.len() == 1 {
+					fix_raw_file_title(&mut query_information.tracks[0]);
+				} else {
+					from_playlist = true;
+				}
+				queueable_tracks.extend(query_information.tracks);
+			}
+			// Otherwise, only queue the top result (or the top few, if the guild wants
+			// them)
+			QueryPart::Search(search_phrase) => {
+				let search_results = {
+					let conn = ctx.data().db_pool.get()?;
+					get_guild_settings(&conn, guild.id.0 as i64)?.search_results
+				};
+				let query_information = match lavalink
+					.get_tracks(build_search_query(search_phrase.as_str(), search_source))
+					.await
+				{
+					Ok(query_information) => query_information,
+					Err(e) => {
+						report_lavalink_failure(ctx, "search for tracks", e).await?;
+						return Ok(());
+					}
+				};
+				let mut found_tracks = query_information.tracks;
+				let alternatives = found_tracks.split_off(
+					(search_results.max(1) as usize)
+						.min(MAX_SEARCH_RESULTS)
+						.min(found_tracks.len()),
+				);
+				queueable_tracks.extend(found_tracks);
+				if single_search {
+					search_alternatives = alternatives;
+				}
+			}
+		}
+	}
+
+	if queueable_tracks.is_empty() {
 		reply(ctx, "Could not find anything for the search query.").await?;
 		return Ok(());
 	}
 
-	// For URLs that point to raw files, Lavalink seems to just return them with a
-	// title of "Unknown title" - this is a slightly hacky solution to set the title
-	// to the filename of the raw file
-	if is_url && query_tracks == 1 {
-		let track_info = &mut queueable_tracks[queueable_tracks_len - 1];
-		if track_info.info.is_some() && track_info.info.as_ref().unwrap().title.eq(UNKNOWN_TITLE) {
-			track_info.info = match &track_info.info {
-				Some(old_info) => {
-					let mut new_info = old_info.clone();
-					new_info.title = Url::parse(old_info.uri.as_str())
-						.expect(
-							"unable to parse track info URI when it should have been guaranteed \
-							 to be valid",
-						)
-						.path_segments()
-						.expect("unable to parse URI as a proper path")
-						.last()
-						.expect("unable to find the last path segment of URI")
-						.to_owned();
-					Some(new_info)
-				}
-				None => None,
-			};
+	// Leave out anything from a source the guild has blocked
+	let blocked_sources = {
+		let conn = ctx.data().db_pool.get()?;
+		get_blocked_sources(&conn, guild.id.0 as i64)?
+	};
+	if !blocked_sources.is_empty() {
+		let found_count = queueable_tracks.len();
+		queueable_tracks.retain(|track| {
+			!track.info.as_ref().map_or(false, |info| {
+				is_blocked_source(info.uri.as_str(), &blocked_sources)
+			})
+		});
+		let blocked_count = found_count - queueable_tracks.len();
+		if blocked_count > 0 {
+			reply(
+				ctx,
+				if queueable_tracks.is_empty() {
+					"That's from a source that's blocked in this server.".to_owned()
+				} else {
+					format!(
+						"Left out {} track{} from sources that are blocked in this server.",
+						blocked_count,
+						if blocked_count == 1 { "" } else { "s" }
+					)
+				},
+			)
+			.await?;
+			if queueable_tracks.is_empty() {
+				return Ok(());
+			}
 		}
 	}
 
+	let queueable_tracks_len = queueable_tracks.len();
+
+	// Keep a few alternatives around in case the only track that was found doesn't
+	// play
+	if queueable_tracks_len == 1 {
+		search_alternatives.retain(|track| {
+			!track.info.as_ref().map_or(true, |info| {
+				is_blocked_source(info.uri.as_str(), &blocked_sources)
+			})
+		});
+		search_alternatives.truncate(MAX_SEARCH_FALLBACKS);
+		if !search_alternatives.is_empty() {
+			record_search_fallback(
+				ctx.data(),
+				guild.id,
+				SearchFallback {
+					track: queueable_tracks[0].track.clone(),
+					alternatives: search_alternatives.into(),
+					requester: ctx.author().id,
+					started_at: None,
+				},
+			);
+		}
+	}
+
+	// Note how much was already queued, so the user can be told where their track
+	// ended up
+	let previous_queue_len = lavalink
+		.nodes()
+		.await
+		.get(&guild.id.0)
+		.map_or(0, |node| node.queue.len());
+
+	let (skip_non_music, mute_segments) = {
+		let conn = ctx.data().db_pool.get()?;
+		let guild_settings = get_guild_settings(&conn, guild.id.0 as i64)?;
+		(guild_settings.skip_non_music, guild_settings.mute_segments)
+	};
+
+	// Big additions take a while to queue, so let the user know it's happening
+	let mut progress_message = if queueable_tracks_len >= PLAY_PROGRESS_THRESHOLD {
+		let reply_handle = reply(
+			ctx,
+			format!("Queueing {} tracks\u{2026}", queueable_tracks_len),
+		)
+		.await?;
+		if let Some(reply_handle) = reply_handle {
+			reply_handle.message().await.ok()
+		} else {
+			None
+		}
+	} else {
+		None
+	};
+	let mut last_progress_update = Instant::now();
+
 	// Queue the tracks up
 	let mut new_first_track_duration = None;
 	for (index, track) in queueable_tracks.iter().enumerate() {
 		let mut new_start_time = None;
 
 		// YouTube SponsorBlock integration
-		let track_segments_identifier_opt = track.info.as_ref().map(|i| &i.identifier);
-		let mut cache_track_with_none = true;
-		'sponsorblock: {
-			let track_segments_identifier;
-			if let Some(identifier) = track_segments_identifier_opt {
-				track_segments_identifier = identifier;
-			} else {
-				break 'sponsorblock;
-			}
+		let track_segments = if let Some(info) = &track.info {
+			get_skip_segments(ctx.data(), info)
+				.await
+				.and_then(|segments| segments_for_guild(segments, skip_non_music))
+		} else {
+			None
+		};
 
-			// If we already have the segments for this video cached, we don't need to fetch
-			// them again
+		// Apply the segments the same way whether they were just fetched or were
+		// already cached, so every track skips its start segment
+		if let (Some(segments), Some(info)) = (&track_segments, &track.info) {
+			// Start the track after the segment right at the beginning, if there is one -
+			// unless it's being muted, in which case it's muted once the track starts
+			if let Some(start_segment) = segments
+				.first()
+				.filter(|s| s.is_at_start && !(mute_segments && s.is_mute))
 			{
-				let mut segment_data_handle = ctx.data().segment_data.lock().unwrap();
-				if let Some(Some(segments)) = segment_data_handle
-					.cached_segments
-					.get(track_segments_identifier)
-				{
-					// Load the special start and end times if necessary
-					if !segments.is_empty() && segments[0].is_at_start {
-						new_start_time = Some(Duration::from_secs_f32(segments[0].end));
-					}
-					// Break
-					cache_track_with_none = false;
-					break 'sponsorblock;
-				}
+				new_start_time = Some(Duration::from_secs_f32(start_segment.end));
 			}
 
-			if let Some(info) = &track.info {
-				const SEGMENT_COMBINE_THRESHOLD: f32 = 0.35; // The maximum distance between two segments to combine
-				const SEGMENT_LENGTH_THRESHOLD: f32 = 0.5; // The minimum length a segment should be
-				const DURATION_DISCARD_THRESHOLD: f32 = 1.25; // The maximum difference from the submission video length to accept
-
-				// No point if it's a stream
-				if !info.is_seekable {
-					break 'sponsorblock;
-				}
-
-				let parsed_uri = Url::parse(&info.uri).expect(
-					"unable to parse track info URI when it should have been guaranteed to be \
-					 valid",
+			// Store the new duration, without the skipped segments, for the first track
+			if index == 0 {
+				let new_track_duration = info.length.saturating_sub(
+					(segments.iter().map(|t| t.end - t.start).sum::<f32>() * MILLIS_PER_SECOND_F32)
+						as u64,
 				);
-
-				if let Some(video_id) = get_youtube_video_id(&parsed_uri) {
-					if let Ok(segments) = ctx
-						.data()
-						.sponsor_block
-						.fetch_segments(&video_id, ACCEPTED_CATEGORIES)
-						.await
-					{
-						// Calculate the track duration
-						let track_duration = info.length as f32 / MILLIS_PER_SECOND_F32;
-						// Get the pertinent information and filter out segments that may be
-						// incorrect (submitted before some edit to the video length that
-						// invalidates the timecodes)
-						let mut skip_timecodes = segments
-							.iter()
-							.filter(|s| {
-								// Because some segments were added before video durations started
-								// being recorded
-								if let Some(video_duration_upon_submission) =
-									s.video_duration_on_submission
-								{
-									(video_duration_upon_submission - track_duration).abs()
-										<= DURATION_DISCARD_THRESHOLD
-								} else {
-									true
-								}
-							})
-							.filter_map(|s| match &s.segment {
-								ActionableSegment::Sponsor(t)
-								| ActionableSegment::UnpaidSelfPromotion(t)
-								| ActionableSegment::InteractionReminder(t)
-								| ActionableSegment::IntermissionIntroAnimation(t)
-								| ActionableSegment::EndcardsCredits(t)
-								| ActionableSegment::NonMusic(t) => Some(SkipSegment {
-									start: t.start,
-									end: t.end,
-									is_at_start: false,
-									is_at_end: false,
-								}),
-								ActionableSegment::Highlight(_)
-								| ActionableSegment::PreviewRecap(_)
-								| ActionableSegment::FillerTangent(_) => None,
-							})
-							.collect::<Vec<_>>();
-						// Ensure the segments are ordered by their time in the content
-						skip_timecodes
-							.sort_unstable_by_key(|t| (t.start * MILLIS_PER_SECOND_F32) as u32);
-						// Combine segments that are close together
-						let mut skip_timecodes_len = skip_timecodes.len();
-						if skip_timecodes_len > 1 {
-							for i in (1..skip_timecodes_len).rev() {
-								if skip_timecodes[i].start - skip_timecodes[i - 1].end
-									> SEGMENT_COMBINE_THRESHOLD
-								{
-									continue;
-								}
-								skip_timecodes[i - 1].end = skip_timecodes[i].end;
-								skip_timecodes.remove(i);
-							}
-						}
-						// Remove segments that are too short to be worth skipping with the Lavalink
-						// seek delay
-						skip_timecodes = skip_timecodes
-							.drain(..)
-							.filter(|t| t.end - t.start >= SEGMENT_LENGTH_THRESHOLD)
-							.collect::<Vec<_>>();
-
-						// Final processing
-						skip_timecodes_len = skip_timecodes.len();
-						if skip_timecodes_len > 0 {
-							// Store the new duration, without the skipped segments, for the first
-							// track
-							if index == 0 {
-								let new_track_duration = info.length
-									- (skip_timecodes.iter().map(|t| t.end - t.start).sum::<f32>()
-										* MILLIS_PER_SECOND_F32) as u64;
-								// The track durations are displayed with 1s precision, so there's
-								// no point in setting the new track duration if it's a difference
-								// of <1s
-								if new_track_duration <= info.length - MILLIS_PER_SECOND {
-									new_first_track_duration = Some(new_track_duration);
-								}
-							}
-
-							// Set the start time for the track if there's a segment right at the
-							// beginning
-							if skip_timecodes[0].start <= SEGMENT_COMBINE_THRESHOLD {
-								skip_timecodes[0].is_at_start = true;
-								new_start_time =
-									Some(Duration::from_secs_f32(skip_timecodes[0].end));
-							}
-							// Set the end segment's is_at_end value if it's at the very end
-							if (track_duration - skip_timecodes[skip_timecodes_len - 1].end).abs()
-								<= SEGMENT_COMBINE_THRESHOLD
-							{
-								skip_timecodes[skip_timecodes_len - 1].is_at_end = true;
-							}
-						}
-
-						// Cache the segments if there's segments to cache
-						if skip_timecodes.is_empty() {
-							break 'sponsorblock;
-						}
-						{
-							let mut segment_data_handle = ctx.data().segment_data.lock().unwrap();
-							segment_data_handle
-								.cached_segments
-								.put(track_segments_identifier.clone(), Some(skip_timecodes));
-						}
-						cache_track_with_none = false;
-					}
+				// The track durations are displayed with 1s precision, so there's no point in
+				// setting the new track duration if it's a difference of <1s
+				if new_track_duration <= info.length.saturating_sub(MILLIS_PER_SECOND) {
+					new_first_track_duration = Some(new_track_duration);
 				}
 			}
 		}
-		// If no segments were found, cache that fact so we don't have to check the next
-		// time the video is requested
-		if cache_track_with_none {
-			if let Some(track_segments_identifier) = track_segments_identifier_opt {
-				let mut segment_data_handle = ctx.data().segment_data.lock().unwrap();
-				segment_data_handle
-					.cached_segments
-					.put(track_segments_identifier.clone(), None);
-			}
-		}
 
 		// Queue
 		let mut queueable = lavalink.play(guild.id.0, track.clone());
@@ -453,17 +987,50 @@ pub async fn play(
 			queueable.start_time(start_time);
 		}
 		if let Err(e) = queueable.queue().await {
-			reply(ctx, "Failed to queue up query result.").await?;
-			eprintln!("Failed to queue up query result: {}", e);
+			// Turn the progress message into the failure, so it isn't left looking like
+			// the tracks are still being queued
+			if let Some(mut progress_message) = progress_message {
+				eprintln!("Failed to queue up query result: {}", e);
+				progress_message
+					.edit(ctx.discord(), |m| {
+						m.embed(|e| {
+							e.colour(MAIN_COLOUR).description(format!(
+								"{} Only {}/{} tracks were queued.",
+								LAVALINK_UNAVAILABLE_MESSAGE, index, queueable_tracks_len
+							))
+						})
+					})
+					.await
+					.with_context(|| "failed to edit the progress message")?;
+			} else {
+				report_lavalink_failure(ctx, "queue up query result", e).await?;
+			}
 			return Ok(());
 		};
-	}
 
-	// Update the queued count for the guild
-	{
-		let mut hash_map = ctx.data().queued_count.lock().unwrap();
-		let queued_count = hash_map.entry(guild.id).or_default();
-		*queued_count += queueable_tracks_len;
+		// Count each track as soon as it's queued, so that a failure partway through
+		// doesn't throw off the numbering
+		ctx.data().queue_numbering.record_added(guild.id, 1);
+
+		if let Some(progress_message) = &mut progress_message {
+			if last_progress_update.elapsed() >= PLAY_PROGRESS_INTERVAL {
+				last_progress_update = Instant::now();
+				// A missed update isn't worth stopping over, since the next one will catch up
+				progress_message
+					.edit(ctx.discord(), |m| {
+						m.embed(|e| {
+							e.colour(MAIN_COLOUR).description(format!(
+								"Queueing {} tracks\u{2026} ({}/{})",
+								queueable_tracks_len,
+								index + 1,
+								queueable_tracks_len
+							))
+						})
+					})
+					.await
+					.ok();
+			}
+		}
 	}
 
 	// Notify the user of the added tracks
@@ -472,7 +1039,12 @@ pub async fn play(
 		reply(
 			ctx,
 			format!(
-				"Added to queue: [{}]({}) [{}]",
+				"{}: [{}]({}) [{}]",
+				if previous_queue_len == 0 {
+					"Now playing".to_owned()
+				} else {
+					format!("Added to queue (position {})", previous_queue_len + 1)
+				},
 				chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
 				track_info.uri,
 				if track_info.is_stream {
@@ -508,162 +1080,58 @@ pub async fn play(
 				}
 			}
 		}
-		reply_embed(ctx, |e| {
-			e.title(format!("Added {} Tracks:", queueable_tracks_len))
-				.description(desc)
-		})
-		.await?;
-	}
 
-	Ok(())
-}
-fn get_youtube_video_id(uri: &Url) -> Option<String> {
-	if let Some(host) = uri.host_str() {
-		if host.ends_with("youtube.com") {
-			if let Some(query) = uri.query() {
-				let query_parameters = query.split('&');
-				for parameter in query_parameters {
-					if let Some(stripped) = parameter.strip_prefix("v=") {
-						return Some(stripped.to_owned());
-					}
-				}
-				None
-			} else {
-				None
-			}
-		} else if host.ends_with("youtu.be") {
-			Some(
-				uri.path_segments()
-					.expect("unable to parse URI as a proper path")
-					.last()
-					.expect("unable to find the last path segment of URI")
-					.to_owned(),
-			)
-		} else {
-			None
+		// Let the user know where the tracks came from and how long they'll take
+		let total_duration = sum_track_durations(&queueable_tracks);
+		let stream_count = queueable_tracks
+			.iter()
+			.filter(|track| track.info.as_ref().map_or(false, |info| info.is_stream))
+			.count();
+		let mut footer = format!(
+			"From {} \u{2022} {} total",
+			describe_track_source(&queueable_tracks, from_playlist),
+			display_timecode(total_duration)
+		);
+		if stream_count > 0 {
+			footer.push_str(
+				format!(
+					", plus {} stream{}",
+					stream_count,
+					if stream_count == 1 { "" } else { "s" }
+				)
+				.as_str(),
+			);
 		}
-	} else {
-		None
-	}
-}
-
-/// Skip the current track.
-#[command(
-	prefix_command,
-	slash_command,
-	category = "Playback",
-	aliases("next", "stop", "n", "s")
-)]
-pub async fn skip(ctx: PoiseContext<'_>) -> Result<(), Error> {
-	let guild_id = if let Some(guild_id) = ctx.guild_id() {
-		guild_id
-	} else {
-		reply(ctx, "You must use this command from within a server.").await?;
-		return Ok(());
-	};
-
-	let lavalink = &ctx.data().lavalink;
 
-	if let Some(track) = lavalink.skip(guild_id.0).await {
-		let track_info = track.track.info.as_ref().unwrap();
-		// If the queue is now empty, the player needs to be stopped
-		if lavalink
-			.nodes()
-			.await
-			.get(&guild_id.0)
-			.unwrap()
-			.queue
-			.is_empty()
-		{
-			lavalink
-				.stop(guild_id.0)
+		// The progress message becomes the summary, if there was one
+		let title = format!("Added {} Tracks:", queueable_tracks_len);
+		if let Some(mut progress_message) = progress_message {
+			progress_message
+				.edit(ctx.discord(), |m| {
+					m.embed(|e| {
+						e.colour(MAIN_COLOUR)
+							.title(title)
+							.description(desc)
+							.footer(|f| f.text(footer))
+					})
+				})
 				.await
-				.with_context(|| "failed to stop playback of the current track".to_owned())?;
+				.with_context(|| "failed to edit the progress message")?;
+		} else {
+			reply_embed(ctx, |e| {
+				e.title(title).description(desc).footer(|f| f.text(footer))
+			})
+			.await?;
 		}
-		reply(
-			ctx,
-			format!(
-				"Skipped: [{}]({})",
-				chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
-				track_info.uri
-			),
-		)
-		.await?;
-	} else {
-		reply(ctx, "Nothing to skip.").await?;
 	}
 
 	Ok(())
 }
-
-/// Pause the current track.
-///
-/// The opposite of `resume`.
-#[command(prefix_command, slash_command, category = "Playback")]
-pub async fn pause(ctx: PoiseContext<'_>) -> Result<(), Error> {
-	let guild_id = if let Some(guild_id) = ctx.guild_id() {
-		guild_id
-	} else {
-		reply(ctx, "You must use this command from within a server.").await?;
-		return Ok(());
-	};
-
-	let lavalink = &ctx.data().lavalink;
-
-	if let Err(e) = lavalink.pause(guild_id.0).await {
-		reply(ctx, "Failed to pause playback.").await?;
-		eprintln!("Failed to pause playback: {}", e);
-		return Ok(());
-	};
-
-	reply(ctx, "Paused playback.").await?;
-
-	Ok(())
-}
-
-/// Resume the current track.
-///
-/// The opposite of `pause`.
-#[command(prefix_command, slash_command, category = "Playback")]
-pub async fn resume(ctx: PoiseContext<'_>) -> Result<(), Error> {
-	let guild_id = if let Some(guild_id) = ctx.guild_id() {
-		guild_id
-	} else {
-		reply(ctx, "You must use this command from within a server.").await?;
-		return Ok(());
-	};
-
-	let lavalink = &ctx.data().lavalink;
-
-	if let Err(e) = lavalink.resume(guild_id.0).await {
-		reply(ctx, "Failed to resume playback.").await?;
-		eprintln!("Failed to resume playback: {}", e);
-		return Ok(());
-	};
-
-	reply(ctx, "Resumed playback.").await?;
-
-	Ok(())
 }
 
-/// Seek to a specific time in the current track.
-///
-/// You can specify the time to skip to as a timecode (`2:35`) or as individual
+/// Parses a time to seek to, which can be a timecode (`2:35`) or individual
 /// time values (`2m35s`).
-///
-/// If the time specified is past the end of the track, the track ends.
-#[command(
-	prefix_command,
-	slash_command,
-	category = "Playback",
-	aliases("scrub", "jump")
-)]
-pub async fn seek(
-	ctx: PoiseContext<'_>,
-	#[rest]
-	#[description = "What time to skip to."]
-	time: String,
-) -> Result<(), Error> {
+fn parse_timecode(time: &str) -> Option<Duration> {
 	// Constants
 	const COLON: char = ':';
 	const DECIMAL: char = '.';
@@ -671,9 +1139,8 @@ pub async fn seek(
 	// Parse the time - this is a little hacky and gross, but it allows for support
 	// of timecodes like `2:35`. This is more ergonomic for users than something
 	// like `2m35s`, and this way both formats are supported.
-	let mut invalid_value = false;
 	let mut time_prepared = String::with_capacity(time.len());
-	'prepare_time: for timecode in time.split_whitespace() {
+	for timecode in time.split_whitespace() {
 		// First iteration to find indices and make sure the timecode is valid
 		let mut colon_index_first = None;
 		let mut colon_index_second = None;
@@ -686,21 +1153,18 @@ pub async fn seek(
 					colon_index_second = Some(i);
 				} else {
 					// Maximum of two colons in a timecode
-					invalid_value = true;
-					break 'prepare_time;
+					return None;
 				}
 				if decimal_index.is_some() {
 					// Colons don't come after decimals
-					invalid_value = true;
-					break 'prepare_time;
+					return None;
 				}
 			} else if c == DECIMAL {
 				if decimal_index.is_none() {
 					decimal_index = Some(i);
 				} else {
 					// Only one decimal value
-					invalid_value = true;
-					break 'prepare_time;
+					return None;
 				}
 			}
 		}
@@ -733,85 +1197,1624 @@ pub async fn seek(
 		time_prepared.push_str(new_word.as_str());
 		time_prepared.push(' ');
 	}
-	if invalid_value {
-		reply(ctx, "Invalid value for time.").await?;
-		return Ok(());
+
+	parse_duration(time_prepared.as_str()).ok()
+}
+
+/// Gets the encoded form of the track that's currently playing in a guild,
+/// which identifies it.
+async fn current_track(lavalink: &LavalinkClient, guild_id: SerenityGuildId) -> Option<String> {
+	lavalink.nodes().await.get(&guild_id.0).and_then(|node| {
+		node.now_playing
+			.as_ref()
+			.map(|now_playing| now_playing.track.track.clone())
+	})
+}
+
+/// Gets the SponsorBlock segments to skip for a track, fetching them only if
+/// they aren't cached already.
+///
+/// Whatever is fetched is cached, including the fact that a track has no
+/// segments, so the next time the track is played doesn't have to check again.
+async fn get_skip_segments(data: &Data, info: &Info) -> Option<Vec<SkipSegment>> {
+	let cached_segments = data
+		.segment_data
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.cached_segments
+		.get(&info.identifier)
+		.cloned();
+	if let Some(track_segments) = cached_segments {
+		return track_segments;
 	}
 
-	let time_dur = if let Ok(duration) = parse_duration(time_prepared.as_str()) {
-		duration
+	let track_segments = fetch_skip_segments(&data.sponsor_block, info).await;
+	data.segment_data
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.cached_segments
+		.put(info.identifier.clone(), track_segments.clone());
+
+	track_segments
+}
+
+/// For URLs that point to raw files, Lavalink seems to just return them with a
+/// title of "Unknown title" - this is a slightly hacky solution to set the
+/// title to the filename of the raw file.
+fn fix_raw_file_title(track: &mut Track) {
+	if let Some(info) = track.info.as_mut() {
+		if info.title.eq(UNKNOWN_TITLE) {
+			info.title = Url::parse(info.uri.as_str())
+				.expect(
+					"unable to parse track info URI when it should have been guaranteed to be \
+					 valid",
+				)
+				.path_segments()
+				.expect("unable to parse URI as a proper path")
+				.last()
+				.expect("unable to find the last path segment of URI")
+				.to_owned();
+		}
+	}
+}
+
+/// Estimates the position (in milliseconds) of the track that's playing in the
+/// guild.
+///
+/// The position is only updated every few seconds, so this accounts for the
+/// time that's passed since then - unless playback is paused, in which case it
+/// hasn't moved.
+fn estimate_position(data: &Data, guild_id: SerenityGuildId, track_info: &Info) -> u64 {
+	let mut position = track_info.position;
+	if !data
+		.paused_guilds
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.contains(&guild_id)
+	{
+		let last_player_update = data
+			.last_player_updates
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&guild_id)
+			.copied();
+		if let Some(last_player_update) = last_player_update {
+			position += last_player_update.elapsed().as_millis() as u64;
+		}
+	}
+	if !track_info.is_stream {
+		position = position.min(track_info.length);
+	}
+	position
+}
+
+/// Adds a timestamp to a YouTube link so that it opens at the given position.
+///
+/// Links to anywhere else are left as they are.
+fn link_at_position(uri: &str, position: u64) -> String {
+	if let Some(mut parsed_uri) = Url::parse(uri)
+		.ok()
+		.filter(|parsed_uri| get_youtube_video_id(parsed_uri).is_some())
+	{
+		parsed_uri
+			.query_pairs_mut()
+			.append_pair("t", format!("{}s", position / MILLIS_PER_SECOND).as_str());
+		parsed_uri.into()
 	} else {
-		reply(ctx, "Invalid value for time.").await?;
-		return Ok(());
-	};
+		uri.to_owned()
+	}
+}
 
-	// Seek to the parsed time
-	let guild_id = if let Some(guild_id) = ctx.guild_id() {
-		guild_id
+panic_safe! {
+/// Queue up a track after a delay, eg. `playin 5m <query>`.
+///
+/// The track is found right away, but it isn't added to the queue until the
+/// time is up. Tracks can be scheduled up to an hour ahead.
+///
+/// Use `cancelscheduled` to cancel all of the scheduled tracks. They're also
+/// cancelled if Radium leaves the voice channel.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "playin",
+	aliases("schedule")
+)]
+pub async fn play_in(
+	ctx: PoiseContext<'_>,
+	#[description = "How long to wait before queueing it, eg. `5m`."] delay: String,
+	#[rest]
+	#[description = "What to play."]
+	query: String,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
 	} else {
 		reply(ctx, "You must use this command from within a server.").await?;
 		return Ok(());
 	};
 
-	let lavalink = &ctx.data().lavalink;
-
-	if let Err(e) = lavalink.seek(guild_id.0, time_dur).await {
-		reply(ctx, "Failed to seek to the specified time.").await?;
-		eprintln!("Failed to seek to the specified time: {}", e);
+	let delay = if let Ok(delay) = parse_duration(delay.as_str()) {
+		delay
+	} else {
+		reply(ctx, "Invalid value for the delay.").await?;
 		return Ok(());
 	};
+	if delay > MAX_SCHEDULE_DELAY {
+		reply(ctx, "Tracks can only be scheduled up to an hour ahead.").await?;
+		return Ok(());
+	}
 
-	reply(ctx, "Scrubbed to the specified time.").await?;
+	let query = query.trim();
+	let is_url = Url::parse(query).is_ok();
+	if !is_url && violates_profanity_filter(ctx.data(), guild.id, query)? {
+		reply(ctx, FILTERED_MESSAGE).await?;
+		return Ok(());
+	}
 
-	Ok(())
-}
+	if !ensure_voice_connection(ctx, &guild).await? {
+		return Ok(());
+	}
 
-/// Clear the playback queue.
-///
-/// In addition to clearing the queue, this also resets the queue position for
-/// new tracks. This is the only way this happens other than when the bot goes
-/// offline.
-#[command(prefix_command, slash_command, category = "Playback", aliases("c"))]
-pub async fn clear(ctx: PoiseContext<'_>) -> Result<(), Error> {
-	let guild_id = if let Some(guild_id) = ctx.guild_id() {
-		guild_id
+	// Find the track now, so that any problems come up while the user is around
+	let lavalink = &ctx.data().lavalink;
+	let query_information = lavalink
+		.get_tracks(if is_url {
+			query.to_owned()
+		} else {
+			build_search_query(query, SearchSource::YouTube)
+		})
+		.await?;
+	let track = if let Some(track) = query_information.tracks.into_iter().next() {
+		track
 	} else {
-		reply(ctx, "You must use this command from within a server.").await?;
+		reply(ctx, "Could not find anything for the search query.").await?;
 		return Ok(());
 	};
+	let track_info = track.info.as_ref().unwrap();
 
-	let lavalink = &ctx.data().lavalink;
+	let blocked_sources = {
+		let conn = ctx.data().db_pool.get()?;
+		get_blocked_sources(&conn, guild.id.0 as i64)?
+	};
+	if is_blocked_source(track_info.uri.as_str(), &blocked_sources) {
+		reply(ctx, "That's from a source that's blocked in this server.").await?;
+		return Ok(());
+	}
 
-	while lavalink.skip(guild_id.0).await.is_some() {}
-	lavalink
-		.stop(guild_id.0)
-		.await
-		.with_context(|| "failed to stop playback of the current track".to_owned())?;
-	reply(ctx, "The queue is now empty.").await?;
+	let reply_message = format!(
+		"Scheduled to add to the queue in {}: [{}]({})",
+		display_timecode(delay.as_millis() as u64),
+		chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+		track_info.uri
+	);
 
-	{
-		let mut hash_map = ctx.data().queued_count.lock().unwrap();
-		let queued_count = hash_map.entry(guild_id).or_default();
-		*queued_count = 0;
-	}
+	// Queue it once the time is up
+	let data = Arc::clone(ctx.data());
+	let guild_id = guild.id;
+	let requester = ctx.author().id;
+	let task = spawn(async move {
+		sleep(delay).await;
+
+		// It's too late to cancel the track now, so it doesn't need to be kept track
+		// of anymore
+		forget_due_scheduled_plays(&data, guild_id);
+
+		// The task is cancelled if Radium leaves, but check anyway in case it was
+		// disconnected some other way
+		if data.songbird.get(guild_id).is_none() {
+			return;
+		}
+		let mut queueable = data.lavalink.play(guild_id.0, track);
+		queueable.requester(requester.0);
+		if let Err(e) = queueable.queue().await {
+			eprintln!("Failed to queue up a scheduled track: {}", e);
+			return;
+		}
+		data.queue_numbering.record_added(guild_id, 1);
+	});
+	ctx.data()
+		.scheduled_plays
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.entry(guild_id)
+		.or_default()
+		.push(ScheduledPlay {
+			play_at: Instant::now() + delay,
+			task,
+		});
+	// The task may have finished before it could be added, if the delay was short
+	forget_due_scheduled_plays(ctx.data(), guild_id);
+
+	reply(ctx, reply_message).await?;
 
 	Ok(())
 }
+}
 
-/// Show what's currently playing, and how far along in the track Radium is.
+/// Forgets the guild's scheduled tracks that are already due, since they can't
+/// be cancelled anymore.
+fn forget_due_scheduled_plays(data: &Data, guild_id: SerenityGuildId) {
+	let mut scheduled_plays = data
+		.scheduled_plays
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner);
+	if let Some(guild_scheduled_plays) = scheduled_plays.get_mut(&guild_id) {
+		let now = Instant::now();
+		guild_scheduled_plays.retain(|scheduled_play| scheduled_play.play_at > now);
+		if guild_scheduled_plays.is_empty() {
+			scheduled_plays.remove(&guild_id);
+		}
+	}
+}
+
+panic_safe! {
+/// Cancel all of the tracks scheduled with `playin`.
 ///
-/// If the track has a defined end point, a progress bar will be displayed.
-/// Otherwise, if the track is a live stream, only the time it's been playing
-/// will be displayed.
+/// If the server has a DJ role set, only members with it can do this.
 #[command(
 	prefix_command,
 	slash_command,
 	category = "Playback",
-	rename = "nowplaying",
-	aliases("np", "position", "current", "rn")
+	rename = "cancelscheduled"
 )]
-pub async fn now_playing(ctx: PoiseContext<'_>) -> Result<(), Error> {
-	fn create_progress_display(length: Option<u64>, position: u64) -> String {
+pub async fn cancel_scheduled(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	// Tracks that have already been queued don't count
+	let now = Instant::now();
+	let cancelled_count = ctx
+		.data()
+		.scheduled_plays
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.remove(&guild.id)
+		.unwrap_or_default()
+		.into_iter()
+		.filter(|scheduled_play| {
+			scheduled_play.task.abort();
+			scheduled_play.play_at > now
+		})
+		.count();
+
+	reply(
+		ctx,
+		match cancelled_count {
+			0 => "Nothing is scheduled.".to_owned(),
+			1 => "Cancelled 1 scheduled track.".to_owned(),
+			_ => format!("Cancelled {} scheduled tracks.", cancelled_count),
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Skip the current track.
+///
+/// If you skip twice in quick succession, the second skip is ignored in case it
+/// was an accident.
+///
+/// If the server has a DJ role set, only members with it can skip. If the
+/// server has set `voteskip` and enough people are listening, everyone else
+/// votes to skip instead, and the track is skipped once a majority has voted.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	aliases("next", "stop", "n", "s")
+)]
+pub async fn skip(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let guild_id = guild.id;
+
+	let guild_settings = {
+		let conn = ctx.data().db_pool.get()?;
+		get_guild_settings(&conn, guild_id.0 as i64)?
+	};
+	if let Some(vote_skip_threshold) = guild_settings.vote_skip_threshold {
+		// DJs can always skip right away, so only everyone else has to vote
+		if !is_dj(ctx, &guild, guild_settings.dj_role_id).await? {
+			let listener_count =
+				if let Some(channel_id) = authour_channel_id(&guild, ctx.author().id) {
+					count_listeners(&guild, channel_id)
+				} else {
+					reply(ctx, "You must be listening to vote to skip.").await?;
+					return Ok(());
+				};
+			if listener_count > vote_skip_threshold.max(0) as usize {
+				return vote_to_skip(ctx, guild_id, listener_count).await;
+			}
+		}
+	}
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	// Ignore accidental double skips from the same user
+	let double_skip = {
+		let now = Instant::now();
+		let mut last_skips = ctx
+			.data()
+			.last_skips
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let double_skip = last_skips.get(&guild_id).map_or(false, |(user_id, time)| {
+			*user_id == ctx.author().id && now - *time < SKIP_DEBOUNCE_PERIOD
+		});
+		if !double_skip {
+			last_skips.insert(guild_id, (ctx.author().id, now));
+		}
+		double_skip
+	};
+	if double_skip {
+		reply(ctx, "Already skipped.").await?;
+		return Ok(());
+	}
+
+	skip_current_track(ctx, guild_id).await
+}
+}
+
+/// Records the authour's vote to skip the current track, and skips it once a
+/// majority of the listeners have voted.
+async fn vote_to_skip(
+	ctx: PoiseContext<'_>,
+	guild_id: SerenityGuildId,
+	listener_count: usize,
+) -> Result<(), Error> {
+	// There's nothing to vote on if nothing is playing
+	let nothing_playing = ctx
+		.data()
+		.lavalink
+		.nodes()
+		.await
+		.get(&guild_id.0)
+		.map_or(true, |node| node.queue.is_empty());
+	if nothing_playing {
+		reply(ctx, "Nothing to skip.").await?;
+		return Ok(());
+	}
+
+	let votes_needed = (listener_count + 1) / 2;
+	let vote_count = {
+		let mut skip_votes = ctx
+			.data()
+			.skip_votes
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let guild_votes = skip_votes.entry(guild_id).or_default();
+		guild_votes.insert(ctx.author().id);
+		let vote_count = guild_votes.len();
+		if vote_count >= votes_needed {
+			skip_votes.remove(&guild_id);
+		}
+		vote_count
+	};
+
+	if vote_count < votes_needed {
+		reply(
+			ctx,
+			format!("{}/{} votes to skip.", vote_count, votes_needed),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	skip_current_track(ctx, guild_id).await
+}
+
+/// Skips the current track in the guild and replies with what was skipped.
+async fn skip_current_track(ctx: PoiseContext<'_>, guild_id: SerenityGuildId) -> Result<(), Error> {
+	let lavalink = &ctx.data().lavalink;
+
+	if let Some(track) = lavalink.skip(guild_id.0).await {
+		let track_info = track.track.info.as_ref().unwrap();
+		// If the queue is now empty, the player needs to be stopped
+		if lavalink
+			.nodes()
+			.await
+			.get(&guild_id.0)
+			.unwrap()
+			.queue
+			.is_empty()
+		{
+			if let Err(e) = lavalink.stop(guild_id.0).await {
+				report_lavalink_failure(ctx, "stop playback of the current track", e).await?;
+				return Ok(());
+			}
+		}
+		reply(
+			ctx,
+			format!(
+				"Skipped: [{}]({})",
+				chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+				track_info.uri
+			),
+		)
+		.await?;
+	} else {
+		reply(ctx, "Nothing to skip.").await?;
+	}
+
+	Ok(())
+}
+
+panic_safe! {
+/// Remove a track from the queue.
+///
+/// `position` is the track's number in `queue`. Removing the current track is
+/// the same as skipping it.
+///
+/// If the server has a DJ role set, only members with it can remove tracks.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	aliases("rm", "delete")
+)]
+pub async fn remove(
+	ctx: PoiseContext<'_>,
+	#[description = "The track's number in the queue."] position: usize,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let guild_id = guild.id;
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	let lavalink = &ctx.data().lavalink;
+
+	// Work out where the track is in the node's queue, going by the numbers the
+	// queue is shown with, and take it out under the same lock so the queue can't
+	// change in between
+	// The current track comes back as `None`, since it can't just be taken out of
+	// the queue while it's playing
+	let removed = if let Some(mut node) = lavalink
+		.nodes()
+		.await
+		.get_mut(&guild_id.0)
+		.filter(|node| !node.queue.is_empty())
+	{
+		let queue_len = node.queue.len();
+		let entry_offset = ctx.data().queue_numbering.entry_offset(guild_id, queue_len);
+		match position
+			.checked_sub(entry_offset + 1)
+			.filter(|index| *index < queue_len)
+		{
+			Some(0) => Ok(None),
+			Some(index) => Ok(Some(node.queue.remove(index).track)),
+			None => Err("There's no track at that position in the queue."),
+		}
+	} else {
+		Err("The queue is empty.")
+	};
+
+	match removed {
+		Ok(Some(track)) => {
+			ctx.data().queue_numbering.record_removed(guild_id);
+
+			let track_info = track.info.as_ref().unwrap();
+			reply(
+				ctx,
+				format!(
+					"Removed: [{}]({})",
+					chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+					track_info.uri
+				),
+			)
+			.await?;
+		}
+		Ok(None) => return skip_current_track(ctx, guild_id).await,
+		Err(message) => {
+			reply(ctx, message).await?;
+		}
+	}
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Move a track to a different position in the queue.
+///
+/// `from` and `to` are track numbers in `queue`. The current track can't be
+/// moved, and nothing can be moved ahead of it.
+///
+/// If the server has a DJ role set, only members with it can move tracks.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "move",
+	aliases("mv", "bump")
+)]
+pub async fn move_track(
+	ctx: PoiseContext<'_>,
+	#[description = "The track's number in the queue."] from: usize,
+	#[description = "The number it should have instead."] to: usize,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let guild_id = guild.id;
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	let lavalink = &ctx.data().lavalink;
+
+	// Moving a track around doesn't change how many tracks have been added, so the
+	// numbering stays consistent without having to record anything
+	let moved = if let Some(mut node) = lavalink.nodes().await.get_mut(&guild_id.0) {
+		let queue_len = node.queue.len();
+		let entry_offset = ctx.data().queue_numbering.entry_offset(guild_id, queue_len);
+		let to_index = |position: usize| {
+			position
+				.checked_sub(entry_offset + 1)
+				.filter(|index| *index < queue_len)
+		};
+		match (to_index(from), to_index(to)) {
+			(Some(0), _) | (_, Some(0)) => {
+				Err("The current track can't be moved, and nothing can be moved ahead of it.")
+			}
+			(Some(from_index), Some(to_index)) => {
+				let queued_track = node.queue.remove(from_index);
+				node.queue.insert(to_index, queued_track);
+				Ok(node.queue[to_index].track.clone())
+			}
+			(None, _) | (_, None) => Err("There's no track at that position in the queue."),
+		}
+	} else {
+		Err("The queue is empty.")
+	};
+
+	match moved {
+		Ok(track) => {
+			let track_info = track.info.as_ref().unwrap();
+			reply(
+				ctx,
+				format!(
+					"Moved [{}]({}) to position {}.",
+					chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+					track_info.uri,
+					to
+				),
+			)
+			.await?;
+		}
+		Err(message) => {
+			reply(ctx, message).await?;
+		}
+	}
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Pause the current track.
+///
+/// The opposite of `resume`.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn pause(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	if let Err(e) = lavalink.pause(guild_id.0).await {
+		report_lavalink_failure(ctx, "pause playback", e).await?;
+		return Ok(());
+	};
+
+	ctx.data()
+		.paused_guilds
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.insert(guild_id);
+
+	reply(ctx, "Paused playback.").await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Resume the current track.
+///
+/// The opposite of `pause`.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn resume(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	if let Err(e) = lavalink.resume(guild_id.0).await {
+		report_lavalink_failure(ctx, "resume playback", e).await?;
+		return Ok(());
+	};
+
+	ctx.data()
+		.paused_guilds
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.remove(&guild_id);
+
+	reply(ctx, "Resumed playback.").await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Silence playback without pausing it, so live streams keep going.
+///
+/// The opposite of `unmute`, which brings back the volume from before.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	aliases("silence")
+)]
+pub async fn mute(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	set_muted(ctx, true).await
+}
+}
+
+panic_safe! {
+/// Bring playback back to the volume it was at before `mute`.
+///
+/// The opposite of `mute`.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn unmute(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	set_muted(ctx, false).await
+}
+}
+
+/// Mutes or unmutes the guild's playback, which is shared by `mute` and
+/// `unmute`.
+async fn set_muted(ctx: PoiseContext<'_>, muted: bool) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	if lavalink.nodes().await.get(&guild_id.0).is_none() {
+		reply(ctx, "Nothing is playing at the moment.").await?;
+		return Ok(());
+	}
+
+	let volume = {
+		let mut playback_modifiers = ctx
+			.data()
+			.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let modifiers = playback_modifiers.entry(guild_id).or_default();
+		if modifiers.muted == muted {
+			None
+		} else {
+			modifiers.muted = muted;
+			Some(if muted { 0 } else { modifiers.volume })
+		}
+	};
+	let volume = if let Some(volume) = volume {
+		volume
+	} else {
+		reply(
+			ctx,
+			if muted {
+				"Playback is already muted."
+			} else {
+				"Playback isn't muted."
+			},
+		)
+		.await?;
+		return Ok(());
+	};
+
+	if let Err(e) = lavalink.volume(guild_id.0, volume).await {
+		// Put the modifiers back the way they were, since nothing changed
+		if let Some(modifiers) = ctx
+			.data()
+			.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get_mut(&guild_id)
+		{
+			modifiers.muted = !muted;
+		}
+		report_lavalink_failure(
+			ctx,
+			if muted {
+				"mute playback"
+			} else {
+				"unmute playback"
+			},
+			e,
+		)
+		.await?;
+		return Ok(());
+	};
+
+	reply(
+		ctx,
+		if muted {
+			"\u{1f507} Muted playback. Use `unmute` to bring it back."
+		} else {
+			"Unmuted playback."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+
+panic_safe! {
+/// Set the playback volume, as a percentage up to 150.
+///
+/// The volume is remembered, so Radium starts at it the next time it joins.
+/// Use `volume` on its own to see what it's at.
+#[command(prefix_command, slash_command, category = "Playback", aliases("vol"))]
+pub async fn volume(
+	ctx: PoiseContext<'_>,
+	#[description = "The volume as a percentage, up to 150."] volume: Option<u8>,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let volume = if let Some(volume) = volume {
+		volume
+	} else {
+		// The player's volume is the most up-to-date, but there might not be a player
+		let current_volume = ctx
+			.data()
+			.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&guild.id)
+			.map(|modifiers| modifiers.volume);
+		let current_volume = if let Some(current_volume) = current_volume {
+			current_volume
+		} else {
+			let conn = ctx.data().db_pool.get()?;
+			get_guild_settings(&conn, guild.id.0 as i64)?
+				.volume
+				.map_or(DEFAULT_VOLUME, |volume| {
+					(volume.max(0) as u16).min(MAX_VOLUME)
+				})
+		};
+		reply(ctx, format!("The volume is at {}%.", current_volume)).await?;
+		return Ok(());
+	};
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	let clamped = u16::from(volume) > MAX_VOLUME;
+	let volume = u16::from(volume).min(MAX_VOLUME);
+
+	// Change the volume of the current player, if there is one
+	let lavalink = &ctx.data().lavalink;
+	if lavalink.nodes().await.get(&guild.id.0).is_some() {
+		if let Err(e) = lavalink.volume(guild.id.0, volume).await {
+			report_lavalink_failure(ctx, "change the volume", e).await?;
+			return Ok(());
+		}
+		// Changing the volume brings playback out of being muted
+		let mut playback_modifiers = ctx
+			.data()
+			.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let modifiers = playback_modifiers.entry(guild.id).or_default();
+		modifiers.volume = volume;
+		modifiers.muted = false;
+	}
+
+	// Remember it for the next time Radium joins
+	{
+		let conn = ctx.data().db_pool.get()?;
+		save_guild_volume(&conn, guild.id.0 as i64, Some(i32::from(volume)))
+			.with_context(|| "failed to save the volume to the database")?;
+	}
+
+	reply(
+		ctx,
+		if clamped {
+			format!(
+				"\u{1f50a} Set the volume to {0}%. That's as loud as it goes, since anything \
+				 louder distorts badly.",
+				volume
+			)
+		} else {
+			format!("\u{1f50a} Set the volume to {}%.", volume)
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Put an effect on the audio, or take it off with `off`.
+///
+/// The filters are `bassboost`, `nightcore` and `vaporwave`, and only one can
+/// be on at a time. It stays on until it's turned off, the queue is cleared,
+/// or Radium leaves.
+///
+/// This relies on the audio server (Lavalink) supporting filters.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn filter(
+	ctx: PoiseContext<'_>,
+	#[description = "The filter to use, or `off`."] name: String,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let name = name.trim();
+	let new_filter = if name.eq_ignore_ascii_case(FILTER_OFF_OPTION) {
+		None
+	} else if let Some(new_filter) = FilterKind::from_name(name) {
+		Some(new_filter)
+	} else {
+		reply(
+			ctx,
+			format!(
+				"The filter must be one of {}, or `{}`.",
+				FilterKind::ALL
+					.iter()
+					.map(|filter| format!("`{}`", filter.name()))
+					.collect::<Vec<_>>()
+					.join(", "),
+				FILTER_OFF_OPTION
+			),
+		)
+		.await?;
+		return Ok(());
+	};
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	// Apply it right away if something is already playing, otherwise it's applied
+	// when the next track starts
+	let lavalink = &ctx.data().lavalink;
+	if lavalink.nodes().await.contains_key(&guild.id.0) {
+		let normalize = {
+			let conn = ctx.data().db_pool.get()?;
+			get_guild_settings(&conn, guild.id.0 as i64)?.normalize
+		};
+		if let Err(e) = apply_filters(lavalink, guild.id.0, normalize, new_filter).await {
+			report_lavalink_failure(ctx, "apply the filter", e).await?;
+			return Ok(());
+		}
+	}
+
+	{
+		let mut active_filters = ctx
+			.data()
+			.active_filters
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		if let Some(new_filter) = new_filter {
+			active_filters.insert(guild.id, new_filter);
+		} else {
+			active_filters.remove(&guild.id);
+		}
+	}
+
+	reply(
+		ctx,
+		if let Some(new_filter) = new_filter {
+			format!("Turned on the `{}` filter.", new_filter.name())
+		} else {
+			"Turned off the filter.".to_owned()
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Seek to a specific time in the current track.
+///
+/// You can specify the time to skip to as a timecode (`2:35`), as individual
+/// time values (`2m35s`), or as a percentage of the way through the track
+/// (`50%`).
+///
+/// If the time specified is past the end of the track, the track ends.
+///
+/// If the track changes while the command is being handled, nothing happens, so
+/// you don't end up seeking in a track you didn't mean to. This can't tell
+/// apart two plays of the same track back-to-back, though.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	aliases("scrub", "jump")
+)]
+pub async fn seek(
+	ctx: PoiseContext<'_>,
+	#[rest]
+	#[description = "What time to skip to."]
+	time: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	// Note which track this is meant for, so that it can be checked again right
+	// before seeking. The length is taken at the same time, so that a percentage is
+	// always of the track being checked for.
+	let current = lavalink.nodes().await.get(&guild_id.0).and_then(|node| {
+		node.now_playing.as_ref().map(|now_playing| {
+			(
+				now_playing.track.track.clone(),
+				now_playing
+					.track
+					.info
+					.as_ref()
+					.filter(|info| !info.is_stream)
+					.map(|info| info.length),
+			)
+		})
+	});
+	let (target_track, track_length) = if let Some(current) = current {
+		current
+	} else {
+		reply(ctx, "Nothing is playing.").await?;
+		return Ok(());
+	};
+
+	let time = time.trim();
+	let time_dur = if let Some(percentage) = time.strip_suffix('%') {
+		let percentage = if let Ok(percentage) = percentage.trim().parse::<f64>() {
+			percentage.clamp(0.0, 100.0)
+		} else {
+			reply(ctx, "Invalid value for the percentage.").await?;
+			return Ok(());
+		};
+		if let Some(track_length) = track_length {
+			Duration::from_millis((track_length as f64 * percentage / 100.0) as u64)
+		} else {
+			reply(ctx, "Live streams can't be seeked by percentage.").await?;
+			return Ok(());
+		}
+	} else if let Some(duration) = parse_timecode(time) {
+		duration
+	} else {
+		reply(ctx, "Invalid value for time.").await?;
+		return Ok(());
+	};
+
+	// Seek to the parsed time, as long as the track hasn't changed in the meantime.
+	// Nothing is awaited between this check and the seek, so that the window for
+	// the track to change without being noticed is as small as it can be.
+	if current_track(lavalink, guild_id).await.as_ref() != Some(&target_track) {
+		reply(ctx, "The track changed, so nothing was done.").await?;
+		return Ok(());
+	}
+
+	if let Err(e) = lavalink.seek(guild_id.0, time_dur).await {
+		report_lavalink_failure(ctx, "seek to the specified time", e).await?;
+		return Ok(());
+	};
+
+	reply(ctx, "Scrubbed to the specified time.").await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Clear the playback queue.
+///
+/// In addition to clearing the queue, this also resets the queue position for
+/// new tracks. This is the only way this happens other than when the bot leaves
+/// the voice channel or goes offline.
+///
+/// The tracks that `previous` goes back through and the filter from `filter`
+/// are cleared too.
+///
+/// Clearing a queue of more than 20 tracks has to be confirmed. If the server
+/// has a DJ role set, only members with it can clear the queue.
+#[command(prefix_command, slash_command, category = "Playback", aliases("c"))]
+pub async fn clear(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let guild_id = guild.id;
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	let lavalink = &ctx.data().lavalink;
+
+	// Make sure large queues aren't cleared by accident
+	let queue_len = lavalink
+		.nodes()
+		.await
+		.get(&guild_id.0)
+		.map_or(0, |node| node.queue.len());
+	if queue_len > CLEAR_CONFIRMATION_THRESHOLD
+		&& !confirm(
+			ctx,
+			format!(
+				"Are you sure you want to clear all {} tracks in the queue?",
+				queue_len
+			),
+			"Clear",
+			CLEAR_CONFIRMATION_TIMEOUT,
+		)
+		.await?
+	{
+		reply(ctx, "The queue was left as it was.").await?;
+		return Ok(());
+	}
+
+	while lavalink.skip(guild_id.0).await.is_some() {}
+	if let Err(e) = lavalink.stop(guild_id.0).await {
+		report_lavalink_failure(ctx, "stop playback of the current track", e).await?;
+		return Ok(());
+	}
+	ctx.data().queue_numbering.forget(guild_id);
+	ctx.data()
+		.previous_tracks
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.remove(&guild_id);
+
+	// A cleared queue starts over without a filter
+	let had_filter = ctx
+		.data()
+		.active_filters
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.remove(&guild_id)
+		.is_some();
+	if had_filter {
+		let normalize = {
+			let conn = ctx.data().db_pool.get()?;
+			get_guild_settings(&conn, guild_id.0 as i64)?.normalize
+		};
+		if let Err(e) = apply_filters(lavalink, guild_id.0, normalize, None).await {
+			report_lavalink_failure(ctx, "remove the filter", e).await?;
+			return Ok(());
+		}
+	}
+
+	reply(ctx, "The queue is now empty.").await?;
+
+	Ok(())
+}
+}
+
+/// Puts the upcoming tracks in the queue (everything after the current track)
+/// into a new order, where `order` lists the indices of `upcoming` in the order
+/// they should be in.
+///
+/// `upcoming` is what the upcoming tracks were when the order was worked out.
+/// If the queue has changed since then, it's left alone and `false` is
+/// returned.
+fn reorder_upcoming(queue: &mut Vec<TrackQueue>, upcoming: &[Track], order: &[usize]) -> bool {
+	if queue.len() != upcoming.len() + 1
+		|| !queue[1..]
+			.iter()
+			.zip(upcoming)
+			.all(|(queued_track, track)| queued_track.track.track == track.track)
+	{
+		return false;
+	}
+
+	let mut previous_order = queue.split_off(1).into_iter().map(Some).collect::<Vec<_>>();
+	queue.extend(
+		order
+			.iter()
+			.filter_map(|index| previous_order[*index].take()),
+	);
+
+	true
+}
+
+panic_safe! {
+/// Shuffle the upcoming tracks in the queue.
+///
+/// The current track keeps playing, and everything after it is shuffled.
+///
+/// Use `shuffle preview` to see the new order first, with a button to apply
+/// it. If the server has a DJ role set, only members with it can shuffle the
+/// queue.
+#[command(prefix_command, slash_command, category = "Playback", aliases("mix"))]
+pub async fn shuffle(
+	ctx: PoiseContext<'_>,
+	#[flag]
+	#[description = "Show the shuffled order without applying it right away."]
+	preview: bool,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let guild_id = guild.id;
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	let lavalink = &ctx.data().lavalink;
+
+	// The order is worked out ahead of time so that a preview shows exactly what
+	// gets applied
+	let (upcoming, entry_offset) = if let Some(node) = lavalink.nodes().await.get(&guild_id.0) {
+		(
+			node.queue
+				.iter()
+				.skip(1)
+				.map(|queued_track| queued_track.track.clone())
+				.collect::<Vec<_>>(),
+			ctx.data()
+				.queue_numbering
+				.entry_offset(guild_id, node.queue.len()),
+		)
+	} else {
+		(Vec::new(), 0)
+	};
+	if upcoming.len() < 2 {
+		reply(ctx, "There aren't enough upcoming tracks to shuffle.").await?;
+		return Ok(());
+	}
+	let mut order = (0..upcoming.len()).collect::<Vec<_>>();
+	order.shuffle(&mut thread_rng());
+
+	if preview {
+		// Numbered the same way as the queue, where the current track comes first
+		let number_width = (entry_offset + upcoming.len() + 1).log10() as usize + 1;
+		let mut desc = "The upcoming tracks would be in this order:\n\n".to_owned();
+		for (i, index) in order.iter().enumerate() {
+			let track_info = upcoming[*index].info.as_ref().unwrap();
+			desc.push_str(format!("`{:01$}.` [", entry_offset + i + 2, number_width).as_str());
+			push_chopped_str(&mut desc, track_info.title.as_str(), MAX_LIST_ENTRY_LENGTH);
+			desc.push_str("](");
+			desc.push_str(track_info.uri.as_str());
+			desc.push(')');
+			if i < order.len() - 1 {
+				desc.push('\n');
+				if desc.len() > DESCRIPTION_LENGTH_CUTOFF {
+					desc.push_str("*\u{2026}the rest has been clipped*");
+					break;
+				}
+			}
+		}
+
+		if !confirm(ctx, desc, "Shuffle", SHUFFLE_PREVIEW_TIMEOUT).await? {
+			reply(ctx, "The queue was left as it was.").await?;
+			return Ok(());
+		}
+	}
+
+	let reordered = lavalink
+		.nodes()
+		.await
+		.get_mut(&guild_id.0)
+		.map_or(false, |mut node| {
+			reorder_upcoming(&mut node.queue, &upcoming, &order)
+		});
+	if reordered {
+		reply(ctx, "Shuffled the upcoming tracks.").await?;
+	} else {
+		reply(
+			ctx,
+			"The queue changed in the meantime, so it wasn't shuffled. Try again.",
+		)
+		.await?;
+	}
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Loop the current track or the whole queue.
+///
+/// Use `loop track` to keep playing the current track, `loop queue` to add
+/// tracks back to the end of the queue once they finish, and `loop off` to
+/// stop looping. Skipped tracks aren't looped.
+///
+/// Run it without a mode to see the current one. If the server has a DJ role
+/// set, only members with it can change the loop mode.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "loop",
+	aliases("repeat")
+)]
+pub async fn loop_mode(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `track`, `queue`, or `off`."] mode: Option<String>,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let mode = if let Some(mode) = mode {
+		mode
+	} else {
+		let current_mode = ctx
+			.data()
+			.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&guild.id)
+			.map_or(LoopMode::Off, |modifiers| modifiers.loop_mode);
+		reply(
+			ctx,
+			current_mode.display().map_or_else(
+				|| "Looping is off.".to_owned(),
+				|display| format!("Looping: {}", display),
+			),
+		)
+		.await?;
+		return Ok(());
+	};
+
+	let new_mode = match mode.trim().to_lowercase().as_str() {
+		"track" | "song" | "one" => LoopMode::Track,
+		"queue" | "all" => LoopMode::Queue,
+		"off" | "none" => LoopMode::Off,
+		_ => {
+			reply(ctx, "The mode must be either `track`, `queue`, or `off`.").await?;
+			return Ok(());
+		}
+	};
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	ctx.data()
+		.playback_modifiers
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.entry(guild.id)
+		.or_default()
+		.loop_mode = new_mode;
+
+	reply(
+		ctx,
+		match new_mode {
+			LoopMode::Off => "Stopped looping.",
+			LoopMode::Track => "Looping the current track.",
+			LoopMode::Queue => "Looping the queue.",
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Fetch the SponsorBlock segments for every track in a playlist ahead of time,
+/// without queueing anything.
+///
+/// Segments are remembered once they're fetched, so the tracks can skip their
+/// segments right away when they're played later. Only the first 100 tracks
+/// are fetched, and this shares a 30 second cooldown with `refreshsegments`.
+/// If the server has a DJ role set, only members with it can use this.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "warmsegments"
+)]
+pub async fn warm_segments(
+	ctx: PoiseContext<'_>,
+	#[description = "The URL of the playlist."] url: String,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let guild_id = guild.id;
+
+	if !can_control_playback(ctx, &guild).await? {
+		reply(ctx, NOT_A_DJ_MESSAGE).await?;
+		return Ok(());
+	}
+
+	if Url::parse(url.as_str()).is_err() {
+		reply(ctx, "That isn't a valid URL.").await?;
+		return Ok(());
+	}
+
+	// Go easy on the SponsorBlock API
+	let on_cooldown = start_segment_refresh_cooldown(ctx.data(), guild_id);
+	if on_cooldown {
+		reply(ctx, "Segments were only just fetched. Try again in a bit.").await?;
+		return Ok(());
+	}
+
+	let query_information = match ctx.data().lavalink.get_tracks(url).await {
+		Ok(query_information) => query_information,
+		Err(e) => {
+			report_lavalink_failure(ctx, "load a URL", e).await?;
+			return Ok(());
+		}
+	};
+	if query_information.tracks.is_empty() {
+		reply(ctx, "Could not find anything at that URL.").await?;
+		return Ok(());
+	}
+
+	let mut processed_count = 0;
+	let mut with_segments_count = 0;
+	for track in query_information.tracks.iter().take(MAX_WARM_TRACKS) {
+		if let Some(info) = &track.info {
+			processed_count += 1;
+			if get_skip_segments(ctx.data(), info).await.is_some() {
+				with_segments_count += 1;
+			}
+		}
+	}
+
+	reply(
+		ctx,
+		format!(
+			"Fetched the segments for {} track{}, {} of which {} segments to skip.",
+			processed_count,
+			if processed_count == 1 { "" } else { "s" },
+			with_segments_count,
+			if with_segments_count == 1 {
+				"has"
+			} else {
+				"have"
+			}
+		),
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Fetch the SponsorBlock segments for the current track again.
+///
+/// Segments are remembered once they're fetched, so this is useful if a video
+/// has had segments submitted since it was last played. This can only be used
+/// once every 30 seconds.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "refreshsegments"
+)]
+pub async fn refresh_segments(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	let track_info = lavalink.nodes().await.get(&guild_id.0).and_then(|node| {
+		node.now_playing
+			.as_ref()
+			.and_then(|now_playing| now_playing.track.info.clone())
+	});
+	let track_info = if let Some(track_info) = track_info {
+		track_info
+	} else {
+		reply(ctx, "Nothing is playing.").await?;
+		return Ok(());
+	};
+	let is_youtube = Url::parse(track_info.uri.as_str())
+		.ok()
+		.and_then(|parsed_uri| get_youtube_video_id(&parsed_uri))
+		.is_some();
+	if !is_youtube {
+		reply(ctx, "Segments are only available for YouTube videos.").await?;
+		return Ok(());
+	}
+
+	// Go easy on the SponsorBlock API
+	let on_cooldown = start_segment_refresh_cooldown(ctx.data(), guild_id);
+	if on_cooldown {
+		reply(
+			ctx,
+			"Segments were only just refreshed. Try again in a bit.",
+		)
+		.await?;
+		return Ok(());
+	}
+
+	let skip_segments = fetch_skip_segments(&ctx.data().sponsor_block, &track_info).await;
+	let skip_non_music = {
+		let conn = ctx.data().db_pool.get()?;
+		get_guild_settings(&conn, guild_id.0 as i64)?.skip_non_music
+	};
+	let guild_skip_segments = skip_segments
+		.clone()
+		.and_then(|segments| segments_for_guild(segments, skip_non_music));
+	let segment_count = guild_skip_segments.as_ref().map_or(0, Vec::len);
+
+	// Replace the cached segments, and the active ones if the track is still
+	// playing
+	{
+		let mut segment_data_handle = ctx
+			.data()
+			.segment_data
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		segment_data_handle
+			.cached_segments
+			.put(track_info.identifier.clone(), skip_segments);
+		let lavalink_guild_id = LavalinkGuildId(guild_id.0);
+		let still_playing = segment_data_handle
+			.active_segments
+			.get(&lavalink_guild_id)
+			.map_or(true, |guild_segments| {
+				guild_segments.track_identifier == track_info.identifier
+			});
+		if still_playing {
+			if let Some(segments) = guild_skip_segments {
+				segment_data_handle.active_segments.insert(
+					lavalink_guild_id,
+					GuildSegments {
+						track_identifier: track_info.identifier.clone(),
+						segments,
+					},
+				);
+			} else {
+				segment_data_handle
+					.active_segments
+					.remove(&lavalink_guild_id);
+			}
+		}
+	}
+
+	reply(
+		ctx,
+		match segment_count {
+			0 => "There aren't any segments to skip in this track.".to_owned(),
+			1 => "Found 1 segment to skip.".to_owned(),
+			_ => format!("Found {} segments to skip.", segment_count),
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+/// Starts the guild's cooldown for fetching segments on demand, unless it's
+/// already on cooldown, in which case this returns `true`.
+///
+/// Cooldowns that have run out are forgotten along the way, so only the guilds
+/// that are on cooldown are kept track of.
+fn start_segment_refresh_cooldown(data: &Data, guild_id: SerenityGuildId) -> bool {
+	let mut last_segment_refreshes = data
+		.last_segment_refreshes
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner);
+	last_segment_refreshes
+		.retain(|_, last_refresh| last_refresh.elapsed() < REFRESH_SEGMENTS_COOLDOWN);
+	if last_segment_refreshes.contains_key(&guild_id) {
+		true
+	} else {
+		last_segment_refreshes.insert(guild_id, Instant::now());
+		false
+	}
+}
+
+panic_safe! {
+/// Show what's currently playing, and how far along in the track Radium is.
+///
+/// Where the track is in the queue is shown as well. If playback is paused,
+/// that's shown too.
+///
+/// If the track has a defined end point, a progress bar will be displayed.
+/// Otherwise, if the track is a live stream, only the time it's been playing
+/// will be displayed.
+///
+/// Use `nowplaying --compact` for a single line instead, or `nowplaying --full`
+/// for the full details if the server prefers the single line.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "nowplaying",
+	aliases("np", "position", "current", "rn")
+)]
+pub async fn now_playing(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `--compact` or `--full`, to override the server's default."]
+	style: Option<String>,
+) -> Result<(), Error> {
+	fn create_progress_display(length: Option<u64>, position: u64) -> String {
 		const EMPTY_BLOCK: char = '\u{25b1}';
 		const FULL_BLOCK: char = '\u{25b0}';
 		const PROGRESS_BAR_SIZE: u64 = 10;
@@ -860,9 +2863,357 @@ pub async fn now_playing(ctx: PoiseContext<'_>) -> Result<(), Error> {
 			);
 			ret.push('\n');
 		}
-		ret
-	}
+		ret
+	}
+
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let compact_override = match style.as_deref().map(str::trim) {
+		None | Some("") => None,
+		Some(COMPACT_OPTION) => Some(true),
+		Some(FULL_OPTION) => Some(false),
+		Some(_) => {
+			reply(
+				ctx,
+				format!(
+					"The style must be either `{}` or `{}`.",
+					COMPACT_OPTION, FULL_OPTION
+				),
+			)
+			.await?;
+			return Ok(());
+		}
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	let mut something_playing = false;
+	if let Some(node) = lavalink.nodes().await.get(&guild_id.0) {
+		if let Some(now_playing) = &node.now_playing {
+			let track_info = now_playing.track.info.as_ref().unwrap();
+			let (skip_non_music, compact) = {
+				let conn = ctx.data().db_pool.get()?;
+				let guild_settings = get_guild_settings(&conn, guild_id.0 as i64)?;
+				(
+					guild_settings.skip_non_music,
+					compact_override.unwrap_or(guild_settings.compact_now_playing),
+				)
+			};
+			let track_segments = {
+				let mut segment_data_handle = ctx
+					.data()
+					.segment_data
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner);
+				segment_data_handle
+					.cached_segments
+					.get(&track_info.identifier)
+					.cloned()
+					.flatten()
+					.and_then(|segments| segments_for_guild(segments, skip_non_music))
+			};
+			let is_paused = ctx
+				.data()
+				.paused_guilds
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.contains(&guild_id);
+			let position = estimate_position(ctx.data(), guild_id, track_info);
+
+			// The compact style is just the basics, in a single line
+			if compact {
+				reply_plain(
+					ctx,
+					format!(
+						"{} {} \u{2014} {}",
+						if is_paused { "\u{23f8}" } else { "\u{25b6}" },
+						escape_str(
+							chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH).as_str()
+						),
+						if track_info.is_stream {
+							format!("{} {}", display_timecode(position), LIVE_INDICATOR)
+						} else {
+							format!(
+								"{}/{}",
+								display_timecode(position),
+								display_timecode(track_info.length)
+							)
+						}
+					),
+				)
+				.await?;
+				return Ok(());
+			}
+
+			let modifiers_display = {
+				let playback_modifiers = ctx
+					.data()
+					.playback_modifiers
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner);
+				playback_modifiers
+					.get(&guild_id)
+					.cloned()
+					.unwrap_or_default()
+					.display()
+			};
+			let active_filter = ctx
+				.data()
+				.active_filters
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.get(&guild_id)
+				.copied();
+			// The playing track is the first one in the queue, so it's numbered the same
+			// way
+			let queue_len = node.queue.len();
+			let queue_position_display = (queue_len > 0).then(|| {
+				let entry_offset = ctx.data().queue_numbering.entry_offset(guild_id, queue_len);
+				let remaining = queue_len - 1;
+				format!(
+					"Track {} of {} \u{2022} {}",
+					entry_offset + 1,
+					entry_offset + queue_len,
+					match remaining {
+						0 => "nothing else queued".to_owned(),
+						1 => "1 more track queued".to_owned(),
+						_ => format!("{} more tracks queued", remaining),
+					}
+				)
+			});
+			let requester_id = UserId(
+				now_playing
+					.requester
+					.expect("expected a requester associated with a playing track")
+					.0,
+			);
+			// The requester may have left the server since, in which case they're just
+			// mentioned
+			let requester = guild_id
+				.member(ctx.discord(), requester_id)
+				.await
+				.ok()
+				.map(|member| (member.display_name().into_owned(), member.user.face()));
+			reply_embed(ctx, |e| {
+				if let Some((requester_name, requester_avatar)) = &requester {
+					e.author(|a| {
+						a.name(format!("Requested by {}", requester_name))
+							.icon_url(requester_avatar)
+					});
+				}
+				e.title(if is_paused {
+					"Now Playing (\u{23f8} Paused)"
+				} else {
+					"Now Playing"
+				})
+				.field(
+					"Track:",
+					format!(
+						"[{}]({})",
+						chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+						if track_info.is_stream {
+							track_info.uri.clone()
+						} else {
+							link_at_position(track_info.uri.as_str(), position)
+						},
+					),
+					false,
+				);
+				// Not every source knows who made the track
+				let author = track_info.author.trim();
+				if !author.is_empty() && author != UNKNOWN_AUTHOR {
+					e.field("Artist/Uploader:", escape_str(author), false);
+				}
+				if requester.is_none() {
+					e.field("Requested By:", requester_id.mention(), false);
+				}
+				e.field(
+					"Progress:",
+					create_progress_display(
+						if track_info.is_stream {
+							None
+						} else {
+							Some(track_info.length)
+						},
+						position,
+					),
+					false,
+				);
+				if let Some(segments) = track_segments {
+					e.field(
+						"Skip Segments:",
+						display_segments(&segments, track_info.length),
+						false,
+					);
+				}
+				if let Some(modifiers_display) = modifiers_display {
+					e.field("Playback:", modifiers_display, false);
+				}
+				if let Some(active_filter) = active_filter {
+					e.field("Filter:", active_filter.name(), false);
+				}
+				if let Some(queue_position_display) = queue_position_display {
+					e.footer(|f| f.text(queue_position_display));
+				}
+				e
+			})
+			.await?;
+			something_playing = true;
+		}
+	}
+	if !something_playing {
+		// Let users know if Radium is still around, just idle
+		let connected_channel = if let Some(call) = ctx.data().songbird.get(guild_id) {
+			call.lock()
+				.await
+				.current_channel()
+				.map(|channel_id| ChannelId(channel_id.0))
+		} else {
+			None
+		};
+		if let Some(channel_id) = connected_channel {
+			reply(
+				ctx,
+				format!(
+					"Nothing is playing at the moment. Connected to {}, with an empty queue.",
+					channel_id.mention()
+				),
+			)
+			.await?;
+			return Ok(());
+		}
+		reply(ctx, "Nothing is playing at the moment.").await?;
+	}
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Show the lyrics for the current track.
+///
+/// The artist and title are worked out from the track's details, so tracks
+/// with unusual titles might not be found. Use `lyrics --clean` to leave out
+/// section labels like `[Chorus]`.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn lyrics(
+	ctx: PoiseContext<'_>,
+	#[description = "`--clean` to leave out labels like `[Chorus]`."] option: Option<String>,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let clean = match option.as_deref().map(str::trim) {
+		None | Some("") => false,
+		Some(CLEAN_OPTION) => true,
+		Some(_) => {
+			reply(ctx, format!("The only option is `{}`.", CLEAN_OPTION)).await?;
+			return Ok(());
+		}
+	};
+
+	let now_playing_info = ctx
+		.data()
+		.lavalink
+		.nodes()
+		.await
+		.get(&guild_id.0)
+		.and_then(|node| {
+			node.now_playing
+				.as_ref()
+				.and_then(|now_playing| now_playing.track.info.clone())
+		});
+	let track_info = if let Some(track_info) = now_playing_info {
+		track_info
+	} else {
+		reply(ctx, "Nothing is playing at the moment.").await?;
+		return Ok(());
+	};
+
+	let (artist, title) = guess_song(track_info.title.as_str(), track_info.author.as_str());
+	let lyrics = match fetch_lyrics(&ctx.data().http_client, artist.as_str(), title.as_str()).await
+	{
+		Ok(Some(lyrics)) => lyrics,
+		Ok(None) => {
+			reply(
+				ctx,
+				format!(
+					"Couldn't find any lyrics for {} by {}.",
+					escape_str(title.as_str()),
+					escape_str(artist.as_str())
+				),
+			)
+			.await?;
+			return Ok(());
+		}
+		Err(e) => {
+			reply(
+				ctx,
+				"The lyrics couldn't be fetched, please try again shortly.",
+			)
+			.await?;
+			eprintln!("Failed to fetch lyrics: {}", e);
+			return Ok(());
+		}
+	};
+	let lyrics = if clean {
+		strip_section_labels(lyrics.as_str())
+	} else {
+		lyrics
+	};
+
+	let pages = split_into_pages(
+		escape_str(lyrics.as_str()).as_str(),
+		DESCRIPTION_LENGTH_CUTOFF,
+	);
+	let footer = format!("{} \u{2014} {}", artist, title);
+	reply_paginated(
+		ctx,
+		chop_str(
+			format!("Lyrics: {}", track_info.title).as_str(),
+			MAX_LIST_ENTRY_LENGTH,
+		)
+		.as_str(),
+		pages.as_slice(),
+		Some(chop_str(footer.as_str(), MAX_LIST_ENTRY_LENGTH).as_str()),
+		None,
+		LYRICS_PAGINATION_TIMEOUT,
+	)
+	.await?;
+
+	Ok(())
+}
+}
 
+panic_safe! {
+/// Show the playback queue.
+///
+/// Long queues are split into pages, with a button to jump back to the page
+/// holding the next track. Use `queue export` to get the full queue as a text
+/// file instead.
+///
+/// Use `queue mine` or `queue @user` to only show the tracks requested by
+/// someone in particular.
+#[command(prefix_command, slash_command, category = "Playback", aliases("q"))]
+pub async fn queue(
+	ctx: PoiseContext<'_>,
+	#[flag]
+	#[description = "Send the full queue as a text file."]
+	export: bool,
+	#[flag]
+	#[description = "Only show the tracks you requested."]
+	mine: bool,
+	#[description = "Only show the tracks requested by this user."] user: Option<User>,
+) -> Result<(), Error> {
 	let guild_id = if let Some(guild_id) = ctx.guild_id() {
 		guild_id
 	} else {
@@ -870,76 +3221,212 @@ pub async fn now_playing(ctx: PoiseContext<'_>) -> Result<(), Error> {
 		return Ok(());
 	};
 
+	let filter_user_id = if mine {
+		Some(ctx.author().id)
+	} else {
+		user.map(|user| user.id)
+	};
+
 	let lavalink = &ctx.data().lavalink;
 
-	let mut something_playing = false;
+	// The pages are built up front, so the node isn't held while they're browsed
+	let mut queue_pages = None;
 	if let Some(node) = lavalink.nodes().await.get(&guild_id.0) {
-		if let Some(now_playing) = &node.now_playing {
-			let track_info = now_playing.track.info.as_ref().unwrap();
-			let track_segments = {
-				let mut segment_data_handle = ctx.data().segment_data.lock().unwrap();
-				segment_data_handle
-					.cached_segments
-					.get(&track_info.identifier)
-					.cloned()
-			};
-			reply_embed(ctx, |e| {
-				e.title("Now Playing")
-					.field(
-						"Track:",
+		let queue = &node.queue;
+		let queue_len = queue.len();
+
+		if queue_len > 0 {
+			let entry_offset = ctx.data().queue_numbering.entry_offset(guild_id, queue_len);
+			let number_width = (entry_offset + queue_len).log10() as usize + 1;
+
+			// Number the entries before filtering them, so they keep their true positions
+			let entries = queue
+				.iter()
+				.enumerate()
+				.map(|(i, queued_track)| (entry_offset + i + 1, queued_track))
+				.filter(|(_, queued_track)| {
+					filter_user_id.map_or(true, |user_id| {
+						queued_track.requester.map(|requester| requester.0) == Some(user_id.0)
+					})
+				})
+				.collect::<Vec<_>>();
+			let entries_len = entries.len();
+
+			let filter_summary = if let Some(user_id) = filter_user_id {
+				if entries_len == 0 {
+					reply(
+						ctx,
 						format!(
-							"[{}]({})",
-							chop_str(track_info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
-							track_info.uri,
+							"{} doesn't have any tracks in the queue.",
+							user_id.mention()
 						),
-						false,
 					)
-					.field(
-						"Requested By:",
-						UserId(
-							now_playing
-								.requester
-								.expect("expected a requester associated with a playing track")
-								.0,
+					.await?;
+					return Ok(());
+				}
+				Some(format!(
+					"Showing {} of {} tracks requested by {}.",
+					entries_len,
+					queue_len,
+					user_id.mention()
+				))
+			} else {
+				None
+			};
+
+			// Exports aren't subject to the embed length limits, so they're never clipped
+			if export {
+				let mut contents = String::new();
+				for (number, queued_track) in &entries {
+					let track_info = queued_track.track.info.as_ref().unwrap();
+					contents.push_str(
+						format!(
+							"{:01$}. {2} - {3}\n",
+							number, number_width, track_info.title, track_info.uri
 						)
-						.mention(),
-						false,
-					)
-					.field(
-						"Progress:",
-						create_progress_display(
-							if track_info.is_stream {
-								None
-							} else {
-								Some(track_info.length)
-							},
-							track_info.position,
-						),
-						false,
+						.as_str(),
 					);
-				if let Some(Some(segments)) = track_segments {
-					e.field(
-						"Skip Segments:",
-						display_segments(&segments, track_info.length),
-						false,
+				}
+				reply_file(
+					ctx,
+					if let Some(filter_summary) = filter_summary {
+						filter_summary
+					} else if queue_len == 1 {
+						format!("Queue ({} total track):", queue_len)
+					} else {
+						format!("Queue ({} total tracks):", queue_len)
+					},
+					QUEUE_EXPORT_FILENAME,
+					contents.into_bytes(),
+				)
+				.await?;
+				return Ok(());
+			}
+
+			let mut header = String::new();
+			// Show what's playing above the queue, since it's not numbered with the rest
+			if let Some(now_playing) = &node.now_playing {
+				let track_info = now_playing.track.info.as_ref().unwrap();
+				let position = estimate_position(ctx.data(), guild_id, track_info);
+				header.push_str("\u{25b6} Now: [");
+				push_chopped_str(
+					&mut header,
+					track_info.title.as_str(),
+					MAX_LIST_ENTRY_LENGTH,
+				);
+				header.push_str("](");
+				header.push_str(track_info.uri.as_str());
+				header.push_str(") ");
+				if track_info.is_stream {
+					header.push_str(LIVE_INDICATOR);
+				} else {
+					header.push_str(
+						format!(
+							"[{}/{}]",
+							display_timecode(position),
+							display_timecode(track_info.length)
+						)
+						.as_str(),
 					);
 				}
-				e
-			})
-			.await?;
-			something_playing = true;
+				header.push_str("\n\n");
+			}
+			if let Some(filter_summary) = filter_summary {
+				header.push_str(filter_summary.as_str());
+				header.push_str("\n\n");
+			}
+			let pages = entries
+				.chunks(QUEUE_PAGE_SIZE)
+				.map(|page_entries| {
+					let mut desc = header.clone();
+					for (i, (number, queued_track)) in page_entries.iter().enumerate() {
+						let track_info = queued_track.track.info.as_ref().unwrap();
+						desc.push_str(format!("`{:01$}.` [", number, number_width).as_str());
+						push_chopped_str(
+							&mut desc,
+							track_info.title.as_str(),
+							MAX_LIST_ENTRY_LENGTH,
+						);
+						desc.push_str("](");
+						desc.push_str(track_info.uri.as_str());
+						desc.push(')');
+						if i < page_entries.len() - 1 {
+							desc.push('\n');
+							if desc.len() > DESCRIPTION_LENGTH_CUTOFF {
+								desc.push_str("*\u{2026}the rest has been clipped*");
+								break;
+							}
+						}
+					}
+					desc
+				})
+				.collect::<Vec<_>>();
+			// The next track is the first one after what's currently playing
+			let next_track_page = entries
+				.iter()
+				.position(|(number, _)| *number > entry_offset + 1)
+				.unwrap_or_default()
+				/ QUEUE_PAGE_SIZE;
+			// Let the user know why the queue might never end
+			let loop_mode = ctx
+				.data()
+				.playback_modifiers
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.get(&guild_id)
+				.map_or(LoopMode::Off, |modifiers| modifiers.loop_mode);
+			let loop_note = match loop_mode {
+				LoopMode::Off => None,
+				LoopMode::Track => Some("Looping: the current track repeats."),
+				LoopMode::Queue => Some("Looping: the queue repeats once it finishes."),
+			};
+
+			let title = if queue_len == 1 {
+				format!("Queue ({} total track):", queue_len)
+			} else {
+				format!("Queue ({} total tracks):", queue_len)
+			};
+
+			queue_pages = Some((title, pages, loop_note, next_track_page));
 		}
 	}
-	if !something_playing {
-		reply(ctx, "Nothing is playing at the moment.").await?;
+
+	if let Some((title, pages, loop_note, next_track_page)) = queue_pages {
+		reply_paginated(
+			ctx,
+			title.as_str(),
+			pages.as_slice(),
+			loop_note,
+			Some(PageJump {
+				label: "Jump to Next Track",
+				page: next_track_page,
+			}),
+			QUEUE_PAGINATION_TIMEOUT,
+		)
+		.await?;
+	} else {
+		reply(ctx, "Nothing is in the queue.").await?;
 	}
 
 	Ok(())
 }
+}
 
-/// Show the playback queue.
-#[command(prefix_command, slash_command, category = "Playback", aliases("q"))]
-pub async fn queue(ctx: PoiseContext<'_>) -> Result<(), Error> {
+panic_safe! {
+/// Show the tracks that were recently played in this server.
+///
+/// The most recent track is first. Use `history <count>` to show more or fewer
+/// of them - up to the last 50 tracks are remembered.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	aliases("recent", "played")
+)]
+pub async fn history(
+	ctx: PoiseContext<'_>,
+	#[description = "How many tracks to show."] count: Option<usize>,
+) -> Result<(), Error> {
 	let guild_id = if let Some(guild_id) = ctx.guild_id() {
 		guild_id
 	} else {
@@ -947,53 +3434,439 @@ pub async fn queue(ctx: PoiseContext<'_>) -> Result<(), Error> {
 		return Ok(());
 	};
 
+	let count = count
+		.unwrap_or(DEFAULT_HISTORY_COUNT)
+		.clamp(1, MAX_HISTORY_LENGTH);
+	let entries = ctx
+		.data()
+		.play_history
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.get(&guild_id)
+		.map(|history| history.iter().take(count).cloned().collect::<Vec<_>>())
+		.unwrap_or_default();
+	let entries_len = entries.len();
+	if entries_len == 0 {
+		reply(ctx, "Nothing has been played recently.").await?;
+		return Ok(());
+	}
+
+	let timezone = {
+		let conn = ctx.data().db_pool.get()?;
+		get_guild_settings(&conn, guild_id.0 as i64)?.timezone
+	};
+
+	let number_width = entries_len.log10() as usize + 1;
+	let mut desc = String::new();
+	for (i, played_track) in entries.iter().enumerate() {
+		desc.push_str(format!("`{:01$}.` [", i + 1, number_width).as_str());
+		push_chopped_str(
+			&mut desc,
+			played_track.title.as_str(),
+			MAX_LIST_ENTRY_LENGTH,
+		);
+		desc.push_str("](");
+		desc.push_str(played_track.uri.as_str());
+		desc.push(')');
+		if let Some(requester) = played_track.requester {
+			desc.push_str(format!(" - {}", requester.mention()).as_str());
+		}
+		desc.push_str(
+			format!(
+				" - {}",
+				display_time(played_track.played_at, timezone.as_str())
+			)
+			.as_str(),
+		);
+		if i < entries_len - 1 {
+			desc.push('\n');
+			if desc.len() > DESCRIPTION_LENGTH_CUTOFF {
+				desc.push_str("*\u{2026}the rest has been clipped*");
+				break;
+			}
+		}
+	}
+
+	reply_embed(ctx, |e| {
+		e.title(if entries_len == 1 {
+			"Recently Played (1 track):".to_owned()
+		} else {
+			format!("Recently Played ({} tracks):", entries_len)
+		})
+		.description(desc)
+	})
+	.await?;
+
+	Ok(())
+}
+}
+
+/// Checks whether a track from the history can be queued again, replying with
+/// why not if it can't.
+///
+/// The server's settings may have changed since the track was played, so it's
+/// held to the same rules as a newly-queued track. Its title stands in for the
+/// search, since that's what the profanity filter would have been checking.
+async fn check_requeue_allowed(
+	ctx: PoiseContext<'_>,
+	guild_id: SerenityGuildId,
+	uri: &str,
+	title: &str,
+) -> Result<bool, Error> {
+	let blocked_sources = {
+		let conn = ctx.data().db_pool.get()?;
+		get_blocked_sources(&conn, guild_id.0 as i64)?
+	};
+	if is_blocked_source(uri, &blocked_sources) {
+		reply(ctx, "That's from a source that's blocked in this server.").await?;
+		return Ok(false);
+	}
+	if violates_profanity_filter(ctx.data(), guild_id, title)? {
+		reply(ctx, FILTERED_MESSAGE).await?;
+		return Ok(false);
+	}
+
+	Ok(true)
+}
+
+panic_safe! {
+/// Play a track from the history again.
+///
+/// The number is the one the track has in the `history` list, so `replay 1`
+/// queues the most recently played track again.
+///
+/// The track has to follow the same rules as `play`, so tracks from blocked
+/// sources or with filtered words in their titles can't be replayed.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn replay(
+	ctx: PoiseContext<'_>,
+	#[description = "The track's number in the history."] number: usize,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let history_len = ctx
+		.data()
+		.play_history
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.get(&guild.id)
+		.map_or(0, VecDeque::len);
+	if history_len == 0 {
+		reply(ctx, "Nothing has been played recently.").await?;
+		return Ok(());
+	}
+	if number < 1 || number > history_len {
+		reply(
+			ctx,
+			format!(
+				"The track number must be between 1 and {}, from `history`.",
+				history_len
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+	let played_track = ctx
+		.data()
+		.play_history
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.get(&guild.id)
+		.and_then(|history| history.get(number - 1).cloned());
+	let played_track = if let Some(played_track) = played_track {
+		played_track
+	} else {
+		reply(ctx, "That track is no longer in the history.").await?;
+		return Ok(());
+	};
+
+	if !check_requeue_allowed(
+		ctx,
+		guild.id,
+		played_track.uri.as_str(),
+		played_track.title.as_str(),
+	)
+	.await?
+	{
+		return Ok(());
+	}
+
+	if !ensure_voice_connection(ctx, &guild).await? {
+		return Ok(());
+	}
+
+	// The encoded track is played again as-is, so it's the exact same track
 	let lavalink = &ctx.data().lavalink;
+	let info = lavalink
+		.decode_track(played_track.track.clone())
+		.await
+		.with_context(|| "failed to decode the track from the history")?;
+	let track = Track {
+		track: played_track.track,
+		info: Some(info),
+	};
 
-	let mut something_in_queue = false;
-	if let Some(node) = lavalink.nodes().await.get(&guild_id.0) {
-		let queue = &node.queue;
-		let queue_len = queue.len();
+	let previous_queue_len = lavalink
+		.nodes()
+		.await
+		.get(&guild.id.0)
+		.map_or(0, |node| node.queue.len());
+
+	let mut queueable = lavalink.play(guild.id.0, track);
+	queueable.requester(ctx.author().id.0);
+	if let Err(e) = queueable.queue().await {
+		reply(ctx, "Failed to queue up the track.").await?;
+		eprintln!("Failed to queue up the track from the history: {}", e);
+		return Ok(());
+	};
+	ctx.data().queue_numbering.record_added(guild.id, 1);
+
+	reply(
+		ctx,
+		format!(
+			"{}: [{}]({})",
+			if previous_queue_len == 0 {
+				"Replaying".to_owned()
+			} else {
+				format!("Re-queued (position {})", previous_queue_len + 1)
+			},
+			chop_str(played_track.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+			played_track.uri
+		),
+	)
+	.await?;
 
-		if queue_len > 0 {
-			something_in_queue = true;
+	Ok(())
+}
+}
 
-			let global_queued_count = {
-				let mut hash_map = ctx.data().queued_count.lock().unwrap();
-				*hash_map.entry(guild_id).or_default()
-			};
-			let entry_offset = global_queued_count - queue_len;
-			let number_width = global_queued_count.log10() as usize + 1;
-
-			let mut desc = String::new();
-			for (i, queued_track) in queue.iter().enumerate() {
-				let track_info = queued_track.track.info.as_ref().unwrap();
-				desc.push_str(format!("`{:01$}.` [", entry_offset + i + 1, number_width).as_str());
-				push_chopped_str(&mut desc, track_info.title.as_str(), MAX_LIST_ENTRY_LENGTH);
-				desc.push_str("](");
-				desc.push_str(track_info.uri.as_str());
-				desc.push(')');
-				if i < queue_len - 1 {
-					desc.push('\n');
-					if desc.len() > DESCRIPTION_LENGTH_CUTOFF {
-						desc.push_str("*\u{2026}the rest has been clipped*");
-						break;
-					}
+panic_safe! {
+/// Play the previous track again, right after the current one.
+///
+/// The track is forgotten once it's queued again, so using this again goes
+/// further back. If nothing is playing, the last track to play is started
+/// again.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	aliases("prev", "back")
+)]
+pub async fn previous(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	// The current track is recorded as soon as it starts, so it has to be passed
+	// over
+	let is_playing = lavalink
+		.nodes()
+		.await
+		.get(&guild.id.0)
+		.map_or(false, |node| node.now_playing.is_some());
+	let index = if is_playing { 1 } else { 0 };
+	let track = ctx
+		.data()
+		.previous_tracks
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.get(&guild.id)
+		.and_then(|previous_tracks| previous_tracks.get(index))
+		.cloned();
+	let track = if let Some(track) = track {
+		track
+	} else {
+		reply(ctx, "There's no previous track to play.").await?;
+		return Ok(());
+	};
+	// Tracks are only recorded along with their info
+	let info = track.info.clone().unwrap();
+
+	if !check_requeue_allowed(ctx, guild.id, info.uri.as_str(), info.title.as_str()).await? {
+		return Ok(());
+	}
+
+	if !ensure_voice_connection(ctx, &guild).await? {
+		return Ok(());
+	}
+
+	// Forget the track now that it's about to be queued. Any tracks that started in
+	// the meantime were recorded in front of it, so it can only have moved further
+	// back
+	let forgotten_at = ctx
+		.data()
+		.previous_tracks
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.get_mut(&guild.id)
+		.and_then(|previous_tracks| {
+			let position = index
+				+ previous_tracks
+					.iter()
+					.skip(index)
+					.position(|previous_track| previous_track.track == track.track)?;
+			previous_tracks.remove(position);
+			Some(position)
+		});
+
+	let mut queueable = lavalink.play(guild.id.0, track.clone());
+	queueable.requester(ctx.author().id.0);
+	if let Err(e) = queueable.queue().await {
+		// Put the track back, so that it can still be played again
+		if let Some(position) = forgotten_at {
+			let mut previous_tracks = ctx
+				.data()
+				.previous_tracks
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner);
+			let guild_tracks = previous_tracks.entry(guild.id).or_default();
+			guild_tracks.insert(position.min(guild_tracks.len()), track);
+		}
+		reply(ctx, "Failed to queue up the track.").await?;
+		eprintln!("Failed to queue up the previous track: {}", e);
+		return Ok(());
+	};
+	ctx.data().queue_numbering.record_added(guild.id, 1);
+
+	// Move the track up from the end of the queue so that it plays next
+	let plays_next = if let Some(mut node) = lavalink.nodes().await.get_mut(&guild.id.0) {
+		if node.queue.len() > 2 {
+			let queued_track = node.queue.pop().unwrap();
+			node.queue.insert(1, queued_track);
+		}
+		node.queue.len() > 1
+	} else {
+		false
+	};
+
+	reply(
+		ctx,
+		format!(
+			"{}: [{}]({})",
+			if plays_next {
+				"Playing next"
+			} else {
+				"Replaying"
+			},
+			chop_str(info.title.as_str(), MAX_SINGLE_ENTRY_LENGTH),
+			info.uri
+		),
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Estimate how long it'll be until a queued track starts playing.
+///
+/// The position is the number the track has in the `queue` list.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "playtime",
+	aliases("eta", "when")
+)]
+pub async fn play_time(
+	ctx: PoiseContext<'_>,
+	#[description = "The position of the track in the queue."] position: usize,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let lavalink = &ctx.data().lavalink;
+
+	// Work out the estimate, or the reason there isn't one
+	let estimate = if let Some(node) = lavalink.nodes().await.get(&guild_id.0) {
+		let queue = &node.queue;
+		let entry_offset = ctx
+			.data()
+			.queue_numbering
+			.entry_offset(guild_id, queue.len());
+
+		match position
+			.checked_sub(entry_offset + 1)
+			.filter(|index| *index < queue.len())
+		{
+			None => Err(format!("There's no track at position {}.", position)),
+			Some(0) => Err("That track is playing right now.".to_owned()),
+			Some(index) => {
+				// The rest of the current track, then everything between it and the requested
+				// track
+				let mut total_millis = 0;
+				let mut live_track_ahead = false;
+				if let Some(now_playing) = &node.now_playing {
+					let track_info = now_playing.track.info.as_ref().unwrap();
+					live_track_ahead |= track_info.is_stream;
+					total_millis += track_info.length.saturating_sub(track_info.position);
 				}
-			}
-			reply_embed(ctx, |e| {
-				e.title(if queue_len == 1 {
-					format!("Queue ({} total track):", queue_len)
+				for queued_track in &queue[1..index] {
+					let track_info = queued_track.track.info.as_ref().unwrap();
+					live_track_ahead |= track_info.is_stream;
+					total_millis += track_info.length.saturating_sub(queued_track.start_time);
+				}
+
+				Ok(if live_track_ahead {
+					"Plays in an unknown amount of time (live track ahead).".to_owned()
 				} else {
-					format!("Queue ({} total tracks):", queue_len)
+					format!("Plays in about {}.", display_timecode(total_millis))
 				})
-				.description(desc)
-			})
-			.await?;
+			}
 		}
-	}
-	if !something_in_queue {
-		reply(ctx, "Nothing is in the queue.").await?;
-	}
+	} else {
+		Err("Nothing is in the queue.".to_owned())
+	};
+
+	match estimate {
+		Ok(message) | Err(message) => reply(ctx, message).await?,
+	};
 
 	Ok(())
 }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn generic_attachments_are_played_by_extension() {
+		assert!(is_playable_attachment(
+			Some(GENERIC_CONTENT_TYPE),
+			"song.flac"
+		));
+		assert!(is_playable_attachment(
+			Some(GENERIC_CONTENT_TYPE),
+			"SONG.OPUS"
+		));
+		assert!(is_playable_attachment(None, "song.flac"));
+		assert!(!is_playable_attachment(
+			Some(GENERIC_CONTENT_TYPE),
+			"notes.txt"
+		));
+		assert!(!is_playable_attachment(Some(GENERIC_CONTENT_TYPE), "flac"));
+	}
+
+	#[test]
+	fn specific_content_types_are_trusted() {
+		assert!(is_playable_attachment(Some("audio/flac"), "song"));
+		assert!(is_playable_attachment(Some("video/mp4"), "clip.bin"));
+		assert!(!is_playable_attachment(Some("image/png"), "song.flac"));
+	}
+}
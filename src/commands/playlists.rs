@@ -0,0 +1,411 @@
+// Uses
+use std::{borrow::Cow, time::Duration};
+
+use anyhow::Context;
+use diesel::{
+	delete,
+	insert_into,
+	Connection,
+	ExpressionMethods,
+	OptionalExtension,
+	QueryDsl,
+	QueryResult,
+	RunQueryDsl,
+};
+use poise::serenity::model::misc::Mentionable;
+
+use super::playback::{ensure_voice_connection, is_blocked_source};
+use crate::{
+	db::{get_blocked_sources, models::PlaylistTrack, schema::*, DbConnection},
+	util::{confirm, escape_str, reply, reply_embed},
+	Error,
+	PoiseContext,
+};
+
+// Constants
+/// The most tracks that a playlist can hold, since loading one has to look up
+/// every track again.
+const MAX_PLAYLIST_LENGTH: usize = 200;
+const OVERWRITE_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Commands
+
+panic_safe! {
+/// Save the queue as a playlist, to load again later.
+///
+/// The whole queue is saved, including the current track. Playlists are saved
+/// for you in this server, and the name is case-insensitive. If you already
+/// have a playlist with the same name, you'll be asked before it's
+/// overwritten.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playlists",
+	rename = "saveplaylist"
+)]
+pub async fn save_playlist(
+	ctx: PoiseContext<'_>,
+	#[description = "The name to save the playlist as."] identifier: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let (ctx_guild_id, ctx_user_id) = (guild_id.0 as i64, ctx.author().id.0 as i64);
+	let identifier = identifier.trim().to_lowercase();
+
+	let uris = ctx
+		.data()
+		.lavalink
+		.nodes()
+		.await
+		.get(&guild_id.0)
+		.map(|node| {
+			node.queue
+				.iter()
+				.filter_map(|queued_track| queued_track.track.info.as_ref())
+				.map(|info| info.uri.clone())
+				.collect::<Vec<_>>()
+		})
+		.unwrap_or_default();
+	if uris.is_empty() {
+		reply(ctx, "Nothing is in the queue.").await?;
+		return Ok(());
+	}
+	if uris.len() > MAX_PLAYLIST_LENGTH {
+		reply(
+			ctx,
+			format!(
+				"Playlists can only hold up to {} tracks.",
+				MAX_PLAYLIST_LENGTH
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	// Make sure an existing playlist isn't overwritten by accident
+	let already_exists = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		playlist_tracks::table
+			.filter(playlist_tracks::guild_id.eq(ctx_guild_id))
+			.filter(playlist_tracks::user_id.eq(ctx_user_id))
+			.filter(playlist_tracks::name.eq(&identifier))
+			.select(playlist_tracks::position)
+			.first::<i32>(&conn)
+			.optional()
+			.with_context(|| "failed to check for an existing playlist")?
+			.is_some()
+	};
+	if already_exists
+		&& !confirm(
+			ctx,
+			format!(
+				"A playlist named `{}` already exists. Overwrite it?",
+				escape_str(identifier.as_str())
+			),
+			"Overwrite",
+			OVERWRITE_CONFIRMATION_TIMEOUT,
+		)
+		.await?
+	{
+		reply(
+			ctx,
+			format!(
+				"Kept the existing playlist `{}`.",
+				escape_str(identifier.as_str())
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	// Replace the playlist's tracks all at once, so it's never left half-saved
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		let tracks = uris
+			.iter()
+			.enumerate()
+			.map(|(position, uri)| PlaylistTrack {
+				guild_id: ctx_guild_id,
+				user_id: ctx_user_id,
+				name: Cow::from(identifier.as_str()),
+				position: position as i32,
+				uri: Cow::from(uri.as_str()),
+			})
+			.collect::<Vec<_>>();
+		replace_playlist(&conn, identifier.as_str(), &tracks)
+			.with_context(|| "failed to save the playlist to the database")?;
+	}
+
+	reply(
+		ctx,
+		format!(
+			"Saved {} track{} as the playlist `{}`.",
+			uris.len(),
+			if uris.len() == 1 { "" } else { "s" },
+			escape_str(identifier.as_str())
+		),
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Add one of your saved playlists to the queue.
+///
+/// Each track is looked up again, so any that are no longer available are left
+/// out.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playlists",
+	rename = "loadplaylist"
+)]
+pub async fn load_playlist(
+	ctx: PoiseContext<'_>,
+	#[description = "The name of the playlist to load."] identifier: String,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let (ctx_guild_id, ctx_user_id) = (guild.id.0 as i64, ctx.author().id.0 as i64);
+	let identifier = identifier.trim().to_lowercase();
+
+	let (uris, blocked_sources) = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		(
+			playlist_tracks::table
+				.filter(playlist_tracks::guild_id.eq(ctx_guild_id))
+				.filter(playlist_tracks::user_id.eq(ctx_user_id))
+				.filter(playlist_tracks::name.eq(&identifier))
+				.order_by(playlist_tracks::position)
+				.select(playlist_tracks::uri)
+				.load::<String>(&conn)
+				.with_context(|| "failed to retrieve the playlist's tracks")?,
+			get_blocked_sources(&conn, ctx_guild_id)?,
+		)
+	};
+	if uris.is_empty() {
+		reply(
+			ctx,
+			format!(
+				"A playlist could not be found with the name `{}`.",
+				escape_str(identifier.as_str())
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	if !ensure_voice_connection(ctx, &guild).await? {
+		return Ok(());
+	}
+
+	let lavalink = &ctx.data().lavalink;
+	let mut queued_count = 0;
+	let mut missing_count = 0;
+	for uri in &uris {
+		if is_blocked_source(uri.as_str(), &blocked_sources) {
+			missing_count += 1;
+			continue;
+		}
+		let track = if let Some(track) = lavalink
+			.auto_search_tracks(uri.as_str())
+			.await
+			.ok()
+			.and_then(|query_result| query_result.tracks.into_iter().next())
+		{
+			track
+		} else {
+			missing_count += 1;
+			continue;
+		};
+
+		let mut queueable = lavalink.play(guild.id.0, track);
+		queueable.requester(ctx.author().id.0);
+		if let Err(e) = queueable.queue().await {
+			reply(ctx, "Failed to queue up the playlist.").await?;
+			eprintln!("Failed to queue up a track from a playlist: {}", e);
+			return Ok(());
+		}
+		// Count each track as soon as it's queued, so that a failure partway through
+		// doesn't throw off the numbering
+		ctx.data().queue_numbering.record_added(guild.id, 1);
+		queued_count += 1;
+	}
+
+	let mut response = format!(
+		"Added {} track{} from the playlist `{}`.",
+		queued_count,
+		if queued_count == 1 { "" } else { "s" },
+		escape_str(identifier.as_str())
+	);
+	if missing_count > 0 {
+		response.push_str(
+			format!(
+				" {} track{} couldn't be found or {} from a blocked source.",
+				missing_count,
+				if missing_count == 1 { "" } else { "s" },
+				if missing_count == 1 { "is" } else { "are" }
+			)
+			.as_str(),
+		);
+	}
+	reply(ctx, response).await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Show a list of all your saved playlists.
+#[command(prefix_command, slash_command, category = "Playlists")]
+pub async fn playlists(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	// Every track has a row of its own, so the rows are counted up by playlist
+	let names = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		playlist_tracks::table
+			.filter(playlist_tracks::guild_id.eq(guild_id.0 as i64))
+			.filter(playlist_tracks::user_id.eq(ctx.author().id.0 as i64))
+			.order_by(playlist_tracks::name)
+			.select(playlist_tracks::name)
+			.load::<String>(&conn)
+			.with_context(|| "failed to retrieve a list of the saved playlists")?
+	};
+	let mut playlist_lengths = Vec::<(String, usize)>::new();
+	for name in names {
+		match playlist_lengths.last_mut() {
+			Some((last_name, length)) if *last_name == name => *length += 1,
+			Some(_) | None => playlist_lengths.push((name, 1)),
+		}
+	}
+
+	if playlist_lengths.is_empty() {
+		reply(
+			ctx,
+			format!(
+				"No saved playlists could be found for {}.",
+				ctx.author().id.mention()
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	let mut output = format!("For {}:", ctx.author().id.mention());
+	for (name, length) in &playlist_lengths {
+		output.push_str(
+			format!(
+				"\n**{}:** {} track{}",
+				escape_str(name.as_str()),
+				length,
+				if *length == 1 { "" } else { "s" }
+			)
+			.as_str(),
+		);
+	}
+
+	reply_embed(ctx, |e| e.title("Saved Playlists").description(output)).await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Delete one of your saved playlists.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playlists",
+	rename = "deleteplaylist"
+)]
+pub async fn delete_playlist(
+	ctx: PoiseContext<'_>,
+	#[description = "The name of the playlist to delete."] identifier: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+	let identifier = identifier.trim().to_lowercase();
+
+	let deleted_rows = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		delete(playlist_tracks::table)
+			.filter(playlist_tracks::guild_id.eq(guild_id.0 as i64))
+			.filter(playlist_tracks::user_id.eq(ctx.author().id.0 as i64))
+			.filter(playlist_tracks::name.eq(&identifier))
+			.execute(&conn)
+			.with_context(|| "failed to delete the playlist from the database")?
+	};
+
+	if deleted_rows > 0 {
+		reply(
+			ctx,
+			format!(
+				"The playlist `{}` was deleted.",
+				escape_str(identifier.as_str())
+			),
+		)
+		.await?;
+	} else {
+		reply(
+			ctx,
+			format!(
+				"A playlist could not be found with the name `{}`.",
+				escape_str(identifier.as_str())
+			),
+		)
+		.await?;
+	}
+
+	Ok(())
+}
+}
+
+// Utility Functions
+
+/// Replaces all of the tracks in a playlist with `tracks`, which all belong to
+/// the playlist named `name`.
+///
+/// This is done in a single transaction, so the playlist is never left
+/// half-saved.
+fn replace_playlist(conn: &DbConnection, name: &str, tracks: &[PlaylistTrack]) -> QueryResult<()> {
+	conn.transaction(|| {
+		if let Some(first_track) = tracks.first() {
+			delete(playlist_tracks::table)
+				.filter(playlist_tracks::guild_id.eq(first_track.guild_id))
+				.filter(playlist_tracks::user_id.eq(first_track.user_id))
+				.filter(playlist_tracks::name.eq(name))
+				.execute(conn)?;
+		}
+		for track in tracks {
+			insert_into(playlist_tracks::table)
+				.values(track)
+				.execute(conn)?;
+		}
+		Ok(())
+	})
+}
@@ -0,0 +1,1026 @@
+// Uses
+use std::sync::PoisonError;
+
+use anyhow::Context;
+use chrono_tz::Tz;
+#[cfg(feature = "postgres")]
+use diesel::insert_into;
+#[cfg(feature = "sqlite")]
+use diesel::insert_or_ignore_into;
+use diesel::{delete, update, ExpressionMethods, QueryDsl, RunQueryDsl};
+use poise::serenity::model::{guild::Role, misc::Mentionable, user::User};
+
+use super::playback::{is_server_manager, MAX_SEARCH_RESULTS, MAX_VOLUME};
+use crate::{
+	db::{
+		ensure_guild_settings,
+		get_blocked_sources,
+		is_user_ignored,
+		models::{BlockedSource, IgnoredUser},
+		save_guild_volume,
+		schema::*,
+	},
+	filters::apply_filters,
+	util::{escape_str, parse_toggle, reply},
+	Error,
+	PoiseContext,
+};
+
+// Commands
+
+panic_safe! {
+/// Set whether Radium deafens itself when it joins a voice channel.
+///
+/// Radium never needs to hear anything, so it deafens itself by default. This
+/// saves a little bandwidth.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "selfdeafen",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn self_deafen(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::self_deafen.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the self-deafen setting to the database")?;
+	}
+
+	// Apply it right away if Radium is already in a voice channel
+	if let Some(call) = ctx.data().songbird.get(guild_id) {
+		call.lock().await.deafen(new_value).await?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"Radium will now deafen itself in voice channels."
+		} else {
+			"Radium will no longer deafen itself in voice channels."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set whether TTS messages and searches are checked for profanity.
+///
+/// When it's on, anything containing a filtered word is rejected. It's off by
+/// default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "profanityfilter",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn profanity_filter(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::profanity_filter.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the profanity filter setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"TTS messages and searches will now be checked for profanity."
+		} else {
+			"TTS messages and searches will no longer be checked for profanity."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set whether track volumes are evened out.
+///
+/// Some tracks are much louder than others, and this turns the loud ones down
+/// so the volume doesn't need to be adjusted between them. It's off by default.
+///
+/// This relies on the audio server (Lavalink) supporting filters.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn normalize(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::normalize.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the normalization setting to the database")?;
+	}
+
+	// Apply it right away if something is already playing
+	let lavalink = &ctx.data().lavalink;
+	if lavalink.nodes().await.contains_key(&guild_id.0) {
+		let active_filter = ctx
+			.data()
+			.active_filters
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&guild_id)
+			.copied();
+		apply_filters(lavalink, guild_id.0, new_value, active_filter).await?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"Track volumes will now be evened out."
+		} else {
+			"Track volumes will no longer be evened out."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set whether SponsorBlock segments marked to be muted are muted instead of
+/// skipped.
+///
+/// Some segments only need their audio cut out, like a bit of music that
+/// couldn't be used. When this is on, those are muted for their duration
+/// rather than skipped past. It's off by default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "mutesegments",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn mute_segments(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::mute_segments.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the mute segments setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"Segments marked to be muted will now be muted instead of skipped."
+		} else {
+			"Segments marked to be muted will now be skipped like any other."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set whether SponsorBlock segments marked as non-music are skipped.
+///
+/// These are the parts of music videos without any music, like an intro
+/// skit. They don't mean much for anything other than music, so they can get
+/// in the way of podcasts and talks, where the whole video is "non-music".
+/// It's on by default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "skipnonmusic",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn skip_non_music(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::skip_non_music.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the skip non-music setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"Non-music segments will now be skipped."
+		} else {
+			"Non-music segments will no longer be skipped. This applies from the next track on."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set how many search results are queued when playing a search.
+///
+/// By default only the top result is queued, but queueing a few more is a
+/// quick way to play something along with some related tracks. URLs are
+/// unaffected.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "searchresults",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn search_results(
+	ctx: PoiseContext<'_>,
+	#[description = "How many results to queue, up to 5."] count: u32,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	if count < 1 || count as usize > MAX_SEARCH_RESULTS {
+		reply(
+			ctx,
+			format!(
+				"The number of results must be between 1 and {}.",
+				MAX_SEARCH_RESULTS
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::search_results.eq(count as i32))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the search results setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		if count == 1 {
+			"Searches will now queue only the top result.".to_owned()
+		} else {
+			format!("Searches will now queue the top {} results.", count)
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set the volume Radium starts at whenever it joins a voice channel.
+///
+/// The volume is a percentage, up to 150. Use `defaultvolume reset` to go back
+/// to the audio server's default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "defaultvolume",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn default_volume(
+	ctx: PoiseContext<'_>,
+	#[description = "The volume as a percentage, or `reset`."] volume: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if volume.trim().eq_ignore_ascii_case("reset") {
+		None
+	} else if let Some(new_value) = volume
+		.trim()
+		.trim_end_matches('%')
+		.parse::<u16>()
+		.ok()
+		.filter(|new_value| *new_value <= MAX_VOLUME)
+	{
+		Some(new_value)
+	} else {
+		reply(
+			ctx,
+			format!(
+				"The volume must be a percentage between 0 and {}, or `reset`.",
+				MAX_VOLUME
+			),
+		)
+		.await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		save_guild_volume(&conn, guild_id.0 as i64, new_value.map(i32::from))
+			.with_context(|| "failed to save the default volume to the database")?;
+	}
+
+	reply(
+		ctx,
+		if let Some(new_value) = new_value {
+			format!(
+				"Radium will now start at {}% volume when it joins.",
+				new_value
+			)
+		} else {
+			"Radium will now start at the default volume when it joins.".to_owned()
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set whether Radium reacts to critical d20 rolls.
+///
+/// When it's on, rolling a single d20 gets a reaction on a natural 20 or a
+/// natural 1. It's on by default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "critreactions",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn crit_reactions(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::crit_reactions.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the crit reactions setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"Critical d20 rolls will now get a reaction."
+		} else {
+			"Critical d20 rolls will no longer get a reaction."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set whether `nowplaying` shows a single line instead of a full embed.
+///
+/// Either way can still be asked for on a case-by-case basis, with `nowplaying
+/// --compact` or `nowplaying --full`. It's off by default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "compactnowplaying",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn compact_now_playing(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `on` or `off`."] setting: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if let Some(new_value) = parse_toggle(setting.as_str()) {
+		new_value
+	} else {
+		reply(ctx, "The setting must be either `on` or `off`.").await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::compact_now_playing.eq(new_value))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the compact now playing setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		if new_value {
+			"`nowplaying` will now show a single line."
+		} else {
+			"`nowplaying` will now show the full details."
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set how many people have to be listening before skipping takes a vote.
+///
+/// When more people than this are in the voice channel, `skip` only skips once
+/// a majority of them have used it. Members with the DJ role (and server
+/// managers) can always skip right away. Use `voteskip off` to turn voting off,
+/// which is the default.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "voteskip",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn vote_skip_threshold(
+	ctx: PoiseContext<'_>,
+	#[description = "The number of listeners, or `off`."] threshold: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let new_value = if parse_toggle(threshold.as_str()) == Some(false) {
+		None
+	} else if let Ok(new_value) = threshold.trim().parse::<u16>() {
+		Some(new_value)
+	} else {
+		reply(
+			ctx,
+			"The threshold must be a number of listeners, or `off`.",
+		)
+		.await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::vote_skip_threshold.eq(new_value.map(i32::from)))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the vote skip threshold to the database")?;
+	}
+
+	reply(
+		ctx,
+		if let Some(new_value) = new_value {
+			format!(
+				"Skipping will now take a vote when more than {} people are listening.",
+				new_value
+			)
+		} else {
+			"Skipping will no longer take a vote.".to_owned()
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set the timezone that times are shown in, such as in the play history.
+///
+/// The timezone must be an IANA timezone name, eg. `America/Toronto`. The
+/// default is `UTC`.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn timezone(
+	ctx: PoiseContext<'_>,
+	#[description = "An IANA timezone name, eg. `America/Toronto`."] timezone: String,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let timezone = if let Ok(timezone) = timezone.trim().parse::<Tz>() {
+		timezone
+	} else {
+		reply(
+			ctx,
+			format!(
+				"`{}` isn't a recognized timezone. Use an IANA timezone name, eg. \
+				 `America/Toronto`.",
+				escape_str(timezone.as_str())
+			),
+		)
+		.await?;
+		return Ok(());
+	};
+
+	// Save the setting
+	{
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		ensure_guild_settings(&conn, guild_id.0 as i64)
+			.and_then(|_| {
+				update(guild_settings::table.find(guild_id.0 as i64))
+					.set(guild_settings::timezone.eq(timezone.name()))
+					.execute(&conn)
+			})
+			.with_context(|| "failed to save the timezone setting to the database")?;
+	}
+
+	reply(
+		ctx,
+		format!("Times will now be shown in `{}`.", timezone.name()),
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Set the DJ role, restricting the playback control commands to it.
+///
+/// Once it's set, only members with the DJ role (and those who can manage the
+/// server) can use commands like `skip` and `clear`.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "setdjrole",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn set_dj_role(
+	ctx: PoiseContext<'_>,
+	#[description = "The role to use as the DJ role."] role: Role,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	save_dj_role(ctx, guild_id.0 as i64, Some(role.id.0 as i64))?;
+
+	reply(ctx, format!("The DJ role is now {}.", role.mention())).await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Clear the DJ role, letting everyone use the playback control commands again.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "cleardjrole",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn clear_dj_role(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	save_dj_role(ctx, guild_id.0 as i64, None)?;
+
+	reply(ctx, "There is no longer a DJ role.").await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Manage the sources that tracks can't be played from.
+///
+/// Sources are matched against the website a track comes from, so blocking
+/// `twitch` blocks anything from `twitch.tv`. Use `add` or `remove` followed by
+/// the source, or `list` to see what's blocked.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	rename = "blocksource",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn block_source(
+	ctx: PoiseContext<'_>,
+	#[description = "Either `add`, `remove`, or `list`."] action: String,
+	#[rest]
+	#[description = "The source to add or remove, eg. `twitch.tv`."]
+	source: Option<String>,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let source = source
+		.map(|s| s.trim().to_lowercase())
+		.filter(|s| !s.is_empty());
+
+	let response = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		match (action.to_lowercase().as_str(), source) {
+			("add", Some(source)) => {
+				let blocked_source = BlockedSource {
+					guild_id: guild_id.0 as i64,
+					source: source.as_str().into(),
+				};
+				#[cfg(feature = "sqlite")]
+				let inserted = insert_or_ignore_into(blocked_sources::table)
+					.values(&blocked_source)
+					.execute(&conn);
+				#[cfg(feature = "postgres")]
+				let inserted = insert_into(blocked_sources::table)
+					.values(&blocked_source)
+					.on_conflict_do_nothing()
+					.execute(&conn);
+				let inserted_count = inserted
+					.with_context(|| "failed to save the blocked source to the database")?;
+				if inserted_count > 0 {
+					format!("Blocked `{}`.", escape_str(source.as_str()))
+				} else {
+					format!("`{}` is already blocked.", escape_str(source.as_str()))
+				}
+			}
+			("remove", Some(source)) => {
+				let deleted_count = delete(
+					blocked_sources::table
+						.filter(blocked_sources::guild_id.eq(guild_id.0 as i64))
+						.filter(blocked_sources::source.eq(source.as_str())),
+				)
+				.execute(&conn)
+				.with_context(|| "failed to remove the blocked source from the database")?;
+				if deleted_count > 0 {
+					format!("Unblocked `{}`.", escape_str(source.as_str()))
+				} else {
+					format!("`{}` isn't blocked.", escape_str(source.as_str()))
+				}
+			}
+			("add" | "remove", None) => "You need to specify a source.".to_owned(),
+			("list", _) => {
+				let blocked_sources = get_blocked_sources(&conn, guild_id.0 as i64)
+					.with_context(|| "failed to load the blocked sources from the database")?;
+				if blocked_sources.is_empty() {
+					"No sources are blocked.".to_owned()
+				} else {
+					format!(
+						"**Blocked Sources:**\n{}",
+						blocked_sources
+							.iter()
+							.map(|s| format!("- `{}`", escape_str(s)))
+							.collect::<Vec<_>>()
+							.join("\n")
+					)
+				}
+			}
+			_ => "The action must be either `add`, `remove`, or `list`.".to_owned(),
+		}
+	};
+
+	reply(ctx, response).await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Ignore a user's commands in this server.
+///
+/// Anything they try to use is silently dropped, until they're unignored with
+/// `unignore`. The server's owner and anyone who can manage the server can't
+/// be ignored.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn ignore(
+	ctx: PoiseContext<'_>,
+	#[description = "The user to ignore."] user: User,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	if user.bot {
+		reply(ctx, "Bots can't use Radium's commands anyway.").await?;
+		return Ok(());
+	}
+	// Anyone who isn't in the server can't be managing it
+	let can_be_ignored = guild
+		.id
+		.member(ctx.discord(), user.id)
+		.await
+		.map_or(true, |member| !is_server_manager(&guild, &member));
+	if !can_be_ignored || ctx.framework().options().owners.contains(&user.id) {
+		reply(ctx, format!("{} can't be ignored.", user.id.mention())).await?;
+		return Ok(());
+	}
+
+	let inserted_count = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		let ignored_user = IgnoredUser {
+			guild_id: guild.id.0 as i64,
+			user_id: user.id.0 as i64,
+		};
+		#[cfg(feature = "sqlite")]
+		let inserted = insert_or_ignore_into(ignored_users::table)
+			.values(&ignored_user)
+			.execute(&conn);
+		#[cfg(feature = "postgres")]
+		let inserted = insert_into(ignored_users::table)
+			.values(&ignored_user)
+			.on_conflict_do_nothing()
+			.execute(&conn);
+		inserted.with_context(|| "failed to save the ignored user to the database")?
+	};
+
+	reply(
+		ctx,
+		if inserted_count > 0 {
+			format!("Now ignoring {}.", user.id.mention())
+		} else {
+			format!("{} is already ignored.", user.id.mention())
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Stop ignoring a user's commands in this server.
+///
+/// The opposite of `ignore`.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Settings",
+	required_permissions = "MANAGE_GUILD"
+)]
+pub async fn unignore(
+	ctx: PoiseContext<'_>,
+	#[description = "The user to stop ignoring."] user: User,
+) -> Result<(), Error> {
+	let guild_id = if let Some(guild_id) = ctx.guild_id() {
+		guild_id
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let deleted_count = {
+		let conn = ctx.data().db_pool.get().unwrap();
+
+		delete(ignored_users::table.find((guild_id.0 as i64, user.id.0 as i64)))
+			.execute(&conn)
+			.with_context(|| "failed to remove the ignored user from the database")?
+	};
+
+	reply(
+		ctx,
+		if deleted_count > 0 {
+			format!("No longer ignoring {}.", user.id.mention())
+		} else {
+			format!("{} isn't ignored.", user.id.mention())
+		},
+	)
+	.await?;
+
+	Ok(())
+}
+}
+
+/// Checks that the user isn't ignored in the guild, which is run before every
+/// command.
+///
+/// Anyone who's been given permission to manage the guild since they were
+/// ignored isn't ignored anymore, so that they can't be locked out of
+/// `unignore`.
+pub async fn check_not_ignored(ctx: PoiseContext<'_>) -> Result<bool, Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		return Ok(true);
+	};
+
+	let ignored = {
+		let conn = ctx.data().db_pool.get()?;
+		is_user_ignored(&conn, guild.id.0 as i64, ctx.author().id.0 as i64)
+			.with_context(|| "failed to check whether the user is ignored")?
+	};
+	if !ignored {
+		return Ok(true);
+	}
+
+	Ok(guild
+		.id
+		.member(ctx.discord(), ctx.author().id)
+		.await
+		.map_or(false, |member| is_server_manager(&guild, &member)))
+}
+
+// Utility Functions
+
+/// Saves the DJ role for a guild, or clears it if `role_id` is [`None`].
+fn save_dj_role(ctx: PoiseContext<'_>, guild_id: i64, role_id: Option<i64>) -> Result<(), Error> {
+	let conn = ctx.data().db_pool.get().unwrap();
+
+	ensure_guild_settings(&conn, guild_id)
+		.and_then(|_| {
+			update(guild_settings::table.find(guild_id))
+				.set(guild_settings::dj_role_id.eq(role_id))
+				.execute(&conn)
+		})
+		.with_context(|| "failed to save the DJ role to the database")?;
+
+	Ok(())
+}
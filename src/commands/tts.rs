@@ -0,0 +1,320 @@
+// Uses
+use std::sync::PoisonError;
+
+use poise::serenity::model::guild::Guild;
+
+use super::playback::{
+	ensure_voice_connection,
+	violates_profanity_filter,
+	FILTERED_MESSAGE,
+	MAX_SINGLE_ENTRY_LENGTH,
+};
+use crate::{
+	util::{chop_str, escape_str, reply},
+	Error,
+	PoiseContext,
+};
+
+// Constants
+/// The search prefix that the DuncteBot Lavalink plugin uses for TTS.
+const TTS_QUERY_PREFIX: &str = "speak:";
+/// The maximum length of a whole TTS message, in characters.
+const MAX_MESSAGE_LENGTH: usize = 1000;
+/// The maximum length of a single TTS fragment, in characters.
+const MAX_FRAGMENT_LENGTH: usize = 200;
+/// The maximum number of fragments a single TTS message can be split into.
+const MAX_FRAGMENT_COUNT: usize = 10;
+/// The option used to pick a voice, eg. `--voice en-GB`.
+const VOICE_OPTION: &str = "--voice";
+/// The voices (language codes) that the DuncteBot plugin supports.
+const KNOWN_VOICES: [&str; 12] = [
+	"en-US", "en-GB", "en-AU", "en-IN", "fr-FR", "fr-CA", "de-DE", "es-ES", "it-IT", "ja-JP",
+	"nl-NL", "pt-BR",
+];
+
+// Definitions
+/// A TTS message that has been queued, kept so that it can be repeated.
+#[derive(Debug, Clone)]
+pub struct TtsMessage {
+	pub message: String,
+	pub voice: Option<&'static str>,
+}
+
+// Commands
+
+panic_safe! {
+/// Have Radium speak a message in the voice channel.
+///
+/// Long messages are split up into several fragments on word boundaries, which
+/// are queued one after the other. Messages can be at most 1000 characters
+/// long.
+///
+/// You can pick a different voice by starting the message with `--voice`
+/// followed by a language code, eg. `--voice fr-FR Bonjour`. Supported voices
+/// are: `en-US`, `en-GB`, `en-AU`, `en-IN`, `fr-FR`, `fr-CA`, `de-DE`,
+/// `es-ES`, `it-IT`, `ja-JP`, `nl-NL`, and `pt-BR`.
+///
+/// This requires the Lavalink server to have the DuncteBot plugin installed.
+#[command(prefix_command, slash_command, category = "Playback")]
+pub async fn tts(
+	ctx: PoiseContext<'_>,
+	#[rest]
+	#[description = "What to say. Start with `--voice <language code>` to pick a voice."]
+	message: String,
+) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	// Parse the voice option, if any
+	let mut message = message.trim();
+	let mut voice = None;
+	if let Some(remaining) = message.strip_prefix(VOICE_OPTION) {
+		let remaining = remaining.trim_start();
+		let (voice_name, rest) = remaining.split_at(
+			remaining
+				.find(char::is_whitespace)
+				.unwrap_or(remaining.len()),
+		);
+		if let Some(known_voice) = KNOWN_VOICES
+			.iter()
+			.find(|known_voice| known_voice.eq_ignore_ascii_case(voice_name))
+		{
+			voice = Some(*known_voice);
+		} else {
+			reply(
+				ctx,
+				format!(
+					"Unknown voice: {}\nThe supported voices are: {}",
+					escape_str(voice_name),
+					KNOWN_VOICES
+						.iter()
+						.map(|known_voice| format!("`{}`", known_voice))
+						.collect::<Vec<_>>()
+						.join(", ")
+				),
+			)
+			.await?;
+			return Ok(());
+		}
+		message = rest.trim();
+	}
+
+	// Remember the message so that it can be repeated - only messages that were
+	// actually queued are kept, so the stored length is capped by the same limits
+	if queue_tts(ctx, &guild, message, voice).await? {
+		let mut last_tts_messages = ctx
+			.data()
+			.last_tts_messages
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		last_tts_messages.insert(
+			(guild.id, ctx.author().id),
+			TtsMessage {
+				message: message.to_owned(),
+				voice,
+			},
+		);
+	}
+
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Repeat the last TTS message you had Radium say in this server.
+///
+/// Radium forgets these messages whenever it restarts.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Playback",
+	rename = "ttsrepeat",
+	aliases("ttsr")
+)]
+pub async fn tts_repeat(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let guild = if let Some(guild) = ctx.guild() {
+		guild
+	} else {
+		reply(ctx, "You must use this command from within a server.").await?;
+		return Ok(());
+	};
+
+	let last_tts_message = {
+		let last_tts_messages = ctx
+			.data()
+			.last_tts_messages
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		last_tts_messages.get(&(guild.id, ctx.author().id)).cloned()
+	};
+
+	if let Some(last_tts_message) = last_tts_message {
+		queue_tts(
+			ctx,
+			&guild,
+			last_tts_message.message.as_str(),
+			last_tts_message.voice,
+		)
+		.await?;
+	} else {
+		reply(ctx, "You haven't used TTS in this server yet.").await?;
+	}
+
+	Ok(())
+}
+}
+
+// Utility Functions
+
+/// Validates a TTS message, splits it into fragments, and queues them up.
+///
+/// If `voice` is [`None`], the plugin's default voice is used.
+///
+/// Returns whether the message was queued. If it wasn't, the user has already
+/// been told why.
+async fn queue_tts(
+	ctx: PoiseContext<'_>,
+	guild: &Guild,
+	message: &str,
+	voice: Option<&str>,
+) -> Result<bool, Error> {
+	let message_length = message.chars().count();
+	if message_length == 0 {
+		reply(ctx, "There's nothing to say.").await?;
+		return Ok(false);
+	}
+	if message_length > MAX_MESSAGE_LENGTH {
+		reply(
+			ctx,
+			format!(
+				"TTS messages can be at most {} characters long. (yours is {})",
+				MAX_MESSAGE_LENGTH, message_length
+			),
+		)
+		.await?;
+		return Ok(false);
+	}
+
+	if violates_profanity_filter(ctx.data(), guild.id, message)? {
+		reply(ctx, FILTERED_MESSAGE).await?;
+		return Ok(false);
+	}
+
+	let fragments = split_into_fragments(message, MAX_FRAGMENT_LENGTH);
+	if fragments.len() > MAX_FRAGMENT_COUNT {
+		reply(
+			ctx,
+			format!(
+				"TTS messages can be split into at most {} fragments. Try using fewer long words.",
+				MAX_FRAGMENT_COUNT
+			),
+		)
+		.await?;
+		return Ok(false);
+	}
+
+	if !ensure_voice_connection(ctx, guild).await? {
+		return Ok(false);
+	}
+
+	let lavalink = &ctx.data().lavalink;
+
+	// Load every fragment before queueing any of them, so that a failure doesn't
+	// leave half of the message queued
+	let mut queueable_tracks = Vec::with_capacity(fragments.len());
+	for fragment in &fragments {
+		let query_result = lavalink
+			.get_tracks(build_tts_query(fragment, voice))
+			.await?;
+		if let Some(mut track) = query_result.tracks.into_iter().next() {
+			// The plugin doesn't give the tracks meaningful titles
+			if let Some(info) = track.info.as_mut() {
+				info.title = fragment.clone();
+			}
+			queueable_tracks.push(track);
+		} else {
+			reply(ctx, "Unable to load the TTS message.").await?;
+			return Ok(false);
+		}
+	}
+
+	// Queue
+	for track in &queueable_tracks {
+		let mut queueable = lavalink.play(guild.id.0, track.clone());
+		queueable.requester(ctx.author().id.0);
+		if let Err(e) = queueable.queue().await {
+			reply(ctx, "Failed to queue up the TTS message.").await?;
+			eprintln!("Failed to queue up the TTS message: {}", e);
+			return Ok(false);
+		};
+		ctx.data().queue_numbering.record_added(guild.id, 1);
+	}
+
+	reply(
+		ctx,
+		if queueable_tracks.len() == 1 {
+			format!(
+				"Added TTS to queue: {}",
+				chop_str(message, MAX_SINGLE_ENTRY_LENGTH)
+			)
+		} else {
+			format!(
+				"Added TTS to queue in {} parts: {}",
+				queueable_tracks.len(),
+				chop_str(message, MAX_SINGLE_ENTRY_LENGTH)
+			)
+		},
+	)
+	.await?;
+
+	Ok(true)
+}
+
+/// Builds the Lavalink query for a TTS fragment, selecting the voice if one is
+/// specified.
+fn build_tts_query(fragment: &str, voice: Option<&str>) -> String {
+	match voice {
+		Some(voice) => format!("{}{}:{}", TTS_QUERY_PREFIX, voice, fragment),
+		None => format!("{}{}", TTS_QUERY_PREFIX, fragment),
+	}
+}
+
+/// Splits a message into fragments of at most `max_length` characters each,
+/// breaking on word boundaries wherever possible.
+///
+/// Words that are too long to fit in a fragment on their own are broken up.
+fn split_into_fragments(message: &str, max_length: usize) -> Vec<String> {
+	let mut fragments = Vec::new();
+	let mut fragment = String::new();
+	let mut fragment_length = 0;
+	for word in message.split_whitespace() {
+		let mut chars = word.chars().collect::<Vec<_>>();
+		while !chars.is_empty() {
+			let separator_length = usize::from(fragment_length > 0);
+			if fragment_length + separator_length + chars.len() <= max_length {
+				if fragment_length > 0 {
+					fragment.push(' ');
+				}
+				fragment_length += separator_length + chars.len();
+				fragment.extend(chars.drain(..));
+			} else if fragment_length > 0 {
+				// Start a new fragment and try again
+				fragments.push(fragment);
+				fragment = String::new();
+				fragment_length = 0;
+			} else {
+				// The word doesn't fit in an empty fragment, so it has to be split
+				fragments.push(chars.drain(..max_length).collect());
+			}
+		}
+	}
+	if !fragment.is_empty() {
+		fragments.push(fragment);
+	}
+
+	fragments
+}
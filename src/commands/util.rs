@@ -1,19 +1,44 @@
 // Uses
+use std::{
+	sync::PoisonError,
+	time::{Duration, Instant},
+};
+
 use anyhow::Context;
+use lavalink_rs::LavalinkClient;
 use poise::{
 	builtins::{help as poise_help, register_application_commands, HelpResponseMode},
-	command,
-	serenity::model::{gateway::Activity, misc::Mentionable},
+	serenity::{client::bridge::gateway::ShardId, model::misc::Mentionable},
 };
+use tokio::time::timeout;
 
 use crate::{
-	constants::{CREATED_DATE, CREATOR_ID, PREFIX, PROGRAM_COMMIT, PROGRAM_VERSION, SOURCE_LINK},
-	util::{reply, reply_embed},
+	constants::{
+		COMMIT_NUMBER_CHOP_LENGTH,
+		CREATED_DATE,
+		CREATOR_ID,
+		MAIN_COLOUR,
+		PREFIX,
+		PROGRAM_COMMIT,
+		PROGRAM_VERSION,
+		SOURCE_LINK,
+	},
+	db::{clear_saved_status, save_status},
+	util::{display_duration, parse_activity, reply, reply_embed},
 	Error,
 	PoiseContext,
 	PoisePrefixContext,
 };
 
+// Constants
+/// Given to `setstatus` in place of a status type to forget the saved status.
+const CLEAR_STATUS_KEYWORD: &str = "clear";
+/// The longest an embed field's value can be.
+const MAX_FIELD_VALUE: usize = 1024;
+/// How much of a field to leave for the note about entries that didn't fit.
+const CLIPPED_NOTE_RESERVE: usize = 32;
+
+panic_safe! {
 /// Register slash commands in this server or globally.
 ///
 /// Run with no arguments to register globally, run with argument "local" to
@@ -25,8 +50,48 @@ pub async fn register(ctx: PoisePrefixContext<'_>, #[flag] local: bool) -> Resul
 		.with_context(|| "failed to register slash commands".to_owned())?;
 	Ok(())
 }
+}
+
+panic_safe! {
+/// Re-register all slash commands, and report how many were registered.
+///
+/// Run with no arguments to register globally, run with argument "local" to
+/// register in-server. Useful after deploying command changes.
+#[command(
+	prefix_command,
+	owners_only,
+	hide_in_help,
+	category = "Utility",
+	aliases("reload")
+)]
+pub async fn resync(ctx: PoisePrefixContext<'_>, #[flag] local: bool) -> Result<(), Error> {
+	let command_count = ctx.framework.options().application_options.commands.len();
 
+	let start_time = Instant::now();
+	register_application_commands(PoiseContext::Prefix(ctx), !local)
+		.await
+		.with_context(|| "failed to register slash commands".to_owned())?;
+	let elapsed = start_time.elapsed();
+
+	reply_embed(PoiseContext::Prefix(ctx), |e| {
+		e.title("Slash Commands Resynced")
+			.field("Commands:", format!("`{}`", command_count), true)
+			.field("Scope:", if local { "This server" } else { "Global" }, true)
+			.field("Time Taken:", format!("`{}ms`", elapsed.as_millis()), true)
+	})
+	.await?;
+	Ok(())
+}
+}
+
+panic_safe! {
 /// Set the bot status.
+///
+/// The status is saved, so it's restored when Radium restarts. It takes
+/// precedence over any configured startup status or status rotation.
+///
+/// Use `setstatus clear` to forget the saved status, so that the configured
+/// one is used again from the next restart.
 #[command(
 	prefix_command,
 	owners_only,
@@ -38,21 +103,83 @@ pub async fn register(ctx: PoisePrefixContext<'_>, #[flag] local: bool) -> Resul
 pub async fn set_status(
 	ctx: PoisePrefixContext<'_>,
 	r#type: String,
-	#[rest] status: String,
+	#[rest] status: Option<String>,
 ) -> Result<(), Error> {
-	let activity = match r#type.to_lowercase().trim() {
-		"playing" | "play" | "p" => Activity::playing(status), // Playing ...
-		"listening" | "listen" | "l" => Activity::listening(status), // Listening to ...
-		"watching" | "watch" | "w" => Activity::watching(status), // Watching ...
-		"competing" | "compete" | "c" => Activity::competing(status), // Competing in ...
-		_ => return Ok(()),
+	if r#type.trim().eq_ignore_ascii_case(CLEAR_STATUS_KEYWORD) && status.is_none() {
+		let cleared = {
+			let conn = ctx.data.db_pool.get()?;
+			clear_saved_status(&conn)
+				.with_context(|| "failed to clear the saved status from the database")?
+		};
+		reply(
+			PoiseContext::Prefix(ctx),
+			if cleared {
+				"The saved status was cleared. The configured status will be used from the next \
+				 restart."
+			} else {
+				"There's no saved status to clear."
+			},
+		)
+		.await?;
+		return Ok(());
+	}
+
+	let status = status.unwrap_or_default();
+	let activity = if let Some(activity) = parse_activity(r#type.as_str(), status.clone()) {
+		activity
+	} else {
+		reply(
+			PoiseContext::Prefix(ctx),
+			"Unknown status type. It must be one of `playing`, `listening`, `watching`, \
+			 `competing`, or `clear`.",
+		)
+		.await?;
+		return Ok(());
 	};
 
+	// Stop any status rotation so it doesn't replace the new status
+	if let Some(task) = ctx
+		.data
+		.status_rotation_task
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.take()
+	{
+		task.abort();
+	}
 	ctx.discord.set_activity(activity).await;
 
+	{
+		let conn = ctx.data.db_pool.get()?;
+		save_status(
+			&conn,
+			r#type.trim().to_lowercase().as_str(),
+			status.as_str(),
+		)
+		.with_context(|| "failed to save the status to the database")?;
+	}
+
+	reply(PoiseContext::Prefix(ctx), "The status was set and saved.").await?;
+
 	Ok(())
 }
+}
+
+panic_safe! {
+/// Shut Radium down cleanly.
+///
+/// Radium leaves every voice channel it's in before stopping.
+#[command(prefix_command, owners_only, hide_in_help, category = "Utility")]
+pub async fn shutdown(ctx: PoisePrefixContext<'_>) -> Result<(), Error> {
+	reply(PoiseContext::Prefix(ctx), "Shutting down\u{2026}").await?;
+
+	ctx.data.shut_down(ctx.framework.shard_manager()).await;
+
+	Ok(())
+}
+}
 
+panic_safe! {
 /// Get information about available commands. Use `/help help` for more info.
 ///
 /// Calling this command with the name of another command will give you a more
@@ -85,7 +212,85 @@ pub async fn help(
 	.await?;
 	Ok(())
 }
+}
+
+panic_safe! {
+/// Find commands by keyword.
+///
+/// Lists every command whose name, aliases, or description mention the
+/// keyword, grouped by category.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Utility",
+	rename = "commandsearch",
+	aliases("searchcommands", "findcommand")
+)]
+pub async fn command_search(
+	ctx: PoiseContext<'_>,
+	#[rest]
+	#[description = "What to search for."]
+	keyword: String,
+) -> Result<(), Error> {
+	let keyword = keyword.trim().to_lowercase();
+	if keyword.is_empty() {
+		reply(ctx, "You need to specify something to search for.").await?;
+		return Ok(());
+	}
+
+	// Find the matches, keeping the categories in the order the commands are in
+	let mut categories: Vec<(&str, Vec<String>)> = Vec::new();
+	for command_meta in &ctx.framework().options().prefix_options.commands {
+		let command = &command_meta.command;
+		if command.id.hide_in_help {
+			continue;
+		}
+
+		let inline_help = command.id.inline_help.unwrap_or_default();
+		let multiline_help = command
+			.id
+			.multiline_help
+			.map(|multiline_help| multiline_help())
+			.unwrap_or_default();
+		let is_match = command.name.to_lowercase().contains(keyword.as_str())
+			|| command
+				.aliases
+				.iter()
+				.any(|alias| alias.to_lowercase().contains(keyword.as_str()))
+			|| inline_help.to_lowercase().contains(keyword.as_str())
+			|| multiline_help.to_lowercase().contains(keyword.as_str());
+		if !is_match {
+			continue;
+		}
+
+		let entry = format!("`{}{}` - {}", PREFIX, command.name, inline_help);
+		let category = command.id.category.unwrap_or("Other");
+		if let Some((_, entries)) = categories.iter_mut().find(|(c, _)| *c == category) {
+			entries.push(entry);
+		} else {
+			categories.push((category, vec![entry]));
+		}
+	}
+
+	if categories.is_empty() {
+		reply(ctx, "No commands match that.").await?;
+		return Ok(());
+	}
+
+	reply_embed(ctx, |e| {
+		e.title("Matching Commands");
+		for (category, entries) in categories {
+			e.field(category, join_field_entries(&entries), false);
+		}
+		e
+	})
+	.await?;
+
+	Ok(())
+}
+}
 
+panic_safe! {
 /// Get basic information about Radium.
 ///
 /// There isn't much else to say - just use the command.
@@ -108,16 +313,162 @@ pub async fn about(ctx: PoiseContext<'_>) -> Result<(), Error> {
 	.await?;
 	Ok(())
 }
+}
 
-/// Ping Radium.
-///
-/// Perhaps at some point in the future this will display the latency, but for
-/// now it's pretty much useless.
+panic_safe! {
+/// Ping Radium, to see how long it takes to respond.
 ///
-/// It's sticking around for posterity and as a quick way to test if the bot is
-/// operational.
+/// The message latency is how long it takes for Radium's reply to be sent, and
+/// the gateway latency is how long Discord takes to acknowledge that Radium is
+/// still connected. If either is high, Radium is probably lagging.
 #[command(prefix_command, slash_command, category = "Utility")]
 pub async fn ping(ctx: PoiseContext<'_>) -> Result<(), Error> {
-	reply(ctx, "Pong!").await?;
+	let start_time = Instant::now();
+	let reply_handle = reply(ctx, "Pong!").await?;
+	let mut message = if let Some(reply_handle) = reply_handle {
+		reply_handle
+			.message()
+			.await
+			.with_context(|| "failed to get the sent message")?
+	} else {
+		return Ok(());
+	};
+	let message_latency = start_time.elapsed();
+
+	// The latency isn't known until the shard's first heartbeat is acknowledged
+	let gateway_latency = {
+		let shard_manager = ctx.framework().shard_manager();
+		let shard_manager = shard_manager.lock().await;
+		let runners = shard_manager.runners.lock().await;
+		runners
+			.get(&ShardId(ctx.discord().shard_id))
+			.and_then(|runner| runner.latency)
+	};
+
+	message
+		.edit(ctx.discord(), |m| {
+			m.embed(|e| {
+				e.colour(MAIN_COLOUR)
+					.title("Pong!")
+					.field(
+						"Message Latency:",
+						format!("`{}ms`", message_latency.as_millis()),
+						true,
+					)
+					.field(
+						"Gateway Latency:",
+						gateway_latency.map_or_else(
+							|| "Not measured yet".to_owned(),
+							|latency| format!("`{}ms`", latency.as_millis()),
+						),
+						true,
+					)
+			})
+		})
+		.await
+		.with_context(|| "failed to edit the message")?;
+
 	Ok(())
 }
+}
+
+panic_safe! {
+/// Show how long Radium has been running for.
+///
+/// The version it's running is shown too.
+#[command(prefix_command, slash_command, category = "Utility")]
+pub async fn uptime(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	let uptime = ctx.data().started_at.elapsed();
+
+	reply_embed(ctx, |e| {
+		e.title("Uptime")
+			.field(
+				"Running For:",
+				display_duration(uptime.as_millis() as u64),
+				false,
+			)
+			.field(
+				"Version:",
+				format!(
+					"[`v{}`]({}/commit/{}) (`{}`)",
+					PROGRAM_VERSION,
+					SOURCE_LINK,
+					PROGRAM_COMMIT,
+					&PROGRAM_COMMIT[..COMMIT_NUMBER_CHOP_LENGTH]
+				),
+				false,
+			)
+	})
+	.await?;
+	Ok(())
+}
+}
+
+panic_safe! {
+/// Measure Radium's round-trip latency to its audio server (Lavalink).
+///
+/// If this is high or the server is unreachable, audio problems are likely on
+/// the audio server's end rather than Discord's.
+#[command(
+	prefix_command,
+	slash_command,
+	category = "Utility",
+	rename = "nodelatency"
+)]
+pub async fn node_latency(ctx: PoiseContext<'_>) -> Result<(), Error> {
+	match measure_lavalink_latency(&ctx.data().lavalink).await {
+		Some(latency) => {
+			reply(
+				ctx,
+				format!("Audio server round-trip: `{}ms`", latency.as_millis()),
+			)
+			.await?
+		}
+		None => reply(ctx, "The audio server is unreachable.").await?,
+	};
+	Ok(())
+}
+}
+
+// Utility Functions
+
+/// Times a lightweight request to Lavalink.
+///
+/// Returns [`None`] if the request fails or times out.
+async fn measure_lavalink_latency(lavalink: &LavalinkClient) -> Option<Duration> {
+	const TIMEOUT: Duration = Duration::from_secs(5);
+
+	// Loading an empty query is about as lightweight as a REST call to Lavalink
+	// gets, since there's nothing for it to look up
+	let start_time = Instant::now();
+	match timeout(TIMEOUT, lavalink.get_tracks("")).await {
+		Ok(Ok(_)) => Some(start_time.elapsed()),
+		Ok(Err(_)) | Err(_) => None,
+	}
+}
+
+/// Joins entries into an embed field value, one per line, leaving off the ones
+/// that don't fit.
+fn join_field_entries(entries: &[String]) -> String {
+	let mut value = String::new();
+	for (i, entry) in entries.iter().enumerate() {
+		// Leave room for the note, unless there's nothing after this entry to mention
+		let limit = if i + 1 == entries.len() {
+			MAX_FIELD_VALUE
+		} else {
+			MAX_FIELD_VALUE - CLIPPED_NOTE_RESERVE
+		};
+		if value.len() + entry.len() + 1 > limit {
+			if !value.is_empty() {
+				value.push('\n');
+			}
+			value.push_str(format!("*\u{2026}and {} more*", entries.len() - i).as_str());
+			break;
+		}
+		if !value.is_empty() {
+			value.push('\n');
+		}
+		value.push_str(entry.as_str());
+	}
+	value
+}
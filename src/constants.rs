@@ -20,13 +20,30 @@ pub const COMMIT_NUMBER_CHOP_LENGTH: usize = 8;
 
 // Operational Constants
 pub const VIDEO_SEGMENT_CACHE_SIZE: usize = 2048;
+/// The words rejected by the profanity filter, on top of any configured ones.
+pub const DEFAULT_FILTERED_WORDS: [&str; 12] = [
+	"arsehole",
+	"asshole",
+	"bastard",
+	"bitch",
+	"cock",
+	"cunt",
+	"dick",
+	"fuck",
+	"fucking",
+	"motherfucker",
+	"shit",
+	"whore",
+];
 
 // Utility Constants
 pub const MILLIS_PER_SECOND: u64 = 1000;
 pub const SECONDS_PER_MINUTE: u64 = 60;
 pub const MINUTES_PER_HOUR: u64 = 60;
+pub const HOURS_PER_DAY: u64 = 24;
 pub const MILLIS_PER_MINUTE: u64 = MILLIS_PER_SECOND * SECONDS_PER_MINUTE;
 pub const MILLIS_PER_HOUR: u64 = MILLIS_PER_MINUTE * MINUTES_PER_HOUR;
+pub const MILLIS_PER_DAY: u64 = MILLIS_PER_HOUR * HOURS_PER_DAY;
 pub const SECONDS_PER_HOUR: u64 = SECONDS_PER_MINUTE * MINUTES_PER_HOUR;
 pub const MILLIS_PER_SECOND_F32: f32 = MILLIS_PER_SECOND as f32;
 pub const SECONDS_PER_MINUTE_F32: f32 = SECONDS_PER_MINUTE as f32;
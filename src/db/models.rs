@@ -4,6 +4,109 @@ use std::borrow::Cow;
 use super::schema::*;
 
 // Models
+#[derive(Identifiable, Queryable, Insertable)]
+#[table_name = "blocked_sources"]
+#[primary_key(guild_id, source)]
+pub struct BlockedSource<'a> {
+	pub guild_id: i64,
+	pub source: Cow<'a, str>,
+}
+
+/// The status Radium was last given with `setstatus`. There's only ever one
+/// row, with an `id` of 0.
+#[derive(Identifiable, Queryable, Insertable)]
+#[table_name = "bot_status"]
+#[primary_key(id)]
+pub struct BotStatus<'a> {
+	pub id: i32,
+	pub status_type: Cow<'a, str>,
+	pub status_text: Cow<'a, str>,
+}
+
+#[derive(Identifiable, Queryable, Insertable)]
+#[table_name = "dice_jail_counts"]
+#[primary_key(guild_id, user_id)]
+pub struct DiceJailCount {
+	pub guild_id: i64,
+	pub user_id: i64,
+	pub count: i32,
+}
+
+#[derive(Identifiable, Queryable, Insertable)]
+#[table_name = "guild_settings"]
+#[primary_key(guild_id)]
+pub struct GuildSettings {
+	pub guild_id: i64,
+	pub self_deafen: bool,
+	pub dj_role_id: Option<i64>,
+	pub profanity_filter: bool,
+	pub normalize: bool,
+	pub mute_segments: bool,
+	pub search_results: i32,
+	pub volume: Option<i32>,
+	pub crit_reactions: bool,
+	pub skip_non_music: bool,
+	pub timezone: String, // An IANA timezone name, eg. `America/Toronto`
+	pub compact_now_playing: bool,
+	pub vote_skip_threshold: Option<i32>,
+}
+
+impl GuildSettings {
+	/// The settings for a guild that hasn't changed anything.
+	///
+	/// These must match the column defaults in the migrations.
+	#[must_use]
+	pub fn new(guild_id: i64) -> Self {
+		Self {
+			guild_id,
+			self_deafen: true,
+			dj_role_id: None,
+			profanity_filter: false,
+			normalize: false,
+			mute_segments: false,
+			search_results: 1,
+			volume: None,
+			crit_reactions: true,
+			skip_non_music: true,
+			timezone: "UTC".to_owned(),
+			compact_now_playing: false,
+			vote_skip_threshold: None,
+		}
+	}
+}
+
+/// A user whose commands are ignored in a guild.
+#[derive(Identifiable, Queryable, Insertable)]
+#[table_name = "ignored_users"]
+#[primary_key(guild_id, user_id)]
+pub struct IgnoredUser {
+	pub guild_id: i64,
+	pub user_id: i64,
+}
+
+/// One track in a saved playlist, at its position in the playlist.
+#[derive(Identifiable, Queryable, Insertable)]
+#[table_name = "playlist_tracks"]
+#[primary_key(guild_id, user_id, name, position)]
+pub struct PlaylistTrack<'a> {
+	pub guild_id: i64,
+	pub user_id: i64,
+	pub name: Cow<'a, str>,
+	pub position: i32,
+	pub uri: Cow<'a, str>,
+}
+
+/// The first die of a roll, which is recorded for `luck`. The `id` is assigned
+/// by the database.
+#[derive(Insertable)]
+#[table_name = "roll_history"]
+pub struct RolledDie {
+	pub guild_id: i64,
+	pub user_id: i64,
+	pub die_size: i32,
+	pub die_value: i32,
+}
+
 #[derive(Identifiable, Queryable, Insertable)]
 #[table_name = "saved_rolls"]
 #[primary_key(guild_id, user_id, name)]
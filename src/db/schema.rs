@@ -1,3 +1,257 @@
+table! {
+    /// Representation of the `blocked_sources` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    blocked_sources (guild_id, source) {
+        /// The `guild_id` column of the `blocked_sources` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        guild_id -> BigInt,
+        /// The `source` column of the `blocked_sources` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        source -> Text,
+    }
+}
+
+table! {
+    /// Representation of the `bot_status` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    bot_status (id) {
+        /// The `id` column of the `bot_status` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Integer,
+        /// The `status_type` column of the `bot_status` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status_type -> Text,
+        /// The `status_text` column of the `bot_status` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status_text -> Text,
+    }
+}
+
+table! {
+    /// Representation of the `dice_jail_counts` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    dice_jail_counts (guild_id, user_id) {
+        /// The `guild_id` column of the `dice_jail_counts` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        guild_id -> BigInt,
+        /// The `user_id` column of the `dice_jail_counts` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> BigInt,
+        /// The `count` column of the `dice_jail_counts` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        count -> Integer,
+    }
+}
+
+table! {
+    /// Representation of the `guild_settings` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    guild_settings (guild_id) {
+        /// The `guild_id` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        guild_id -> BigInt,
+        /// The `self_deafen` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        self_deafen -> Bool,
+        /// The `dj_role_id` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Nullable<BigInt>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        dj_role_id -> Nullable<BigInt>,
+        /// The `profanity_filter` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        profanity_filter -> Bool,
+        /// The `normalize` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        normalize -> Bool,
+        /// The `mute_segments` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        mute_segments -> Bool,
+        /// The `search_results` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        search_results -> Integer,
+        /// The `volume` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Integer>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        volume -> Nullable<Integer>,
+        /// The `crit_reactions` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crit_reactions -> Bool,
+        /// The `skip_non_music` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        skip_non_music -> Bool,
+        /// The `timezone` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        timezone -> Text,
+        /// The `compact_now_playing` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        compact_now_playing -> Bool,
+        /// The `vote_skip_threshold` column of the `guild_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Integer>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        vote_skip_threshold -> Nullable<Integer>,
+    }
+}
+
+table! {
+    /// Representation of the `ignored_users` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    ignored_users (guild_id, user_id) {
+        /// The `guild_id` column of the `ignored_users` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        guild_id -> BigInt,
+        /// The `user_id` column of the `ignored_users` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> BigInt,
+    }
+}
+
+table! {
+    /// Representation of the `playlist_tracks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    playlist_tracks (guild_id, user_id, name, position) {
+        /// The `guild_id` column of the `playlist_tracks` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        guild_id -> BigInt,
+        /// The `user_id` column of the `playlist_tracks` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> BigInt,
+        /// The `name` column of the `playlist_tracks` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Text,
+        /// The `position` column of the `playlist_tracks` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        position -> Integer,
+        /// The `uri` column of the `playlist_tracks` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        uri -> Text,
+    }
+}
+
+table! {
+    /// Representation of the `roll_history` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    roll_history (id) {
+        /// The `id` column of the `roll_history` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Integer,
+        /// The `guild_id` column of the `roll_history` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        guild_id -> BigInt,
+        /// The `user_id` column of the `roll_history` table.
+        ///
+        /// Its SQL type is `BigInt`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> BigInt,
+        /// The `die_size` column of the `roll_history` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        die_size -> Integer,
+        /// The `die_value` column of the `roll_history` table.
+        ///
+        /// Its SQL type is `Integer`.
+        ///
+        /// (Automatically generated by Diesel.)
+        die_value -> Integer,
+    }
+}
+
 table! {
     /// Representation of the `saved_rolls` table.
     ///
@@ -29,3 +283,14 @@ table! {
         command -> Text,
     }
 }
+
+allow_tables_to_appear_in_same_query!(
+    blocked_sources,
+    bot_status,
+    dice_jail_counts,
+    guild_settings,
+    ignored_users,
+    playlist_tracks,
+    roll_history,
+    saved_rolls,
+);
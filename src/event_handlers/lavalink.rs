@@ -1,18 +1,46 @@
 // Uses
 use std::{
-	sync::{Arc, Mutex},
+	sync::{Arc, Mutex, PoisonError},
 	time::Duration,
 };
 
+use chrono::Utc;
 use lavalink_rs::{
 	gateway::LavalinkEventHandler,
-	model::{GuildId, PlayerDestroyed, PlayerUpdate, TrackStart, TrackStuck, WebSocketClosed},
+	model::{
+		GuildId,
+		PlayerDestroyed,
+		PlayerUpdate,
+		Track,
+		TrackFinish,
+		TrackStart,
+		TrackStuck,
+		WebSocketClosed,
+	},
 	LavalinkClient,
 };
-use poise::serenity::async_trait;
-use tokio::time::{sleep, Instant};
+use poise::serenity::{
+	async_trait,
+	model::id::{GuildId as SerenityGuildId, UserId},
+};
+use tokio::{
+	spawn,
+	time::{sleep, Instant},
+};
 
-use crate::{constants::MILLIS_PER_SECOND_F32, segments::GuildSegments, DataArc};
+use crate::{
+	commands::{record_played_track, record_previous_track, LoopMode, PlayedTrack},
+	constants::MILLIS_PER_SECOND_F32,
+	db::get_guild_settings,
+	filters::apply_filters,
+	segments::{segments_for_guild, GuildSegments},
+	DataArc,
+};
+
+// Constants
+/// How long a track from a search can have been playing for and still fall back
+/// on the next search result if it gets stuck.
+const SEARCH_FALLBACK_WINDOW: Duration = Duration::from_secs(10);
 
 // The event handler for all Lavalink events
 pub struct LavalinkHandler {
@@ -24,14 +52,172 @@ impl LavalinkEventHandler for LavalinkHandler {
 	// Update the active segments info for new tracks
 	async fn track_start(&self, client: LavalinkClient, event: TrackStart) {
 		let identifier = client
-			.decode_track(event.track)
+			.decode_track(event.track.clone())
 			.await
 			.expect("Unable to decode event track string")
 			.identifier;
 		update_segment_data(&self.data, event.guild_id, Some(identifier));
+
+		// Start the clock on falling back to the next search result, and forget the
+		// alternatives for tracks that have been playing fine
+		{
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let data = data_handle.as_ref().unwrap();
+			if let Some(guild_fallbacks) = data
+				.search_fallbacks
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.get_mut(&SerenityGuildId(event.guild_id.0))
+			{
+				guild_fallbacks.retain(|fallback| {
+					fallback.started_at.map_or(true, |started_at| {
+						started_at.elapsed() <= SEARCH_FALLBACK_WINDOW
+					})
+				});
+				for fallback in guild_fallbacks
+					.iter_mut()
+					.filter(|fallback| fallback.track == event.track)
+				{
+					fallback
+						.started_at
+						.get_or_insert_with(|| Instant::now().into_std());
+				}
+			}
+		}
+
+		// Remember who requested the track, so it keeps its requester if it's looped,
+		// and add it to the guild's play history and the tracks `previous` goes back
+		// through
+		let now_playing_opt = client
+			.nodes()
+			.await
+			.get(&event.guild_id.0)
+			.and_then(|node| {
+				node.now_playing.as_ref().map(|now_playing| {
+					(
+						now_playing.track.clone(),
+						now_playing.requester.map(|requester| UserId(requester.0)),
+					)
+				})
+			});
+		if let Some((track, requester)) = now_playing_opt {
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let data = data_handle.as_ref().unwrap();
+			let guild_id = SerenityGuildId(event.guild_id.0);
+			// Votes to skip the previous track don't count towards this one
+			data.skip_votes
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.remove(&guild_id);
+			if let Some(requester) = requester {
+				data.now_playing_requesters
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.insert(guild_id, requester);
+			}
+			if let Some(info) = &track.info {
+				record_played_track(
+					data,
+					guild_id,
+					PlayedTrack {
+						track: track.track.clone(),
+						title: info.title.clone(),
+						uri: info.uri.clone(),
+						requester,
+						played_at: Utc::now(),
+					},
+				);
+				record_previous_track(data, guild_id, track);
+			}
+		}
+
+		// Tracks normally skip their start segment by being queued with a start time,
+		// but ones that weren't (eg. their segments were cached after they were queued,
+		// or the segment is being muted) have to be handled here instead
+		let start_segment_opt = {
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let segment_data_handle = data_handle
+				.as_ref()
+				.unwrap()
+				.segment_data
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner);
+			segment_data_handle
+				.active_segments
+				.get(&event.guild_id)
+				.and_then(|guild_segments| guild_segments.segments.first())
+				.filter(|segment| segment.is_at_start)
+				.copied()
+		};
+		if let Some(start_segment) = start_segment_opt {
+			let started_from_beginning = client
+				.nodes()
+				.await
+				.get(&event.guild_id.0)
+				.and_then(|node| node.now_playing.as_ref().map(|t| t.start_time == 0))
+				.unwrap_or(false);
+			if started_from_beginning {
+				let data = Arc::clone(
+					self.data
+						.lock()
+						.unwrap_or_else(PoisonError::into_inner)
+						.as_ref()
+						.unwrap(),
+				);
+				let mute_segments = start_segment.is_mute
+					&& data
+						.db_pool
+						.get()
+						.ok()
+						.and_then(|conn| get_guild_settings(&conn, event.guild_id.0 as i64).ok())
+						.map_or(false, |guild_settings| guild_settings.mute_segments);
+				if mute_segments {
+					mute_segment(
+						data,
+						&client,
+						event.guild_id,
+						Duration::from_secs_f32(start_segment.end),
+					)
+					.await;
+				} else {
+					// We discard the potential error because there's nothing to be done about
+					// it here
+					client
+						.seek(event.guild_id, Duration::from_secs_f32(start_segment.end))
+						.await
+						.ok();
+				}
+			}
+		}
+
+		// Re-apply normalization and the active filter for every new track, in case the
+		// filters were lost
+		let (normalize, active_filter) = {
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let data = data_handle.as_ref().unwrap();
+			(
+				data.db_pool
+					.get()
+					.ok()
+					.and_then(|conn| get_guild_settings(&conn, event.guild_id.0 as i64).ok())
+					.map_or(false, |guild_settings| guild_settings.normalize),
+				data.active_filters
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.get(&SerenityGuildId(event.guild_id.0))
+					.copied(),
+			)
+		};
+		if normalize || active_filter.is_some() {
+			// Nothing can be done about a failure here either
+			apply_filters(&client, event.guild_id.0, normalize, active_filter)
+				.await
+				.ok();
+		}
 	}
 
 	// During video playback, check regularly if we're close to a segment to skip
+	// (or mute)
 	async fn player_update(&self, client: LavalinkClient, event: PlayerUpdate) {
 		const UPDATE_DELAY_PERIOD: f32 = 5.0; // Number of seconds between updates
 		const SEEK_DELAY: f32 = 0.085; // The amount of delay that seek operations have before completing
@@ -40,22 +226,56 @@ impl LavalinkEventHandler for LavalinkHandler {
 		let event_start_time = Instant::now();
 
 		let guild_segments_opt = {
-			let data_handle = self.data.lock().unwrap();
-			let segment_data_handle = data_handle.as_ref().unwrap().segment_data.lock().unwrap();
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let data = data_handle.as_ref().unwrap();
+
+			// Remember when the position was last updated, so it can be extrapolated from
+			data.last_player_updates
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.insert(
+					SerenityGuildId(event.guild_id.0),
+					event_start_time.into_std(),
+				);
+
+			let segment_data_handle = data
+				.segment_data
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner);
 			segment_data_handle
 				.active_segments
 				.get(&event.guild_id)
 				.cloned()
 		};
+		// Only bother checking the setting if there's something to mute
+		let mute_segments = guild_segments_opt.as_ref().map_or(false, |guild_segments| {
+			guild_segments
+				.segments
+				.iter()
+				.any(|segment| segment.is_mute)
+		}) && {
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let data = data_handle.as_ref().unwrap();
+			data.db_pool
+				.get()
+				.ok()
+				.and_then(|conn| get_guild_settings(&conn, event.guild_id.0 as i64).ok())
+				.map_or(false, |guild_settings| guild_settings.mute_segments)
+		};
 		let mut change_guild_track = None;
 		'seek_block: {
 			if let Some(guild_segments) = guild_segments_opt {
 				let position_f32 = event.state.position as f32 / MILLIS_PER_SECOND_F32;
 				let mut next_segment_opt = None;
 				for segment in &guild_segments.segments {
-					// Segments at the start and end are handled by Lavalink itself -
-					// don't touch them. We also skip segments that have already passed.
-					if segment.is_at_start || segment.end - SEGMENT_END_EPSILON <= position_f32 {
+					// Segments at the start and end are handled elsewhere - don't
+					// touch them. We also skip segments that have already passed, and
+					// segments being muted that have already started, since they're already
+					// muted.
+					if segment.is_at_start
+						|| segment.end - SEGMENT_END_EPSILON <= position_f32
+						|| (mute_segments && segment.is_mute && segment.start <= position_f32)
+					{
 						continue;
 					}
 					next_segment_opt = Some(segment);
@@ -95,13 +315,31 @@ impl LavalinkEventHandler for LavalinkHandler {
 							sleep(Duration::from_secs_f32(time_until_segment - SEEK_DELAY)).await;
 						}
 
-						// Seek
-						// We discard the potential error because there's nothing to be done about
-						// it here
-						client
-							.seek(event.guild_id, Duration::from_secs_f32(next_segment.end))
-							.await
-							.ok();
+						if mute_segments && next_segment.is_mute {
+							// Mute
+							let data = Arc::clone(
+								self.data
+									.lock()
+									.unwrap_or_else(PoisonError::into_inner)
+									.as_ref()
+									.unwrap(),
+							);
+							mute_segment(
+								data,
+								&client,
+								event.guild_id,
+								Duration::from_secs_f32(next_segment.end - next_segment.start),
+							)
+							.await;
+						} else {
+							// Seek
+							// We discard the potential error because there's nothing to be done
+							// about it here
+							client
+								.seek(event.guild_id, Duration::from_secs_f32(next_segment.end))
+								.await
+								.ok();
+						}
 					}
 				}
 			}
@@ -115,10 +353,85 @@ impl LavalinkEventHandler for LavalinkHandler {
 		}
 	}
 
+	// Queue finished tracks up again if the guild is looping
+	async fn track_finish(&self, client: LavalinkClient, event: TrackFinish) {
+		// A search result that couldn't be loaded is replaced by the next result
+		if event.reason == "LOAD_FAILED" {
+			queue_search_fallback(&self.data, &client, event.guild_id, event.track.as_str()).await;
+			return;
+		}
+		// Skipped tracks aren't looped
+		if event.reason != "FINISHED" {
+			return;
+		}
+
+		let guild_id = SerenityGuildId(event.guild_id.0);
+		let (loop_mode, requester) = {
+			let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+			let data = data_handle.as_ref().unwrap();
+			let playback_modifiers = data
+				.playback_modifiers
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner);
+			(
+				playback_modifiers
+					.get(&guild_id)
+					.map_or(LoopMode::Off, |modifiers| modifiers.loop_mode),
+				data.now_playing_requesters
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.get(&guild_id)
+					.copied(),
+			)
+		};
+		if loop_mode == LoopMode::Off {
+			return;
+		}
+
+		let info = if let Ok(info) = client.decode_track(event.track.clone()).await {
+			info
+		} else {
+			return;
+		};
+		let track = Track {
+			track: event.track,
+			info: Some(info),
+		};
+		let mut queueable = client.play(event.guild_id, track);
+		if let Some(requester) = requester {
+			queueable.requester(requester.0);
+		}
+		if queueable.queue().await.is_err() {
+			return;
+		}
+
+		match loop_mode {
+			// Move the track back to the front of the queue so it plays next - since it
+			// left the front and came back, the queue numbering doesn't change
+			LoopMode::Track => {
+				if let Some(mut node) = client.nodes().await.get_mut(&event.guild_id.0) {
+					if let Some(queued_track) = node.queue.pop() {
+						node.queue.insert(0, queued_track);
+					}
+				}
+			}
+			// The track is at the end of the queue now, so it's numbered like any other
+			// newly-queued track
+			LoopMode::Queue => {
+				let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+				let data = data_handle.as_ref().unwrap();
+				data.queue_numbering.record_added(guild_id, 1);
+			}
+			LoopMode::Off => {}
+		}
+	}
+
 	// Automatically skip if a track is stuck
 	async fn track_stuck(&self, client: LavalinkClient, event: TrackStuck) {
 		println!("A currently-playing track is stuck. Skipping.");
 		dbg!(&event);
+		// A search result that gets stuck right away is replaced by the next result
+		queue_search_fallback(&self.data, &client, event.guild_id, event.track.as_str()).await;
 		client.skip(event.guild_id).await;
 	}
 
@@ -126,11 +439,119 @@ impl LavalinkEventHandler for LavalinkHandler {
 		dbg!(&event);
 	}
 
+	// Clean up the guild's playback state so it doesn't linger after the player is
+	// gone
 	async fn player_destroyed(&self, _client: LavalinkClient, event: PlayerDestroyed) {
 		dbg!(&event);
+		let data_handle = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+		if let Some(data) = data_handle.as_ref() {
+			data.clear_guild_playback_state(SerenityGuildId(event.guild_id.0));
+		}
+	}
+}
+
+/// Queues the next result for a search whose track couldn't be played, so that
+/// it plays right after the current track.
+///
+/// Tracks that have been playing for a while don't fall back, since whatever
+/// went wrong probably isn't a problem with the track itself.
+async fn queue_search_fallback(
+	data: &Arc<Mutex<Option<DataArc>>>,
+	client: &LavalinkClient,
+	guild_id: GuildId,
+	failed_track: &str,
+) {
+	let serenity_guild_id = SerenityGuildId(guild_id.0);
+
+	let (alternative, requester) = {
+		let data_handle = data.lock().unwrap_or_else(PoisonError::into_inner);
+		let data = data_handle.as_ref().unwrap();
+		let mut search_fallbacks = data
+			.search_fallbacks
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let guild_fallbacks =
+			if let Some(guild_fallbacks) = search_fallbacks.get_mut(&serenity_guild_id) {
+				guild_fallbacks
+			} else {
+				return;
+			};
+		let mut fallback = if let Some(index) = guild_fallbacks.iter().position(|fallback| {
+			fallback.track == failed_track
+				&& fallback.started_at.map_or(true, |started_at| {
+					started_at.elapsed() <= SEARCH_FALLBACK_WINDOW
+				})
+		}) {
+			guild_fallbacks.remove(index).unwrap()
+		} else {
+			return;
+		};
+		let alternative = if let Some(alternative) = fallback.alternatives.pop_front() {
+			alternative
+		} else {
+			return;
+		};
+
+		// The alternative can fall back on the rest in turn
+		let requester = fallback.requester;
+		if !fallback.alternatives.is_empty() {
+			fallback.track = alternative.track.clone();
+			fallback.started_at = None;
+			guild_fallbacks.push_back(fallback);
+		}
+
+		(alternative, requester)
+	};
+
+	let mut queueable = client.play(guild_id, alternative);
+	queueable.requester(requester.0);
+	if queueable.queue().await.is_err() {
+		return;
+	}
+	{
+		let data_handle = data.lock().unwrap_or_else(PoisonError::into_inner);
+		let data = data_handle.as_ref().unwrap();
+		data.queue_numbering.record_added(serenity_guild_id, 1);
+	}
+
+	// Move it up from the end of the queue, to right after the current track
+	if let Some(mut node) = client.nodes().await.get_mut(&guild_id.0) {
+		if node.queue.len() > 2 {
+			if let Some(queued_track) = node.queue.pop() {
+				node.queue.insert(1, queued_track);
+			}
+		}
 	}
 }
 
+/// Mutes playback for the length of a segment.
+///
+/// The volume is restored once the segment is over, in a separate task so that
+/// other events aren't held up.
+async fn mute_segment(
+	data: DataArc,
+	client: &LavalinkClient,
+	guild_id: GuildId,
+	segment_length: Duration,
+) {
+	client.volume(guild_id, 0).await.ok();
+	let client = client.clone();
+	spawn(async move {
+		sleep(segment_length).await;
+		let modifiers = data
+			.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&SerenityGuildId(guild_id.0))
+			.cloned()
+			.unwrap_or_default();
+		// Stay silent if the guild muted playback in the meantime
+		if !modifiers.muted {
+			client.volume(guild_id, modifiers.volume).await.ok();
+		}
+	});
+}
+
 /// Updates the active track for a guild.
 ///
 /// If `new_track` is [`None`], the active track is unset.
@@ -142,19 +563,35 @@ fn update_segment_data(
 	guild_id: GuildId,
 	new_track: Option<String>,
 ) {
+	let data_handle = data.lock().unwrap_or_else(PoisonError::into_inner);
+	let data = data_handle.as_ref().unwrap();
+
+	// Look up which segments the guild wants skipped before taking the lock
+	let skip_non_music = new_track.is_some()
+		&& data
+			.db_pool
+			.get()
+			.ok()
+			.and_then(|conn| get_guild_settings(&conn, guild_id.0 as i64).ok())
+			.map_or(true, |guild_settings| guild_settings.skip_non_music);
+
 	// Acquire a lock for the segment data
-	let data_handle = data.lock().unwrap();
-	let mut segment_data_handle = data_handle.as_ref().unwrap().segment_data.lock().unwrap();
+	let mut segment_data_handle = data
+		.segment_data
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner);
 
 	// Make the change
 	let mut successfully_set_new_track = false;
 	if let Some(new_track_name) = new_track {
 		// Get the cached segment data if it exists (if it doesn't, the active_segments
 		// entry will be removed)
-		if let Some(Some(new_segments)) = segment_data_handle
+		if let Some(new_segments) = segment_data_handle
 			.cached_segments
 			.get(&new_track_name)
 			.cloned()
+			.flatten()
+			.and_then(|segments| segments_for_guild(segments, skip_non_music))
 		{
 			segment_data_handle.active_segments.insert(
 				guild_id,
@@ -0,0 +1,149 @@
+// Uses
+use lavalink_rs::{
+	model::{Band, Filters, Timescale},
+	LavalinkClient,
+};
+
+use crate::Error;
+
+// Constants
+/// The overall volume multiplier used while normalization is on, which leaves
+/// some headroom so that the loudest tracks don't clip.
+const NORMALIZATION_VOLUME: f64 = 0.8;
+/// The equalizer gains used while normalization is on, as `(band, gain)`.
+///
+/// The lowest bands are where most of a track's loudness tends to come from, so
+/// they're pulled down the most.
+const NORMALIZATION_BANDS: [(i64, f64); 4] = [(0, -0.1), (1, -0.075), (2, -0.05), (3, -0.025)];
+/// The number of equalizer bands Lavalink has.
+const BAND_COUNT: usize = 15;
+const BASS_BOOST_BANDS: [(i64, f64); 4] = [(0, 0.25), (1, 0.2), (2, 0.15), (3, 0.05)];
+/// The playback rate for `nightcore`, which speeds up and pitches up tracks
+/// together.
+const NIGHTCORE_RATE: f64 = 1.25;
+/// The playback rate for `vaporwave`, which slows down and pitches down tracks
+/// together.
+const VAPORWAVE_RATE: f64 = 0.8;
+const VAPORWAVE_BANDS: [(i64, f64); 2] = [(0, 0.3), (1, 0.3)];
+
+// Definitions
+
+/// An effect that can be put on the audio with the `filter` command.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FilterKind {
+	BassBoost,
+	Nightcore,
+	Vaporwave,
+}
+
+impl FilterKind {
+	pub const ALL: [Self; 3] = [Self::BassBoost, Self::Nightcore, Self::Vaporwave];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::BassBoost => "bassboost",
+			Self::Nightcore => "nightcore",
+			Self::Vaporwave => "vaporwave",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL
+			.iter()
+			.copied()
+			.find(|filter| filter.name().eq_ignore_ascii_case(name))
+	}
+
+	/// The equalizer gains for the filter, as `(band, gain)`.
+	fn bands(self) -> &'static [(i64, f64)] {
+		match self {
+			Self::BassBoost => &BASS_BOOST_BANDS,
+			Self::Nightcore => &[],
+			Self::Vaporwave => &VAPORWAVE_BANDS,
+		}
+	}
+
+	fn timescale(self) -> Option<Timescale> {
+		let rate = match self {
+			Self::BassBoost => return None,
+			Self::Nightcore => NIGHTCORE_RATE,
+			Self::Vaporwave => VAPORWAVE_RATE,
+		};
+		Some(Timescale {
+			rate: Some(rate),
+			..Timescale::default()
+		})
+	}
+}
+
+// Functions
+
+/// Applies a guild's audio filters to its player, replacing any that were
+/// already set.
+///
+/// Normalization and the chosen filter can both be on at once, in which case
+/// their equalizer gains are added together.
+///
+/// This relies on the Lavalink server supporting filters. (Lavalink 3.4+)
+pub async fn apply_filters(
+	lavalink: &LavalinkClient,
+	guild_id: u64,
+	normalize: bool,
+	filter: Option<FilterKind>,
+) -> Result<(), Error> {
+	let mut filters = if normalize {
+		normalization_filters()
+	} else {
+		Filters::default()
+	};
+	if let Some(filter) = filter {
+		filters.equalizer = combine_bands(filters.equalizer.as_deref(), filter.bands());
+		filters.timescale = filter.timescale();
+	}
+	lavalink.filters(guild_id, filters).await?;
+	Ok(())
+}
+
+/// The filters used to even out the volume between tracks.
+///
+/// Lavalink doesn't have a true loudness normalizer or limiter, so this
+/// approximates one by turning everything down and taming the bass-heavy low
+/// end, which is usually what makes one track much louder than another.
+fn normalization_filters() -> Filters {
+	Filters {
+		volume: Some(NORMALIZATION_VOLUME),
+		equalizer: Some(
+			NORMALIZATION_BANDS
+				.iter()
+				.map(|&(band, gain)| Band { band, gain })
+				.collect(),
+		),
+		..Filters::default()
+	}
+}
+
+/// Adds the gains of `extra_bands` on top of `bands`.
+///
+/// Returns [`None`] if there are no gains at all, so the equalizer is left off.
+fn combine_bands(bands: Option<&[Band]>, extra_bands: &[(i64, f64)]) -> Option<Vec<Band>> {
+	let mut gains = [0.0; BAND_COUNT];
+	let all_bands = bands
+		.unwrap_or_default()
+		.iter()
+		.map(|band| (band.band, band.gain))
+		.chain(extra_bands.iter().copied());
+	for (band, gain) in all_bands {
+		gains[band as usize] += gain;
+	}
+
+	let combined = gains
+		.iter()
+		.enumerate()
+		.filter(|(_, gain)| **gain != 0.0)
+		.map(|(band, &gain)| Band {
+			band: band as i64,
+			gain,
+		})
+		.collect::<Vec<_>>();
+	(!combined.is_empty()).then(|| combined)
+}
@@ -0,0 +1,107 @@
+// Uses
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::{Client as HttpClient, Error as HttpError, StatusCode};
+use serde::Deserialize;
+use url::Url;
+
+// Constants
+/// The lyrics API, which takes the artist and title as the last two path
+/// segments.
+const LYRICS_API_URL: &str = "https://api.lyrics.ovh/v1/";
+/// The API puts a heading line in front of some lyrics, in French.
+const LYRICS_HEADING_PREFIX: &str = "Paroles de la chanson";
+/// Suffixes that are put on channel names, but aren't part of the artist's
+/// name.
+const CHANNEL_NAME_SUFFIXES: [&str; 3] = [" - Topic", "VEVO", " Official"];
+
+// Definitions
+#[derive(Debug, Deserialize)]
+struct LyricsResponse {
+	lyrics: String,
+}
+
+// Functions
+/// Works out the artist and song title to search for from a track's title and
+/// author.
+///
+/// Video titles are often of the form `Artist - Title (Official Video)`, and
+/// the author is usually the channel that uploaded it, so both are tidied up.
+pub fn guess_song(title: &str, author: &str) -> (String, String) {
+	lazy_static! {
+		static ref ANNOTATION_REGEX: Regex = Regex::new(r"\s*(\([^)]*\)|\[[^\]]*\])").unwrap();
+	}
+
+	let title = ANNOTATION_REGEX.replace_all(title, "");
+	if let Some((artist, title)) = title.split_once(" - ") {
+		return (artist.trim().to_owned(), title.trim().to_owned());
+	}
+
+	let artist = CHANNEL_NAME_SUFFIXES
+		.iter()
+		.fold(author.trim(), |artist, suffix| {
+			artist.strip_suffix(suffix).unwrap_or(artist)
+		});
+	(artist.trim().to_owned(), title.trim().to_owned())
+}
+
+/// Fetches the lyrics for a song.
+///
+/// Returns [`None`] if the API doesn't have lyrics for it.
+pub async fn fetch_lyrics(
+	client: &HttpClient,
+	artist: &str,
+	title: &str,
+) -> Result<Option<String>, HttpError> {
+	let mut url = Url::parse(LYRICS_API_URL).unwrap();
+	url.path_segments_mut()
+		.unwrap()
+		.pop_if_empty()
+		.push(artist)
+		.push(title);
+
+	let response = client.get(url).send().await?;
+	if response.status() == StatusCode::NOT_FOUND {
+		return Ok(None);
+	}
+	let lyrics = response
+		.error_for_status()?
+		.json::<LyricsResponse>()
+		.await?
+		.lyrics
+		.replace("\r\n", "\n");
+
+	let lyrics = if lyrics.starts_with(LYRICS_HEADING_PREFIX) {
+		lyrics.split_once('\n').map_or("", |(_, rest)| rest)
+	} else {
+		lyrics.as_str()
+	}
+	.trim();
+
+	Ok((!lyrics.is_empty()).then(|| lyrics.to_owned()))
+}
+
+/// Strips section labels like `[Chorus]` out of lyrics.
+///
+/// Lines that only held labels are removed entirely, but blank lines between
+/// verses are kept.
+pub fn strip_section_labels(lyrics: &str) -> String {
+	lazy_static! {
+		static ref SECTION_LABEL_REGEX: Regex = Regex::new(r"\[[^\]]*\]").unwrap();
+	}
+
+	lyrics
+		.lines()
+		.filter_map(|line| {
+			let stripped = SECTION_LABEL_REGEX.replace_all(line, "");
+			if stripped.trim().is_empty() && !line.trim().is_empty() {
+				None
+			} else {
+				Some(stripped.trim_end().to_owned())
+			}
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+		.trim()
+		.to_owned()
+}
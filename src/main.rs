@@ -48,54 +48,98 @@ mod commands;
 mod constants;
 mod db;
 mod event_handlers;
+mod filters;
+mod lyrics;
+mod queue_numbering;
 mod segments;
 mod util;
 
 // Uses
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{HashMap, HashSet, VecDeque},
 	env::var,
 	error,
-	sync::{Arc, Mutex},
-	time::Duration,
+	panic::{set_hook, take_hook},
+	sync::{Arc, Mutex, PoisonError},
+	time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use diesel::{
-	r2d2::{ConnectionManager, Pool},
-	SqliteConnection,
-};
 use dotenv::dotenv;
-use lavalink_rs::LavalinkClient;
+use lavalink_rs::{
+	model::{GuildId as LavalinkGuildId, Track},
+	LavalinkClient,
+};
 use poise::{
 	builtins::on_error,
-	serenity::{self, client::parse_token, http::Http, model::id::GuildId},
+	serenity::{
+		self,
+		client::{bridge::gateway::ShardManager, parse_token},
+		http::Http,
+		model::id::{GuildId, UserId},
+	},
 	EditTracker,
 	Framework,
 	FrameworkOptions,
 	PrefixFrameworkOptions,
 };
+use reqwest::Client as HttpClient;
 use songbird::{SerenityInit, Songbird};
 use sponsor_block::Client as SponsorBlockClient;
+#[cfg(unix)]
+use tokio::{
+	select,
+	signal::unix::{signal as unix_signal, SignalKind},
+};
+use tokio::{signal::ctrl_c, spawn, sync::Mutex as AsyncMutex, task::JoinHandle};
 use yansi::Paint;
 
 use crate::{
 	commands::*,
-	constants::{COMMIT_NUMBER_CHOP_LENGTH, HEADER_STYLE, PREFIX, PROGRAM_COMMIT, PROGRAM_VERSION},
-	db::init as database_init,
+	constants::{
+		COMMIT_NUMBER_CHOP_LENGTH,
+		DEFAULT_FILTERED_WORDS,
+		ERROR_STYLE,
+		HEADER_STYLE,
+		PREFIX,
+		PROGRAM_COMMIT,
+		PROGRAM_VERSION,
+	},
+	db::{get_saved_status, init as database_init, DbPool},
 	event_handlers::{LavalinkHandler, SerenityHandler},
+	filters::FilterKind,
+	queue_numbering::QueueNumbering,
 	segments::SegmentData,
+	util::{parse_activity, rotate_activities},
 };
 
 // Runtime Constants
 const DISCORD_TOKEN_VAR: &str = "DISCORD_TOKEN";
 const DATABASE_URL_VAR: &str = "DATABASE_URL";
+#[cfg(feature = "sqlite")]
 const DATABASE_URL_DEFAULT: &str = "db.sqlite";
 const LAVALINK_HOST_VAR: &str = "LAVALINK_HOST";
 const LAVALINK_PASSWORD_VAR: &str = "LAVALINK_PASSWORD";
 const LAVALINK_HOST_DEFAULT: &str = "127.0.0.1";
 const SPONSOR_BLOCK_USER_ID_VAR: &str = "SPONSOR_BLOCK_USER_ID";
 const DISABLE_CLI_COLOURS_VAR: &str = "DISABLE_CLI_COLOURS";
+const EXTRA_OWNERS_VAR: &str = "EXTRA_OWNERS";
+const EXTRA_OWNERS_SEPARATOR: char = ',';
+const FILTERED_WORDS_VAR: &str = "FILTERED_WORDS";
+const FILTERED_WORDS_SEPARATOR: char = ',';
+const STATUS_TYPE_VAR: &str = "STATUS_TYPE";
+const STATUS_TEXT_VAR: &str = "STATUS_TEXT";
+const STATUS_ROTATION_VAR: &str = "STATUS_ROTATION";
+const STATUS_ROTATION_INTERVAL_VAR: &str = "STATUS_ROTATION_INTERVAL";
+const STATUS_ROTATION_INTERVAL_DEFAULT: u64 = 300;
+const STATUS_ROTATION_INTERVAL_MINIMUM: u64 = 15; // Discord rate-limits presence updates
+const STATUS_ROTATION_SEPARATOR: char = '|';
+const STATUS_TYPE_SEPARATOR: char = ':';
+/// Extra prefix command aliases, as `alias=command` pairs, eg.
+/// `bangers=play,nope=skip`.
+const COMMAND_ALIASES_VAR: &str = "COMMAND_ALIASES";
+const COMMAND_ALIASES_SEPARATOR: char = ',';
+const COMMAND_ALIAS_SEPARATOR: char = '=';
 
 // Definitions
 pub type DataArc = Arc<Data>;
@@ -105,12 +149,130 @@ pub type PoisePrefixContext<'a> = poise::PrefixContext<'a, DataArc, Error>;
 pub type SerenityContext = serenity::client::Context;
 
 pub struct Data {
-	db_pool: Pool<ConnectionManager<SqliteConnection>>,
+	/// When Radium started, for `uptime`.
+	started_at: Instant,
+	db_pool: DbPool,
 	songbird: Arc<Songbird>,
 	lavalink: LavalinkClient,
 	sponsor_block: SponsorBlockClient,
-	queued_count: Mutex<HashMap<GuildId, usize>>,
+	/// For requests to other APIs, such as for lyrics.
+	http_client: HttpClient,
+	queue_numbering: QueueNumbering,
 	segment_data: Mutex<SegmentData>,
+	playback_modifiers: Mutex<HashMap<GuildId, PlaybackModifiers>>,
+	last_skips: Mutex<HashMap<GuildId, (UserId, Instant)>>,
+	/// Who has voted to skip the current track in each guild.
+	skip_votes: Mutex<HashMap<GuildId, HashSet<UserId>>>,
+	last_player_updates: Mutex<HashMap<GuildId, Instant>>,
+	last_segment_refreshes: Mutex<HashMap<GuildId, Instant>>,
+	/// Who requested the track that's playing in each guild, so that looped
+	/// tracks keep their requester.
+	now_playing_requesters: Mutex<HashMap<GuildId, UserId>>,
+	/// The guilds where playback is paused.
+	paused_guilds: Mutex<HashSet<GuildId>>,
+	scheduled_plays: Mutex<HashMap<GuildId, Vec<ScheduledPlay>>>,
+	/// The filter from `filter` that's on in each guild.
+	active_filters: Mutex<HashMap<GuildId, FilterKind>>,
+	/// The alternatives for recent searches, oldest first, in case their tracks
+	/// don't play.
+	search_fallbacks: Mutex<HashMap<GuildId, VecDeque<SearchFallback>>>,
+	/// The most recently played tracks in each guild, newest first.
+	///
+	/// This outlives the guild's player, so it isn't cleared with the rest of
+	/// the playback state.
+	play_history: Mutex<HashMap<GuildId, VecDeque<PlayedTrack>>>,
+	/// The most recently started tracks in each guild, newest first, for
+	/// `previous` to go back through.
+	///
+	/// Unlike the play history, this is cleared along with the queue.
+	previous_tracks: Mutex<HashMap<GuildId, VecDeque<Track>>>,
+	last_tts_messages: Mutex<HashMap<(GuildId, UserId), TtsMessage>>,
+	last_dice_jails: Mutex<HashMap<UserId, Instant>>,
+	parsed_roll_cache: Mutex<ParsedRollCache>,
+	filtered_words: Vec<String>,
+	status_rotation_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Data {
+	/// Forgets all of the playback state kept for a guild, for when its player
+	/// is gone.
+	///
+	/// Things that aren't tied to the player (eg. the last TTS messages) are
+	/// kept.
+	pub fn clear_guild_playback_state(&self, guild_id: GuildId) {
+		self.queue_numbering.forget(guild_id);
+		self.playback_modifiers
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.last_skips
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.skip_votes
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.last_player_updates
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.now_playing_requesters
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.paused_guilds
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.active_filters
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		self.search_fallbacks
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+		// Scheduled tracks were meant for the player that's gone, so they're cancelled
+		for scheduled_play in self
+			.scheduled_plays
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id)
+			.unwrap_or_default()
+		{
+			scheduled_play.task.abort();
+		}
+		self.segment_data
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.active_segments
+			.remove(&LavalinkGuildId(guild_id.0));
+	}
+
+	/// Leaves every voice channel and shuts the bot down cleanly.
+	///
+	/// This is used both by the `shutdown` command and when the process is
+	/// asked to stop.
+	pub async fn shut_down(&self, shard_manager: Arc<AsyncMutex<ShardManager>>) {
+		// Every guild with a player is in a voice channel
+		let guild_ids = self
+			.lavalink
+			.nodes()
+			.await
+			.iter()
+			.map(|node| *node.key())
+			.collect::<Vec<_>>();
+		for guild_id in guild_ids {
+			// There's nothing to be done about failures here, since Radium is on its way
+			// out regardless
+			self.songbird.remove(guild_id).await.ok();
+			self.lavalink.destroy(guild_id).await.ok();
+			self.clear_guild_playback_state(GuildId(guild_id));
+		}
+
+		shard_manager.lock().await.shutdown_all().await;
+	}
 }
 
 /// Entry point.
@@ -126,6 +288,19 @@ async fn main() -> Result<(), Error> {
 		Paint::enable_windows_ascii();
 	}
 
+	// Every command and event is handled in its own task, so a panic only ends the
+	// one it happened in, and the shared state is recovered wherever it's locked
+	// Commands also let the user know when they panic (see `catch_panic`), so all
+	// that's left is to make sure the panic stands out in the logs
+	let default_panic_hook = take_hook();
+	set_hook(Box::new(move |panic_info| {
+		eprintln!(
+			"{}",
+			ERROR_STYLE.paint("Something panicked, which is a bug. Radium will keep running.")
+		);
+		default_panic_hook(panic_info);
+	}));
+
 	// Header
 	println!(
 		"{}",
@@ -171,8 +346,102 @@ async fn main() -> Result<(), Error> {
 	println!("{}   {}", HEADER_STYLE.paint("Application ID:"), app_id);
 	println!("{}         {}", HEADER_STYLE.paint("Owner ID:"), owner_id);
 
+	// Additional owners, for when more than one person maintains the bot
 	let mut owners = HashSet::new();
 	owners.insert(owner_id);
+	if let Ok(extra_owners) = var(EXTRA_OWNERS_VAR) {
+		for extra_owner in extra_owners
+			.split(EXTRA_OWNERS_SEPARATOR)
+			.map(str::trim)
+			.filter(|extra_owner| !extra_owner.is_empty())
+		{
+			if let Ok(extra_owner_id) = extra_owner.parse::<u64>() {
+				owners.insert(UserId(extra_owner_id));
+				println!(
+					"{}   {}",
+					HEADER_STYLE.paint("Extra Owner ID:"),
+					extra_owner_id
+				);
+			} else {
+				eprintln!(
+					"{}",
+					ERROR_STYLE.paint(format!(
+						"Invalid user ID `{}` in {}, so it will be skipped.",
+						extra_owner, EXTRA_OWNERS_VAR
+					))
+				);
+			}
+		}
+	}
+
+	// The words rejected by the profanity filter, for guilds that turn it on
+	let mut filtered_words = DEFAULT_FILTERED_WORDS
+		.iter()
+		.map(|word| (*word).to_owned())
+		.collect::<Vec<_>>();
+	if let Ok(extra_filtered_words) = var(FILTERED_WORDS_VAR) {
+		let default_count = filtered_words.len();
+		filtered_words.extend(
+			extra_filtered_words
+				.split(FILTERED_WORDS_SEPARATOR)
+				.map(|word| word.trim().to_lowercase())
+				.filter(|word| !word.is_empty()),
+		);
+		println!(
+			"{}   {}",
+			HEADER_STYLE.paint("Extra Filtered Words:"),
+			filtered_words.len() - default_count
+		);
+	}
+
+	// The activities to show once connected, if any are configured
+	// A rotation takes precedence over a single status
+	let mut startup_activities = if let Ok(status_rotation) = var(STATUS_ROTATION_VAR) {
+		status_rotation
+			.split(STATUS_ROTATION_SEPARATOR)
+			.filter_map(|entry| {
+				let activity = entry.split_once(STATUS_TYPE_SEPARATOR).and_then(
+					|(status_type, status_text)| {
+						parse_activity(status_type, status_text.trim().to_owned())
+					},
+				);
+				if activity.is_none() {
+					eprintln!(
+						"{}",
+						ERROR_STYLE.paint(format!(
+							"Invalid status `{}` in {}, so it will be skipped.",
+							entry.trim(),
+							STATUS_ROTATION_VAR
+						))
+					);
+				}
+				activity
+			})
+			.collect::<Vec<_>>()
+	} else if let (Ok(status_type), Ok(status_text)) = (var(STATUS_TYPE_VAR), var(STATUS_TEXT_VAR))
+	{
+		let activity = parse_activity(status_type.as_str(), status_text);
+		if activity.is_none() {
+			eprintln!(
+				"{}",
+				ERROR_STYLE.paint(format!(
+					"Unknown status type `{}` in {}, so no status will be set.",
+					status_type, STATUS_TYPE_VAR
+				))
+			);
+		}
+		activity.into_iter().collect::<Vec<_>>()
+	} else {
+		Vec::new()
+	};
+	let status_rotation_interval = Duration::from_secs(
+		var(STATUS_ROTATION_INTERVAL_VAR)
+			.ok()
+			.and_then(|interval| interval.parse::<u64>().ok())
+			.unwrap_or(STATUS_ROTATION_INTERVAL_DEFAULT)
+			.max(STATUS_ROTATION_INTERVAL_MINIMUM),
+	);
+
 	let mut options = FrameworkOptions {
 		prefix_options: PrefixFrameworkOptions {
 			prefix: Some(PREFIX.to_owned()),
@@ -182,6 +451,7 @@ async fn main() -> Result<(), Error> {
 			..PrefixFrameworkOptions::default()
 		},
 		on_error: |e, ctx| Box::pin(on_error(e, ctx)),
+		command_check: Some(|ctx| Box::pin(check_not_ignored(ctx))),
 		owners,
 		..FrameworkOptions::default()
 	};
@@ -189,29 +459,116 @@ async fn main() -> Result<(), Error> {
 	// Command Initialization
 	// Utility
 	options.command(register(), |f| f);
+	options.command(resync(), |f| f);
 	options.command(set_status(), |f| f);
+	options.command(shutdown(), |f| f);
 	options.command(help(), |f| f);
+	options.command(command_search(), |f| f);
 	options.command(about(), |f| f);
 	options.command(ping(), |f| f);
+	options.command(uptime(), |f| f);
+	options.command(node_latency(), |f| f);
 	// Playback
 	options.command(join(), |f| f);
 	options.command(leave(), |f| f);
 	options.command(play(), |f| f);
+	options.command(play_in(), |f| f);
+	options.command(cancel_scheduled(), |f| f);
 	options.command(skip(), |f| f);
+	options.command(remove(), |f| f);
+	options.command(move_track(), |f| f);
 	options.command(pause(), |f| f);
 	options.command(resume(), |f| f);
+	options.command(mute(), |f| f);
+	options.command(unmute(), |f| f);
+	options.command(volume(), |f| f);
+	options.command(filter(), |f| f);
 	options.command(seek(), |f| f);
 	options.command(clear(), |f| f);
+	options.command(shuffle(), |f| f);
+	options.command(loop_mode(), |f| f);
 	options.command(now_playing(), |f| f);
+	options.command(lyrics(), |f| f);
+	options.command(refresh_segments(), |f| f);
+	options.command(warm_segments(), |f| f);
 	options.command(queue(), |f| f);
+	options.command(history(), |f| f);
+	options.command(replay(), |f| f);
+	options.command(previous(), |f| f);
+	options.command(play_time(), |f| f);
+	options.command(tts(), |f| f);
+	options.command(tts_repeat(), |f| f);
+	// Playlists
+	options.command(save_playlist(), |f| f);
+	options.command(load_playlist(), |f| f);
+	options.command(delete_playlist(), |f| f);
+	options.command(playlists(), |f| f);
 	// Chance
 	options.command(roll(), |f| f);
+	options.command(adv(), |f| f);
+	options.command(dis(), |f| f);
 	options.command(batch_roll(), |f| f);
+	options.command(roll_vs(), |f| f);
+	options.command(validate_roll(), |f| f);
 	options.command(save_roll(), |f| f);
+	options.command(save_many(), |f| f);
 	options.command(delete_roll(), |f| f);
 	options.command(saved_rolls(), |f| f);
 	options.command(run_roll(), |f| f);
 	options.command(dice_jail(), |f| f);
+	options.command(dice_jail_board(), |f| f);
+	options.command(luck(), |f| f);
+	// Settings
+	options.command(self_deafen(), |f| f);
+	options.command(set_dj_role(), |f| f);
+	options.command(clear_dj_role(), |f| f);
+	options.command(block_source(), |f| f);
+	options.command(ignore(), |f| f);
+	options.command(unignore(), |f| f);
+	options.command(profanity_filter(), |f| f);
+	options.command(normalize(), |f| f);
+	options.command(mute_segments(), |f| f);
+	options.command(skip_non_music(), |f| f);
+	options.command(search_results(), |f| f);
+	options.command(default_volume(), |f| f);
+	options.command(crit_reactions(), |f| f);
+	options.command(compact_now_playing(), |f| f);
+	options.command(vote_skip_threshold(), |f| f);
+	options.command(timezone(), |f| f);
+
+	// Extra aliases, for communities that want their own names for commands
+	// These are added on top of the built-in aliases
+	if let Ok(command_aliases) = var(COMMAND_ALIASES_VAR) {
+		let mut alias_count = 0;
+		for entry in command_aliases
+			.split(COMMAND_ALIASES_SEPARATOR)
+			.map(str::trim)
+			.filter(|entry| !entry.is_empty())
+		{
+			let added =
+				entry
+					.split_once(COMMAND_ALIAS_SEPARATOR)
+					.map_or(false, |(alias, command_name)| {
+						add_command_alias(&mut options, alias.trim(), command_name.trim())
+					});
+			if added {
+				alias_count += 1;
+			} else {
+				eprintln!(
+					"{}",
+					ERROR_STYLE.paint(format!(
+						"Invalid command alias `{}` in {}, so it will be skipped.",
+						entry, COMMAND_ALIASES_VAR
+					))
+				);
+			}
+		}
+		println!(
+			"{}  {}",
+			HEADER_STYLE.paint("Extra Command Aliases:"),
+			alias_count
+		);
+	}
 
 	// Start up the bot
 
@@ -248,28 +605,72 @@ async fn main() -> Result<(), Error> {
 		None => println!("Unknown"),
 	}
 
+	// There's no sensible default location for a Postgres database
+	#[cfg(feature = "sqlite")]
+	let database_url = var(DATABASE_URL_VAR).unwrap_or_else(|_| DATABASE_URL_DEFAULT.to_owned());
+	#[cfg(feature = "postgres")]
+	let database_url = var(DATABASE_URL_VAR).with_context(|| {
+		format!(
+			"expected the Postgres connection URL in the environment variable {}",
+			DATABASE_URL_VAR
+		)
+	})?;
 	let database_pool =
-		database_init(var(DATABASE_URL_VAR).unwrap_or_else(|_| DATABASE_URL_DEFAULT.to_owned()))
-			.with_context(|| "failed to initialize the database")?;
+		database_init(database_url).with_context(|| "failed to initialize the database")?;
+
+	// A status set with `setstatus` takes precedence over the configured ones
+	if let Some(saved_activity) = get_saved_status(&*database_pool.get()?)
+		.with_context(|| "failed to fetch the saved status")?
+		.and_then(|(status_type, status_text)| parse_activity(status_type.as_str(), status_text))
+	{
+		startup_activities = vec![saved_activity];
+	}
+
+	let http_client = HttpClient::builder()
+		.timeout(Duration::new(5, 0))
+		.build()
+		.with_context(|| "failed to build the HTTP client")?;
 
 	let songbird = Songbird::serenity();
 	let songbird_clone = Arc::clone(&songbird); // Required because the closure that uses it moves the value
 
 	let data = Arc::new(Data {
+		started_at: Instant::now(),
 		db_pool: database_pool,
 		songbird: songbird_clone,
 		lavalink: lava_client,
 		sponsor_block: sponsor_block_client,
-		queued_count: Mutex::new(HashMap::new()),
+		http_client,
+		queue_numbering: QueueNumbering::new(),
 		segment_data: Mutex::new(SegmentData::new()),
+		playback_modifiers: Mutex::new(HashMap::new()),
+		last_skips: Mutex::new(HashMap::new()),
+		skip_votes: Mutex::new(HashMap::new()),
+		last_player_updates: Mutex::new(HashMap::new()),
+		last_segment_refreshes: Mutex::new(HashMap::new()),
+		now_playing_requesters: Mutex::new(HashMap::new()),
+		paused_guilds: Mutex::new(HashSet::new()),
+		scheduled_plays: Mutex::new(HashMap::new()),
+		active_filters: Mutex::new(HashMap::new()),
+		search_fallbacks: Mutex::new(HashMap::new()),
+		play_history: Mutex::new(HashMap::new()),
+		previous_tracks: Mutex::new(HashMap::new()),
+		last_tts_messages: Mutex::new(HashMap::new()),
+		last_dice_jails: Mutex::new(HashMap::new()),
+		parsed_roll_cache: Mutex::new(ParsedRollCache::new()),
+		filtered_words,
+		status_rotation_task: Mutex::new(None),
 	});
+	let shutdown_data = Arc::clone(&data);
 	// Set the Data Arc that was given to the LavalinkHandler
 	{
-		let mut data_guard = pre_init_data_arc.lock().unwrap();
+		let mut data_guard = pre_init_data_arc
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
 		*data_guard = Some(Arc::clone(&data));
 	}
 
-	Framework::build()
+	let framework = Framework::build()
 		.options(options)
 		.token(&token)
 		.client_settings(|client_builder| {
@@ -277,13 +678,115 @@ async fn main() -> Result<(), Error> {
 				.raw_event_handler(SerenityHandler)
 				.register_songbird_with(songbird)
 		})
-		.user_data_setup(move |_ctx, _ready, _framework| Box::pin(async move { Ok(data) }))
+		.user_data_setup(move |ctx, _ready, _framework| {
+			Box::pin(async move {
+				if startup_activities.len() > 1 {
+					let task = spawn(rotate_activities(
+						ctx.clone(),
+						startup_activities,
+						status_rotation_interval,
+					));
+					*data
+						.status_rotation_task
+						.lock()
+						.unwrap_or_else(PoisonError::into_inner) = Some(task);
+				} else if let Some(activity) = startup_activities.into_iter().next() {
+					ctx.set_activity(activity).await;
+				}
+				Ok(data)
+			})
+		})
 		.build()
 		.await
-		.with_context(|| "failed to build the bot framework")?
+		.with_context(|| "failed to build the bot framework")?;
+
+	// Clean up before stopping if the process is asked to stop
+	{
+		let signal_data = Arc::clone(&shutdown_data);
+		let shard_manager = framework.shard_manager();
+		spawn(async move {
+			shutdown_signal().await;
+			println!("{}", HEADER_STYLE.paint("Shutting down..."));
+			signal_data.shut_down(shard_manager).await;
+		});
+	}
+
+	let framework_result = framework
 		.start()
 		.await
-		.with_context(|| "failed to start up")?;
+		.with_context(|| "failed to start up");
+
+	// Stop the status rotation now that the bot is no longer running
+	if let Some(task) = shutdown_data
+		.status_rotation_task
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.take()
+	{
+		task.abort();
+	}
+
+	framework_result?;
 
 	Ok(())
 }
+
+/// Waits until the process is asked to stop, with Ctrl+C or (on Unix) SIGTERM.
+async fn shutdown_signal() {
+	#[cfg(unix)]
+	{
+		let mut sigterm =
+			unix_signal(SignalKind::terminate()).expect("unable to listen for SIGTERM");
+		select! {
+			_ = ctrl_c() => {}
+			_ = sigterm.recv() => {}
+		}
+	}
+	#[cfg(not(unix))]
+	ctrl_c().await.ok();
+}
+
+/// Adds an alias to a prefix command. (slash commands can't have aliases)
+///
+/// Returns whether it was added, which it won't be if the command doesn't exist
+/// or the alias is already in use.
+fn add_command_alias(
+	options: &mut FrameworkOptions<DataArc, Error>,
+	alias: &str,
+	command_name: &str,
+) -> bool {
+	let alias = alias.to_lowercase();
+	if alias.is_empty() || alias.contains(char::is_whitespace) {
+		return false;
+	}
+
+	let commands = &mut options.prefix_options.commands;
+	let is_taken = commands.iter().any(|command_meta| {
+		command_meta
+			.command
+			.name
+			.eq_ignore_ascii_case(alias.as_str())
+			|| command_meta
+				.command
+				.aliases
+				.iter()
+				.any(|existing_alias| existing_alias.eq_ignore_ascii_case(alias.as_str()))
+	});
+	if is_taken {
+		return false;
+	}
+
+	if let Some(command_meta) = commands
+		.iter_mut()
+		.find(|command_meta| command_meta.command.name.eq_ignore_ascii_case(command_name))
+	{
+		// Command aliases have to be 'static, and these are only added once at startup,
+		// so leaking them is fine
+		let mut aliases = command_meta.command.aliases.to_vec();
+		aliases.push(Box::leak(alias.into_boxed_str()));
+		command_meta.command.aliases = Box::leak(aliases.into_boxed_slice());
+		true
+	} else {
+		false
+	}
+}
@@ -0,0 +1,244 @@
+// Uses
+use std::{
+	collections::HashMap,
+	sync::{Mutex, PoisonError},
+};
+
+use poise::serenity::model::id::GuildId;
+
+// Definitions
+/// Keeps track of the numbers that each guild's queue entries are shown with,
+/// so that a track keeps its number as the tracks ahead of it finish.
+///
+/// Tracks leave the front of the queue whenever they finish or are skipped,
+/// without Radium being told about it, so the numbering is derived from the
+/// queue length instead of being counted directly. The counts only go down
+/// when a track is taken out of the middle of the queue, which keeps every
+/// other operation on the queue consistent without any special cases. Clearing
+/// the queue starts the numbering over, the same as when the player is gone.
+#[derive(Debug, Default)]
+pub struct QueueNumbering {
+	/// The number of tracks ever added to each guild's queue.
+	added_counts: Mutex<HashMap<GuildId, usize>>,
+}
+
+impl QueueNumbering {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Records that tracks were added to the end of the guild's queue.
+	///
+	/// This has to be called for every track that actually makes it into the
+	/// queue, otherwise the numbering of the queue entries drifts.
+	pub fn record_added(&self, guild_id: GuildId, count: usize) {
+		let mut added_counts = self
+			.added_counts
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let added_count = added_counts.entry(guild_id).or_default();
+		*added_count = added_count.saturating_add(count);
+	}
+
+	/// Records that a track was removed from somewhere after the front of the
+	/// guild's queue, so that the tracks ahead of it keep their numbers.
+	pub fn record_removed(&self, guild_id: GuildId) {
+		if let Some(added_count) = self
+			.added_counts
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get_mut(&guild_id)
+		{
+			*added_count = added_count.saturating_sub(1);
+		}
+	}
+
+	/// Gets how many tracks have left the front of the guild's queue, which is
+	/// the offset that queue entries are numbered from.
+	pub fn entry_offset(&self, guild_id: GuildId, queue_len: usize) -> usize {
+		self.added_counts
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&guild_id)
+			.map_or(0, |added_count| added_count.saturating_sub(queue_len))
+	}
+
+	/// Forgets the guild's numbering entirely, for when its queue is cleared or
+	/// its player is gone.
+	pub fn forget(&self, guild_id: GuildId) {
+		self.added_counts
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&guild_id);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const GUILD_ID: GuildId = GuildId(1);
+
+	/// A stand-in for a guild's queue, which numbers its entries the same way
+	/// the `queue` command does.
+	struct Queue<'a> {
+		numbering: &'a QueueNumbering,
+		tracks: Vec<&'static str>,
+	}
+
+	impl<'a> Queue<'a> {
+		fn new(numbering: &'a QueueNumbering) -> Self {
+			Self {
+				numbering,
+				tracks: Vec::new(),
+			}
+		}
+
+		fn add(&mut self, track: &'static str) {
+			self.tracks.push(track);
+			self.numbering.record_added(GUILD_ID, 1);
+		}
+
+		/// The current track finishing or being skipped, which Radium isn't
+		/// told about.
+		fn advance(&mut self) {
+			self.tracks.remove(0);
+		}
+
+		fn remove(&mut self, index: usize) {
+			self.tracks.remove(index);
+			self.numbering.record_removed(GUILD_ID);
+		}
+
+		fn clear(&mut self) {
+			self.tracks.clear();
+			self.numbering.forget(GUILD_ID);
+		}
+
+		fn numbered(&self) -> Vec<(usize, &'static str)> {
+			let entry_offset = self.numbering.entry_offset(GUILD_ID, self.tracks.len());
+			self.tracks
+				.iter()
+				.enumerate()
+				.map(|(index, track)| (entry_offset + index + 1, *track))
+				.collect()
+		}
+	}
+
+	#[test]
+	fn numbering_survives_a_long_session() {
+		let numbering = QueueNumbering::new();
+		let mut queue = Queue::new(&numbering);
+
+		queue.add("a");
+		queue.add("b");
+		queue.add("c");
+		assert_eq!(queue.numbered(), vec![(1, "a"), (2, "b"), (3, "c")]);
+
+		// Tracks keep their numbers as the ones ahead of them finish
+		queue.advance();
+		assert_eq!(queue.numbered(), vec![(2, "b"), (3, "c")]);
+
+		queue.add("d");
+		queue.advance();
+		assert_eq!(queue.numbered(), vec![(3, "c"), (4, "d")]);
+
+		// Removing a track from the middle only renumbers the tracks after it
+		queue.add("e");
+		queue.add("f");
+		queue.remove(2);
+		assert_eq!(queue.numbered(), vec![(3, "c"), (4, "d"), (5, "f")]);
+
+		// Numbering carries on from where it was after the queue empties
+		queue.advance();
+		queue.advance();
+		queue.advance();
+		assert_eq!(queue.numbered(), vec![]);
+		queue.add("g");
+		assert_eq!(queue.numbered(), vec![(6, "g")]);
+
+		// Clearing the queue starts the numbering over
+		queue.add("h");
+		queue.clear();
+		queue.add("i");
+		assert_eq!(queue.numbered(), vec![(1, "i")]);
+	}
+
+	#[test]
+	fn entry_offset_counts_tracks_that_left_the_front() {
+		let numbering = QueueNumbering::new();
+		assert_eq!(numbering.entry_offset(GUILD_ID, 0), 0);
+
+		numbering.record_added(GUILD_ID, 3);
+		assert_eq!(numbering.entry_offset(GUILD_ID, 3), 0);
+		assert_eq!(numbering.entry_offset(GUILD_ID, 1), 2);
+		assert_eq!(numbering.entry_offset(GUILD_ID, 0), 3);
+
+		// The queue can't be longer than what was added, but it shouldn't underflow
+		assert_eq!(numbering.entry_offset(GUILD_ID, 5), 0);
+	}
+
+	#[test]
+	fn record_removed_keeps_earlier_tracks_numbered() {
+		let numbering = QueueNumbering::new();
+		numbering.record_added(GUILD_ID, 2);
+		numbering.record_removed(GUILD_ID);
+		assert_eq!(numbering.entry_offset(GUILD_ID, 1), 0);
+
+		// Removing more than was added doesn't underflow
+		numbering.record_removed(GUILD_ID);
+		numbering.record_removed(GUILD_ID);
+		assert_eq!(numbering.entry_offset(GUILD_ID, 0), 0);
+
+		// Removing from a guild that hasn't queued anything doesn't start counting for
+		// it
+		numbering.record_removed(GuildId(2));
+		numbering.record_added(GuildId(2), 1);
+		assert_eq!(numbering.entry_offset(GuildId(2), 0), 1);
+	}
+
+	#[test]
+	fn forget_starts_numbering_over() {
+		let numbering = QueueNumbering::new();
+		numbering.record_added(GUILD_ID, 5);
+		numbering.record_added(GuildId(2), 5);
+		numbering.forget(GUILD_ID);
+		assert_eq!(numbering.entry_offset(GUILD_ID, 0), 0);
+
+		// Other guilds are left alone
+		assert_eq!(numbering.entry_offset(GuildId(2), 0), 5);
+	}
+
+	#[test]
+	fn numbering_survives_interleaved_operations() {
+		let numbering = QueueNumbering::new();
+		let mut queue = Queue::new(&numbering);
+
+		queue.add("a");
+		queue.add("b");
+		queue.remove(1);
+		queue.add("c");
+		queue.advance();
+		assert_eq!(queue.numbered(), vec![(2, "c")]);
+
+		queue.clear();
+		queue.add("d");
+		queue.add("e");
+		queue.remove(1);
+		queue.add("f");
+		assert_eq!(queue.numbered(), vec![(1, "d"), (2, "f")]);
+
+		queue.advance();
+		queue.add("g");
+		queue.remove(1);
+		queue.add("h");
+		assert_eq!(queue.numbered(), vec![(2, "f"), (3, "h")]);
+
+		queue.advance();
+		queue.advance();
+		queue.clear();
+		queue.add("i");
+		assert_eq!(queue.numbered(), vec![(1, "i")]);
+	}
+}
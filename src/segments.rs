@@ -1,10 +1,17 @@
 // Uses
 use std::collections::HashMap;
 
-use lavalink_rs::model::GuildId;
+use lavalink_rs::model::{GuildId, Info};
 use lru::LruCache;
+use sponsor_block::{Action, ActionableSegment, Client as SponsorBlockClient, Segment};
+use url::Url;
 
-use crate::constants::VIDEO_SEGMENT_CACHE_SIZE;
+use crate::constants::{ACCEPTED_CATEGORIES, MILLIS_PER_SECOND_F32, VIDEO_SEGMENT_CACHE_SIZE};
+
+// Constants
+const SEGMENT_COMBINE_THRESHOLD: f32 = 0.35; // The maximum distance between two segments to combine
+const SEGMENT_LENGTH_THRESHOLD: f32 = 0.5; // The minimum length a segment should be
+const DURATION_DISCARD_THRESHOLD: f32 = 1.25; // The maximum difference from the submission video length to accept
 
 // Definitions
 #[derive(Debug)]
@@ -43,6 +50,11 @@ pub struct SkipSegment {
 	// mid-playback skipping
 	pub is_at_start: bool,
 	pub is_at_end: bool,
+	// Whether SponsorBlock says the segment should be muted rather than skipped - it's
+	// only muted in guilds that prefer that
+	pub is_mute: bool,
+	// Whether the segment is a non-music section, which guilds can choose not to skip
+	pub is_non_music: bool,
 }
 
 impl SkipSegment {
@@ -50,3 +62,393 @@ impl SkipSegment {
 		self.is_at_start || self.is_at_end
 	}
 }
+
+// Functions
+/// Fetches the SponsorBlock segments for a track and works out which parts of
+/// it should be skipped.
+///
+/// Returns [`None`] if the track isn't a YouTube video, the segments couldn't
+/// be fetched, or there's nothing worth skipping.
+pub async fn fetch_skip_segments(
+	sponsor_block: &SponsorBlockClient,
+	info: &Info,
+) -> Option<Vec<SkipSegment>> {
+	// No point if it's a stream
+	if !info.is_seekable {
+		return None;
+	}
+
+	let parsed_uri = Url::parse(&info.uri)
+		.expect("unable to parse track info URI when it should have been guaranteed to be valid");
+	let video_id = get_youtube_video_id(&parsed_uri)?;
+	let segments = sponsor_block
+		.fetch_segments(&video_id, ACCEPTED_CATEGORIES)
+		.await
+		.ok()?;
+
+	process_segments(&segments, info.length as f32 / MILLIS_PER_SECOND_F32)
+}
+
+/// Works out which parts of a track should be skipped, from its SponsorBlock
+/// segments and its duration in seconds.
+///
+/// Segments that are close together are combined, and ones that are too short
+/// to be worth skipping are left out. Returns [`None`] if there's nothing worth
+/// skipping.
+pub fn process_segments(segments: &[Segment], track_duration: f32) -> Option<Vec<SkipSegment>> {
+	// Get the pertinent information and filter out segments that may be
+	// incorrect (submitted before some edit to the video length that
+	// invalidates the timecodes)
+	let mut skip_timecodes = segments
+		.iter()
+		.filter(|s| {
+			// Because some segments were added before video durations started
+			// being recorded
+			if let Some(video_duration_upon_submission) = s.video_duration_on_submission {
+				(video_duration_upon_submission - track_duration).abs()
+					<= DURATION_DISCARD_THRESHOLD
+			} else {
+				true
+			}
+		})
+		.filter_map(|s| match &s.segment {
+			ActionableSegment::Sponsor(t)
+			| ActionableSegment::UnpaidSelfPromotion(t)
+			| ActionableSegment::InteractionReminder(t)
+			| ActionableSegment::IntermissionIntroAnimation(t)
+			| ActionableSegment::EndcardsCredits(t)
+			| ActionableSegment::NonMusic(t) => Some(SkipSegment {
+				start: t.start,
+				end: t.end,
+				is_at_start: false,
+				is_at_end: false,
+				is_mute: matches!(s.action_type, Action::Mute),
+				is_non_music: matches!(s.segment, ActionableSegment::NonMusic(_)),
+			}),
+			ActionableSegment::Highlight(_)
+			| ActionableSegment::PreviewRecap(_)
+			| ActionableSegment::FillerTangent(_) => None,
+		})
+		.collect::<Vec<_>>();
+	// Ensure the segments are ordered by their time in the content
+	skip_timecodes.sort_unstable_by_key(|t| (t.start * MILLIS_PER_SECOND_F32) as u32);
+	// Combine segments that are close together, or that overlap - non-music
+	// segments are kept apart from the rest, since they're cached for every guild
+	// at once and some guilds leave them out
+	let combined_timecodes = combine_segments(skip_timecodes, true);
+	// Remove segments that are too short to be worth skipping with the Lavalink
+	// seek delay
+	let mut skip_timecodes = combined_timecodes
+		.into_iter()
+		.filter(|t| t.end - t.start >= SEGMENT_LENGTH_THRESHOLD)
+		.collect::<Vec<_>>();
+
+	// Final processing
+	let skip_timecodes_len = skip_timecodes.len();
+	if skip_timecodes_len > 0 {
+		// Mark the start segment if there's a segment right at the beginning
+		if skip_timecodes[0].start <= SEGMENT_COMBINE_THRESHOLD {
+			skip_timecodes[0].is_at_start = true;
+		}
+		// Set the end segment's is_at_end value if it's at the very end
+		if (track_duration - skip_timecodes[skip_timecodes_len - 1].end).abs()
+			<= SEGMENT_COMBINE_THRESHOLD
+		{
+			skip_timecodes[skip_timecodes_len - 1].is_at_end = true;
+		}
+	}
+
+	(!skip_timecodes.is_empty()).then(|| skip_timecodes)
+}
+
+/// Leaves out the segments that a guild doesn't want skipped.
+///
+/// Segments are cached for every guild at once, so this is done whenever
+/// they're about to be used rather than when they're fetched. Returns [`None`]
+/// if there's nothing left to skip.
+pub fn segments_for_guild(
+	segments: Vec<SkipSegment>,
+	skip_non_music: bool,
+) -> Option<Vec<SkipSegment>> {
+	let segments = if skip_non_music {
+		// Non-music segments can be combined with the rest now that they're being
+		// skipped too
+		combine_segments(segments, false)
+	} else {
+		segments
+			.into_iter()
+			.filter(|segment| !segment.is_non_music)
+			.collect::<Vec<_>>()
+	};
+
+	(!segments.is_empty()).then(|| segments)
+}
+
+/// Combines segments that are close together, or that overlap, from segments
+/// that are ordered by their start times.
+///
+/// Segments to mute are never combined with ones to skip. Non-music segments
+/// are only combined with other segments if `keep_non_music_apart` is `false`.
+fn combine_segments(segments: Vec<SkipSegment>, keep_non_music_apart: bool) -> Vec<SkipSegment> {
+	// Each segment is combined into the last one kept, rather than its neighbour,
+	// since a long segment can overlap several after it
+	let mut combined_segments: Vec<SkipSegment> = Vec::with_capacity(segments.len());
+	for segment in segments {
+		if let Some(previous) = combined_segments.last_mut().filter(|previous| {
+			segment.start - previous.end <= SEGMENT_COMBINE_THRESHOLD
+				&& segment.is_mute == previous.is_mute
+				&& (!keep_non_music_apart || segment.is_non_music == previous.is_non_music)
+		}) {
+			// A segment that's entirely inside the previous one doesn't extend it
+			previous.end = previous.end.max(segment.end);
+			previous.is_at_end |= segment.is_at_end;
+			previous.is_non_music &= segment.is_non_music;
+		} else {
+			combined_segments.push(segment);
+		}
+	}
+	combined_segments
+}
+
+/// Gets the ID of a YouTube video from its URL, if it is one.
+pub fn get_youtube_video_id(uri: &Url) -> Option<String> {
+	if let Some(host) = uri.host_str() {
+		if host.ends_with("youtube.com") {
+			if let Some(query) = uri.query() {
+				let query_parameters = query.split('&');
+				for parameter in query_parameters {
+					if let Some(stripped) = parameter.strip_prefix("v=") {
+						return Some(stripped.to_owned());
+					}
+				}
+				None
+			} else {
+				None
+			}
+		} else if host.ends_with("youtu.be") {
+			Some(
+				uri.path_segments()
+					.expect("unable to parse URI as a proper path")
+					.last()
+					.expect("unable to find the last path segment of URI")
+					.to_owned(),
+			)
+		} else {
+			None
+		}
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sponsor_block::TimeSection;
+
+	use super::*;
+
+	const TRACK_DURATION: f32 = 100.0;
+
+	/// Builds a segment the way SponsorBlock would return it.
+	fn segment(
+		segment: ActionableSegment,
+		action_type: Action,
+		video_duration_on_submission: Option<f32>,
+	) -> Segment {
+		Segment {
+			segment,
+			action_type,
+			uuid: String::new(),
+			locked: false,
+			votes: 0,
+			video_duration_on_submission,
+			additional_info: None,
+		}
+	}
+
+	fn sponsor(start: f32, end: f32) -> Segment {
+		segment(
+			ActionableSegment::Sponsor(TimeSection { start, end }),
+			Action::Skip,
+			Some(TRACK_DURATION),
+		)
+	}
+
+	/// Processes the segments, returning the start and end of each one to skip.
+	fn process(segments: &[Segment]) -> Vec<(f32, f32)> {
+		process_segments(segments, TRACK_DURATION)
+			.unwrap_or_default()
+			.into_iter()
+			.map(|segment| (segment.start, segment.end))
+			.collect()
+	}
+
+	#[test]
+	fn close_segments_are_combined() {
+		assert_eq!(
+			process(&[sponsor(10.0, 20.0), sponsor(20.25, 30.0)]),
+			vec![(10.0, 30.0)]
+		);
+	}
+
+	#[test]
+	fn consecutive_close_segments_are_combined() {
+		assert_eq!(
+			process(&[
+				sponsor(10.0, 20.0),
+				sponsor(20.25, 30.0),
+				sponsor(30.25, 40.0)
+			]),
+			vec![(10.0, 40.0)]
+		);
+	}
+
+	#[test]
+	fn segments_are_combined_in_order() {
+		assert_eq!(
+			process(&[sponsor(20.25, 30.0), sponsor(10.0, 20.0)]),
+			vec![(10.0, 30.0)]
+		);
+	}
+
+	#[test]
+	fn overlapping_segments_are_combined() {
+		assert_eq!(
+			process(&[sponsor(10.0, 20.0), sponsor(15.0, 25.0)]),
+			vec![(10.0, 25.0)]
+		);
+		// A long segment can overlap several after it, including ones it contains
+		assert_eq!(
+			process(&[
+				sponsor(10.0, 30.0),
+				sponsor(15.0, 20.0),
+				sponsor(25.0, 40.0)
+			]),
+			vec![(10.0, 40.0)]
+		);
+	}
+
+	#[test]
+	fn segments_at_the_threshold_are_combined() {
+		// Doubling the threshold is exact, so the gap is exactly the threshold
+		assert_eq!(
+			process(&[
+				sponsor(SEGMENT_COMBINE_THRESHOLD / 2.0, SEGMENT_COMBINE_THRESHOLD),
+				sponsor(SEGMENT_COMBINE_THRESHOLD * 2.0, 10.0)
+			]),
+			vec![(SEGMENT_COMBINE_THRESHOLD / 2.0, 10.0)]
+		);
+	}
+
+	#[test]
+	fn segments_past_the_threshold_are_kept_apart() {
+		assert_eq!(
+			process(&[sponsor(10.0, 20.0), sponsor(20.5, 30.0)]),
+			vec![(10.0, 20.0), (20.5, 30.0)]
+		);
+	}
+
+	#[test]
+	fn muted_segments_are_kept_apart_from_skipped_ones() {
+		let muted = segment(
+			ActionableSegment::Sponsor(TimeSection {
+				start: 20.0,
+				end: 30.0,
+			}),
+			Action::Mute,
+			Some(TRACK_DURATION),
+		);
+		let processed = process_segments(&[sponsor(10.0, 20.0), muted], TRACK_DURATION).unwrap();
+		assert_eq!(processed.len(), 2);
+		assert!(!processed[0].is_mute);
+		assert!(processed[1].is_mute);
+	}
+
+	#[test]
+	fn short_segments_are_left_out() {
+		assert_eq!(
+			process(&[sponsor(10.0, 10.25), sponsor(50.0, 60.0)]),
+			vec![(50.0, 60.0)]
+		);
+		assert!(process_segments(&[sponsor(10.0, 10.25)], TRACK_DURATION).is_none());
+	}
+
+	#[test]
+	fn segments_at_the_ends_are_marked() {
+		let processed = process_segments(
+			&[
+				sponsor(0.25, 10.0),
+				sponsor(50.0, 60.0),
+				sponsor(90.0, TRACK_DURATION - 0.25),
+			],
+			TRACK_DURATION,
+		)
+		.unwrap();
+		assert!(processed[0].is_at_start && !processed[0].is_at_end);
+		assert!(!processed[1].is_at_an_end());
+		assert!(!processed[2].is_at_start && processed[2].is_at_end);
+	}
+
+	#[test]
+	fn segments_near_the_ends_are_not_marked() {
+		let processed = process_segments(
+			&[sponsor(1.0, 10.0), sponsor(90.0, TRACK_DURATION - 1.0)],
+			TRACK_DURATION,
+		)
+		.unwrap();
+		assert!(processed.iter().all(|segment| !segment.is_at_an_end()));
+	}
+
+	#[test]
+	fn segments_for_a_different_video_length_are_discarded() {
+		let with_duration = |video_duration_on_submission| {
+			segment(
+				ActionableSegment::Sponsor(TimeSection {
+					start: 10.0,
+					end: 20.0,
+				}),
+				Action::Skip,
+				video_duration_on_submission,
+			)
+		};
+		assert_eq!(
+			process(&[with_duration(Some(
+				TRACK_DURATION + DURATION_DISCARD_THRESHOLD
+			))]),
+			vec![(10.0, 20.0)]
+		);
+		assert!(process(&[with_duration(Some(TRACK_DURATION + 2.0))]).is_empty());
+		assert!(process(&[with_duration(Some(TRACK_DURATION - 2.0))]).is_empty());
+		// Older segments don't have a duration, and are trusted
+		assert_eq!(process(&[with_duration(None)]), vec![(10.0, 20.0)]);
+	}
+
+	#[test]
+	fn non_music_segments_are_combined_for_guilds_that_skip_them() {
+		let non_music = segment(
+			ActionableSegment::NonMusic(TimeSection {
+				start: 20.25,
+				end: 30.0,
+			}),
+			Action::Skip,
+			Some(TRACK_DURATION),
+		);
+		let processed =
+			process_segments(&[sponsor(10.0, 20.0), non_music], TRACK_DURATION).unwrap();
+		assert_eq!(processed.len(), 2);
+
+		let skipping_non_music = segments_for_guild(processed.clone(), true).unwrap();
+		assert_eq!(skipping_non_music.len(), 1);
+		assert_eq!(
+			(skipping_non_music[0].start, skipping_non_music[0].end),
+			(10.0, 30.0)
+		);
+
+		let playing_non_music = segments_for_guild(processed, false).unwrap();
+		assert_eq!(playing_non_music.len(), 1);
+		assert_eq!(
+			(playing_non_music[0].start, playing_non_music[0].end),
+			(10.0, 20.0)
+		);
+	}
+}
@@ -1,12 +1,31 @@
 // Uses
+use std::{borrow::Cow, future::Future, mem::take, panic::AssertUnwindSafe, time::Duration};
+
 use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use futures::FutureExt;
 use lazy_static::lazy_static;
-use poise::{send_reply, serenity::builder::CreateEmbed, ReplyHandle};
+use poise::{
+	send_reply,
+	serenity::{
+		builder::{CreateComponents, CreateEmbed},
+		http::AttachmentType,
+		model::{
+			gateway::Activity,
+			interactions::{message_component::ButtonStyle, InteractionResponseType},
+		},
+	},
+	ReplyHandle,
+};
 use regex::Regex;
+use tokio::time::sleep;
 
 use crate::{
 	constants::{
+		HOURS_PER_DAY,
 		MAIN_COLOUR,
+		MILLIS_PER_DAY,
 		MILLIS_PER_HOUR,
 		MILLIS_PER_MINUTE,
 		MILLIS_PER_SECOND,
@@ -16,8 +35,25 @@ use crate::{
 		SECONDS_PER_MINUTE_F32,
 	},
 	PoiseContext,
+	SerenityContext,
 };
 
+// Constants
+const CONFIRM_BUTTON_ID: &str = "confirm";
+const CANCEL_BUTTON_ID: &str = "cancel";
+const PREVIOUS_PAGE_BUTTON_ID: &str = "previous_page";
+const NEXT_PAGE_BUTTON_ID: &str = "next_page";
+const JUMP_PAGE_BUTTON_ID: &str = "jump_page";
+const UNEXPECTED_ERROR_MESSAGE: &str = "An unexpected error occurred.";
+
+// Definitions
+/// An extra button for a paginated reply, that jumps straight to a particular
+/// page.
+pub struct PageJump<'a> {
+	pub label: &'a str,
+	pub page: usize,
+}
+
 // Functions
 pub async fn reply<S: ToString>(
 	ctx: PoiseContext<'_>,
@@ -28,6 +64,22 @@ pub async fn reply<S: ToString>(
 		.with_context(|| "failed to send message")
 }
 
+/// Runs the body of a command, letting the user know if it panics instead of
+/// leaving them without a reply.
+///
+/// The panic itself is logged by the panic hook.
+pub async fn catch_panic<F>(ctx: PoiseContext<'_>, command: F) -> Result<(), crate::Error>
+where
+	F: Future<Output = Result<(), crate::Error>>,
+{
+	if let Ok(result) = AssertUnwindSafe(command).catch_unwind().await {
+		result
+	} else {
+		reply(ctx, UNEXPECTED_ERROR_MESSAGE).await?;
+		Ok(())
+	}
+}
+
 pub async fn reply_plain<S: ToString>(
 	ctx: PoiseContext<'_>,
 	msg: S,
@@ -46,6 +98,267 @@ pub async fn reply_embed(
 		.with_context(|| "failed to send message")
 }
 
+pub async fn reply_file<S: ToString>(
+	ctx: PoiseContext<'_>,
+	msg: S,
+	filename: &str,
+	data: Vec<u8>,
+) -> Result<Option<ReplyHandle<'_>>, Error> {
+	send_reply(ctx, |m| {
+		m.content(msg.to_string())
+			.attachment(AttachmentType::Bytes {
+				data: Cow::Owned(data),
+				filename: filename.to_owned(),
+			})
+	})
+	.await
+	.with_context(|| "failed to send message")
+}
+
+/// Asks the user to confirm something with a pair of buttons, waiting up to
+/// `timeout` for an answer.
+///
+/// Only the user who invoked the command can answer. Returns whether they
+/// confirmed - not answering in time counts as cancelling.
+pub async fn confirm<S: ToString>(
+	ctx: PoiseContext<'_>,
+	prompt: S,
+	confirm_label: &str,
+	timeout: Duration,
+) -> Result<bool, Error> {
+	let reply_handle = send_reply(ctx, |m| {
+		m.embed(|e| e.colour(MAIN_COLOUR).description(prompt))
+			.components(|c| {
+				c.create_action_row(|r| {
+					r.create_button(|b| {
+						b.custom_id(CONFIRM_BUTTON_ID)
+							.label(confirm_label)
+							.style(ButtonStyle::Danger)
+					})
+					.create_button(|b| {
+						b.custom_id(CANCEL_BUTTON_ID)
+							.label("Cancel")
+							.style(ButtonStyle::Secondary)
+					})
+				})
+			})
+	})
+	.await
+	.with_context(|| "failed to send message")?;
+	let mut message = if let Some(reply_handle) = reply_handle {
+		reply_handle
+			.message()
+			.await
+			.with_context(|| "failed to get the sent message")?
+	} else {
+		return Ok(false);
+	};
+
+	let interaction = message
+		.await_component_interaction(ctx.discord())
+		.author_id(ctx.author().id)
+		.timeout(timeout)
+		.await;
+	let confirmed = interaction
+		.as_ref()
+		.map_or(false, |i| i.data.custom_id == CONFIRM_BUTTON_ID);
+
+	// Remove the buttons so they can't be used again
+	if let Some(interaction) = interaction {
+		interaction
+			.create_interaction_response(ctx.discord(), |r| {
+				r.kind(InteractionResponseType::UpdateMessage)
+					.interaction_response_data(|d| d.components(|c| c))
+			})
+			.await
+			.with_context(|| "failed to respond to the button press")?;
+	} else {
+		message
+			.edit(ctx.discord(), |m| m.components(|c| c))
+			.await
+			.with_context(|| "failed to remove the buttons")?;
+	}
+
+	Ok(confirmed)
+}
+
+/// Sends a reply made up of several pages of embed descriptions, with buttons
+/// to move between them.
+///
+/// Every page shares the same title, and the footer (if any) is shown after the
+/// page number. Only the user who invoked the command can change pages, and
+/// the buttons are removed once nobody has pressed one for `timeout`.
+pub async fn reply_paginated(
+	ctx: PoiseContext<'_>,
+	title: &str,
+	pages: &[String],
+	footer: Option<&str>,
+	jump: Option<PageJump<'_>>,
+	timeout: Duration,
+) -> Result<(), Error> {
+	// There's nothing to page through, so don't bother with the buttons
+	if pages.len() <= 1 {
+		reply_embed(ctx, |e| {
+			e.title(title)
+				.description(pages.first().map_or("", String::as_str));
+			if let Some(footer) = footer {
+				e.footer(|f| f.text(footer));
+			}
+			e
+		})
+		.await?;
+		return Ok(());
+	}
+
+	let mut page = 0;
+	let reply_handle = send_reply(ctx, |m| {
+		m.embed(|e| create_page_embed(e, title, pages, page, footer))
+			.components(|c| create_page_buttons(c, pages.len(), page, jump.as_ref()))
+	})
+	.await
+	.with_context(|| "failed to send message")?;
+	let mut message = if let Some(reply_handle) = reply_handle {
+		reply_handle
+			.message()
+			.await
+			.with_context(|| "failed to get the sent message")?
+	} else {
+		return Ok(());
+	};
+
+	while let Some(interaction) = message
+		.await_component_interaction(ctx.discord())
+		.author_id(ctx.author().id)
+		.timeout(timeout)
+		.await
+	{
+		page = match interaction.data.custom_id.as_str() {
+			PREVIOUS_PAGE_BUTTON_ID => page.saturating_sub(1),
+			NEXT_PAGE_BUTTON_ID => (page + 1).min(pages.len() - 1),
+			JUMP_PAGE_BUTTON_ID => jump
+				.as_ref()
+				.map_or(page, |jump| jump.page.min(pages.len() - 1)),
+			_ => page,
+		};
+		interaction
+			.create_interaction_response(ctx.discord(), |r| {
+				r.kind(InteractionResponseType::UpdateMessage)
+					.interaction_response_data(|d| {
+						d.create_embed(|e| create_page_embed(e, title, pages, page, footer))
+							.components(|c| {
+								create_page_buttons(c, pages.len(), page, jump.as_ref())
+							})
+					})
+			})
+			.await
+			.with_context(|| "failed to respond to the button press")?;
+	}
+
+	// Remove the buttons once they've gone unused for a while
+	message
+		.edit(ctx.discord(), |m| m.components(|c| c))
+		.await
+		.with_context(|| "failed to remove the buttons")?;
+
+	Ok(())
+}
+
+fn create_page_embed<'a>(
+	e: &'a mut CreateEmbed,
+	title: &str,
+	pages: &[String],
+	page: usize,
+	footer: Option<&str>,
+) -> &'a mut CreateEmbed {
+	let page_number = format!("Page {} of {}", page + 1, pages.len());
+	e.colour(MAIN_COLOUR)
+		.title(title)
+		.description(&pages[page])
+		.footer(|f| {
+			f.text(if let Some(footer) = footer {
+				format!("{} \u{2022} {}", page_number, footer)
+			} else {
+				page_number
+			})
+		})
+}
+
+fn create_page_buttons<'a>(
+	c: &'a mut CreateComponents,
+	pages_len: usize,
+	page: usize,
+	jump: Option<&PageJump<'_>>,
+) -> &'a mut CreateComponents {
+	c.create_action_row(|r| {
+		r.create_button(|b| {
+			b.custom_id(PREVIOUS_PAGE_BUTTON_ID)
+				.label("Previous")
+				.style(ButtonStyle::Secondary)
+				.disabled(page == 0)
+		})
+		.create_button(|b| {
+			b.custom_id(NEXT_PAGE_BUTTON_ID)
+				.label("Next")
+				.style(ButtonStyle::Secondary)
+				.disabled(page + 1 >= pages_len)
+		});
+		if let Some(jump) = jump {
+			r.create_button(|b| {
+				b.custom_id(JUMP_PAGE_BUTTON_ID)
+					.label(jump.label)
+					.style(ButtonStyle::Primary)
+					.disabled(page == jump.page)
+			});
+		}
+		r
+	})
+}
+
+/// Splits text into pages of at most `max_len` bytes, breaking between lines
+/// wherever possible.
+pub fn split_into_pages(text: &str, max_len: usize) -> Vec<String> {
+	let mut pages = Vec::new();
+	let mut page = String::new();
+	for line in text.lines() {
+		if !page.is_empty() && page.len() + 1 + line.len() > max_len {
+			pages.push(take(&mut page));
+		}
+		// Lines that don't fit on a page of their own have to be broken up
+		let mut line = line;
+		while line.len() > max_len {
+			let mut split_index = max_len;
+			while !line.is_char_boundary(split_index) {
+				split_index -= 1;
+			}
+			pages.push(line[..split_index].to_owned());
+			line = &line[split_index..];
+		}
+		// Pages don't start with blank lines
+		if !page.is_empty() {
+			page.push('\n');
+		}
+		page.push_str(line);
+	}
+	if !page.is_empty() {
+		pages.push(page);
+	}
+
+	pages
+}
+
+/// Checks whether some text contains any of the filtered words.
+///
+/// Words are matched whole and without regard to case, so `Shitake` isn't
+/// caught by `shit`. The filtered words are expected to be lowercase already.
+pub fn contains_filtered_word(text: &str, filtered_words: &[String]) -> bool {
+	text.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.any(|word| {
+			let word = word.to_lowercase();
+			filtered_words.iter().any(|filtered| *filtered == word)
+		})
+}
+
 /// Escapes a string for use in Discord, escaping all Markdown characters.
 ///
 /// Square brackets can't be escaped with slashes for some reason, so they're
@@ -101,6 +414,28 @@ pub fn display_timecode(millis: u64) -> String {
 	}
 }
 
+/// Displays a length of time in words, eg. `2d 5h 3m 12s`.
+///
+/// Units are left off the front while they're zero, so short lengths stay
+/// short.
+pub fn display_duration(millis: u64) -> String {
+	let units = [
+		(millis / MILLIS_PER_DAY, 'd'),
+		((millis / MILLIS_PER_HOUR) % HOURS_PER_DAY, 'h'),
+		((millis / MILLIS_PER_MINUTE) % MINUTES_PER_HOUR, 'm'),
+		((millis / MILLIS_PER_SECOND) % SECONDS_PER_MINUTE, 's'),
+	];
+	let first_unit = units
+		.iter()
+		.position(|(value, _)| *value > 0)
+		.unwrap_or(units.len() - 1);
+	units[first_unit..]
+		.iter()
+		.map(|(value, unit)| format!("{}{}", value, unit))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
 pub fn display_timecode_f32(seconds: f32) -> String {
 	if seconds >= SECONDS_PER_HOUR_F32 {
 		format!(
@@ -117,3 +452,54 @@ pub fn display_timecode_f32(seconds: f32) -> String {
 		)
 	}
 }
+
+/// Displays a point in time in a guild's timezone, eg. `Feb 7, 14:05 EST`.
+///
+/// `timezone` is the guild's timezone setting, and falls back to UTC if it
+/// isn't recognized.
+pub fn display_time(time: DateTime<Utc>, timezone: &str) -> String {
+	let timezone = timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+	time.with_timezone(&timezone)
+		.format("%b %-d, %H:%M %Z")
+		.to_string()
+}
+
+/// Parses a user-provided on/off toggle.
+///
+/// Returns [`None`] if the value isn't recognized.
+pub fn parse_toggle(value: &str) -> Option<bool> {
+	match value.to_lowercase().trim() {
+		"on" | "yes" | "y" | "true" | "enable" | "enabled" => Some(true),
+		"off" | "no" | "n" | "false" | "disable" | "disabled" => Some(false),
+		_ => None,
+	}
+}
+
+/// Parses an activity type name (eg. `listening`) and the activity text into
+/// an [`Activity`].
+///
+/// Returns [`None`] if the activity type isn't recognized.
+pub fn parse_activity(activity_type: &str, text: String) -> Option<Activity> {
+	match activity_type.to_lowercase().trim() {
+		"playing" | "play" | "p" => Some(Activity::playing(text)), // Playing ...
+		"listening" | "listen" | "l" => Some(Activity::listening(text)), // Listening to ...
+		"watching" | "watch" | "w" => Some(Activity::watching(text)), // Watching ...
+		"competing" | "compete" | "c" => Some(Activity::competing(text)), // Competing in ...
+		_ => None,
+	}
+}
+
+/// Cycles through a list of activities forever, showing each one for
+/// `interval`.
+///
+/// This is meant to be spawned as a background task, and aborted on shutdown.
+pub async fn rotate_activities(
+	ctx: SerenityContext,
+	activities: Vec<Activity>,
+	interval: Duration,
+) {
+	for activity in activities.iter().cycle() {
+		ctx.set_activity(activity.clone()).await;
+		sleep(interval).await;
+	}
+}